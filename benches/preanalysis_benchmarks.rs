@@ -1,5 +1,6 @@
 extern crate binary_type_inference;
 
+use binary_type_inference::analysis::reaching_definitions::ClobberPolicy;
 use binary_type_inference::node_context::register_map;
 use binary_type_inference::util;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
@@ -43,7 +44,7 @@ fn run_reaching_def(ir_name: &str, bin_name: &str) {
             .collect(),
     );
 
-    register_map::run_analysis(&project, &graph);
+    register_map::run_analysis(&project, &graph, ClobberPolicy::default());
 }
 
 fn reaching_def_performance_ls() {
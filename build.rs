@@ -4,6 +4,9 @@ fn main() -> Result<()> {
     // TODO(Ian): This doesnt actually guarentee correct naming per the issue but at least useful for debugging
     config.type_attribute(".", "#[derive(serde::Serialize)]");
     config.type_attribute(".", "#[serde(rename_all = \"camelCase\")]");
+    config.type_attribute(".", "#[derive(schemars::JsonSchema)]");
+    // Maps are encoded in iteration order, so use ordered maps to keep the output deterministic
+    config.btree_map(&["."]);
     config.compile_protos(
         &[
             "data_formats/ctypes.proto",
@@ -0,0 +1,24 @@
+#![no_main]
+use binary_type_inference::{
+    constraint_dsl::{parse_additional_constraints, parse_tids},
+    constraints::{parse_constraint_set, parse_derived_type_variable},
+    solver::constraint_graph::parse_edges,
+};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_INPUT_LEN: usize = 1 << 14;
+
+// Runs the textual constraint, constraint document, and FSA edge parsers.
+fuzz_target!(|data: &[u8]| {
+    if data.len() > MAX_INPUT_LEN {
+        return;
+    }
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = parse_derived_type_variable(text);
+        let _ = parse_constraint_set(text);
+        let _ = parse_edges(text);
+        let _ = parse_additional_constraints(text);
+        let _ = parse_tids(text);
+    }
+});
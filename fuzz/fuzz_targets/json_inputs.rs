@@ -0,0 +1,39 @@
+#![no_main]
+use binary_type_inference::{
+    constraints::AdditionalConstraint,
+    inference_job::{InferenceParsing, JsonDef},
+    solver::type_lattice::LatticeDefinition,
+};
+use cwe_checker_lib::intermediate_representation::Tid;
+use libfuzzer_sys::fuzz_target;
+
+const MAX_INPUT_LEN: usize = 1 << 14;
+
+// Building a lattice's tables is cubic in the number of elements.
+const MAX_LATTICE_RELATIONS: usize = 32;
+
+// Parses json inputs and loads lattices the same way as the lattice loader.
+fuzz_target!(|data: &[u8]| {
+    if data.len() > MAX_INPUT_LEN {
+        return;
+    }
+
+    let _ = <JsonDef as InferenceParsing<AdditionalConstraint>>::parse_collection(data);
+    let _ = <JsonDef as InferenceParsing<Tid>>::parse_collection(data);
+
+    if let Ok(value) = serde_json::from_slice::<serde_json::Value>(data) {
+        let num_relations = value["less_than_relations_between_handles"]
+            .as_array()
+            .map(|rels| rels.len())
+            .unwrap_or(0);
+        if num_relations > MAX_LATTICE_RELATIONS {
+            return;
+        }
+
+        if let Ok(lattice_def) = serde_json::from_value::<LatticeDefinition>(value) {
+            if lattice_def.validate().is_ok() {
+                lattice_def.generate_lattice();
+            }
+        }
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+use binary_type_inference::{
+    constraints::{AdditionalConstraint, SubtypeConstraint},
+    inference_job::{DelimitedProtobufDef, InferenceParsing, ProtobufDef},
+};
+use cwe_checker_lib::intermediate_representation::Tid;
+use libfuzzer_sys::fuzz_target;
+
+const MAX_INPUT_LEN: usize = 1 << 16;
+
+// Reads messages in both framings and converts them from their protobuf representation.
+fuzz_target!(|data: &[u8]| {
+    if data.len() > MAX_INPUT_LEN {
+        return;
+    }
+
+    let _ = <ProtobufDef as InferenceParsing<SubtypeConstraint>>::parse_collection(data);
+    let _ = <ProtobufDef as InferenceParsing<AdditionalConstraint>>::parse_collection(data);
+    let _ = <ProtobufDef as InferenceParsing<Tid>>::parse_collection(data);
+    let _ =
+        <DelimitedProtobufDef as InferenceParsing<AdditionalConstraint>>::parse_collection(data);
+    let _ = <DelimitedProtobufDef as InferenceParsing<Tid>>::parse_collection(data);
+});
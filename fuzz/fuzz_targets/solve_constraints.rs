@@ -0,0 +1,140 @@
+#![no_main]
+use std::collections::{BTreeSet, HashMap};
+
+use arbitrary::Arbitrary;
+use binary_type_inference::{
+    constraint_generation::tid_to_tvar,
+    constraints::{
+        AddConstraint, ConstraintSet, DerivedTypeVar, Field, FieldLabel, SubtypeConstraint,
+        TyConstraint, TypeVariable,
+    },
+    lowering::{LoweringContext, LoweringPolicy},
+    solver::{replay::replay_scc, type_lattice::LatticeDefinition, type_sketch::identity_element},
+    util::FileDebugLogger,
+};
+use cwe_checker_lib::intermediate_representation::Tid;
+use libfuzzer_sys::fuzz_target;
+
+const MAX_CONSTRAINTS: usize = 16;
+const MAX_LABELS: usize = 4;
+
+// The procedure being solved for, the lattice elements, and some local variables.
+const BASE_VARIABLES: &[&str] = &[
+    "sub_00001000",
+    "int",
+    "char",
+    "top",
+    "bottom",
+    "x",
+    "y",
+    "z",
+];
+
+#[derive(Arbitrary, Debug)]
+enum Label {
+    Load,
+    Store,
+    In(u8),
+    Out(u8),
+    Field(u8, i8),
+    Add(i8),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Variable {
+    base: u8,
+    labels: Vec<Label>,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Constraint {
+    SubTy(Variable, Variable),
+    Add(Variable, Variable, Variable),
+}
+
+fn to_field_label(label: &Label) -> FieldLabel {
+    match label {
+        Label::Load => FieldLabel::Load,
+        Label::Store => FieldLabel::Store,
+        Label::In(idx) => FieldLabel::In(usize::from(*idx % 4)),
+        Label::Out(idx) => FieldLabel::Out(usize::from(*idx % 2)),
+        Label::Field(size, offset) => FieldLabel::Field(Field::new(
+            i64::from(*offset),
+            (usize::from(*size % 8) + 1) * 8,
+        )),
+        Label::Add(offset) => FieldLabel::Add(i128::from(*offset)),
+    }
+}
+
+fn to_dtv(var: &Variable) -> DerivedTypeVar {
+    let mut dtv = DerivedTypeVar::new(TypeVariable::new(
+        BASE_VARIABLES[usize::from(var.base) % BASE_VARIABLES.len()].to_owned(),
+    ));
+    var.labels
+        .iter()
+        .take(MAX_LABELS)
+        .for_each(|l| dtv.add_field_label(to_field_label(l)));
+    dtv
+}
+
+fn to_constraint(cons: &Constraint) -> TyConstraint {
+    match cons {
+        Constraint::SubTy(lhs, rhs) => {
+            TyConstraint::SubTy(SubtypeConstraint::new(to_dtv(lhs), to_dtv(rhs)))
+        }
+        Constraint::Add(lhs, rhs, repr) => {
+            TyConstraint::AddCons(AddConstraint::new(to_dtv(lhs), to_dtv(rhs), to_dtv(repr)))
+        }
+    }
+}
+
+// Simplifies a random constraint set for a procedure, builds its sketch, and lowers it to ctypes.
+// Errors are expected for some constraint sets, panics are not.
+fuzz_target!(|constraints: Vec<Constraint>| {
+    if constraints.len() > MAX_CONSTRAINTS {
+        return;
+    }
+
+    let cs_set = ConstraintSet::from(
+        constraints
+            .iter()
+            .map(to_constraint)
+            .collect::<BTreeSet<_>>(),
+    );
+    let lattice = LatticeDefinition::new(
+        vec![
+            ("bottom".to_owned(), "char".to_owned()),
+            ("char".to_owned(), "int".to_owned()),
+            ("int".to_owned(), "top".to_owned()),
+        ],
+        "top".to_owned(),
+        "bottom".to_owned(),
+        "int".to_owned(),
+    )
+    .generate_lattice();
+    let sub = Tid::create("sub_00001000".to_owned(), "00001000".to_owned());
+
+    if let Ok(output) = replay_scc(
+        &cs_set,
+        &[sub.clone()],
+        &lattice,
+        &TypeVariable::new("int".to_owned()),
+        FileDebugLogger::default(),
+    ) {
+        let labeling = output
+            .sketch
+            .get_node_index_for_variable(&DerivedTypeVar::new(tid_to_tvar(&sub)))
+            .map(|idx| (sub, idx))
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let _ = LoweringContext::new(
+            &output.sketch,
+            &labeling,
+            &HashMap::new(),
+            &HashMap::new(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes();
+    }
+});
@@ -0,0 +1,559 @@
+/// Detects results that a procedure passes back to its caller through memory instead of registers.
+/// A pointer parameter that is only ever stored through (never loaded from and never escaping the procedure) is an out-parameter.
+/// If the first parameter is such a pointer and it is also returned in a return register, the parameter is the hidden
+/// pointer to a returned structure (sret).
+/// The analysis depends on reaching definitions to follow copies of the parameter through registers.
+use std::collections::{BTreeMap, HashMap};
+
+use cwe_checker_lib::intermediate_representation::{
+    Arg, BinOpType, Blk, ByteSize, Def, Expression, Jmp, Project, Sub, Term, Tid, Variable,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::reaching_definitions::Definition, constraint_generation::NodeContextMapping,
+    node_context::register_map::RegisterContext,
+};
+
+/// How a result passed through memory is returned to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum MemoryReturnKind {
+    /// The parameter is a hidden pointer to the returned structure, the pointer is also returned
+    StructReturn,
+    /// The parameter points to memory the procedure writes a result to
+    OutParameter,
+}
+
+/// A result returned through memory pointed to by a formal parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MemoryReturn {
+    /// How the result is returned
+    pub kind: MemoryReturnKind,
+    /// The index of the pointer parameter in the formal arguments of the procedure
+    pub param_index: usize,
+    /// The extent of the stores through the pointer
+    pub size: ByteSize,
+}
+
+#[derive(Default)]
+struct ParameterUsage {
+    is_read: bool,
+    escapes: bool,
+    is_returned: bool,
+    stores: Vec<(i64, ByteSize)>,
+}
+
+/// Context for detecting memory returns of each subprocedure.
+pub struct Context<'a> {
+    reaching_defs_start_of_block: HashMap<Tid, RegisterContext>,
+    ir: &'a Project,
+}
+
+fn blk_does_return(blk: &Term<Blk>) -> bool {
+    blk.term
+        .jmps
+        .iter()
+        .any(|jmp| matches!(jmp.term, Jmp::Return(_)))
+}
+
+/// Gets the constant offset from the parameter if every definition of the expression is derived from the parameter with the same offset.
+fn must_offset(
+    expr: &Expression,
+    ctx: &RegisterContext,
+    derived: &BTreeMap<Definition, i64>,
+) -> Option<i64> {
+    match expr {
+        Expression::Var(v) => {
+            let mut offsets = ctx
+                .get_register_context()
+                .get(v)?
+                .iter()
+                .map(|def| derived.get(def).cloned());
+            let first = offsets.next()??;
+            if offsets.all(|off| off == Some(first)) {
+                Some(first)
+            } else {
+                None
+            }
+        }
+        Expression::BinOp {
+            op: BinOpType::IntAdd,
+            lhs,
+            rhs,
+        } => match (lhs.as_ref(), rhs.as_ref()) {
+            (Expression::Const(c), e) | (e, Expression::Const(c)) => {
+                Some(must_offset(e, ctx, derived)? + c.try_to_i64().ok()?)
+            }
+            _ => None,
+        },
+        Expression::BinOp {
+            op: BinOpType::IntSub,
+            lhs,
+            rhs,
+        } => match rhs.as_ref() {
+            Expression::Const(c) => Some(must_offset(lhs, ctx, derived)? - c.try_to_i64().ok()?),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Checks if any variable in the expression may hold a value derived from the parameter.
+fn may_derive(
+    expr: &Expression,
+    ctx: &RegisterContext,
+    derived: &BTreeMap<Definition, i64>,
+) -> bool {
+    expr.input_vars().into_iter().any(|v| {
+        ctx.get_register_context()
+            .get(v)
+            .map(|defs| defs.iter().any(|def| derived.contains_key(def)))
+            .unwrap_or(false)
+    })
+}
+
+impl Context<'_> {
+    /// Creates a memory return analysis from a reaching definitions mapping for the start of each block and a project.
+    pub fn new(
+        ir: &Project,
+        reaching_defs_start_of_block: HashMap<Tid, RegisterContext>,
+    ) -> Context<'_> {
+        Context {
+            reaching_defs_start_of_block,
+            ir,
+        }
+    }
+
+    fn get_register_args<'b>(args: &'b [Arg]) -> impl Iterator<Item = &'b Variable> {
+        args.iter().filter_map(|arg| match arg {
+            Arg::Register {
+                expr: Expression::Var(var),
+                ..
+            } => Some(var),
+            _ => None,
+        })
+    }
+
+    /// Checks if a call may be passed the pointer. Calls to known targets are only checked for the target's register arguments,
+    /// unknown targets may receive the pointer through any register.
+    fn call_may_receive_pointer(
+        &self,
+        jmp: &Jmp,
+        ctx: &RegisterContext,
+        derived: &BTreeMap<Definition, i64>,
+    ) -> bool {
+        let holds_pointer = |var: &Variable| {
+            ctx.get_register_context()
+                .get(var)
+                .map(|defs| defs.iter().any(|def| derived.contains_key(def)))
+                .unwrap_or(false)
+        };
+
+        match jmp {
+            Jmp::Call { target, .. } => {
+                if let Some(sub) = self.ir.program.term.subs.get(target) {
+                    Self::get_register_args(&sub.term.formal_args).any(holds_pointer)
+                } else if let Some(ext) = self.ir.program.term.extern_symbols.get(target) {
+                    Self::get_register_args(&ext.parameters).any(holds_pointer)
+                } else {
+                    ctx.get_register_context().keys().any(holds_pointer)
+                }
+            }
+            Jmp::CallInd { .. } | Jmp::CallOther { .. } => {
+                ctx.get_register_context().keys().any(holds_pointer)
+            }
+            _ => false,
+        }
+    }
+
+    /// Folds over the definitions of each block, passing the register context before each definition.
+    fn fold_defs<T>(
+        &self,
+        sub: &Term<Sub>,
+        init: T,
+        f: &mut impl FnMut(T, &Term<Blk>, Option<&Term<Def>>, &RegisterContext) -> T,
+    ) -> T {
+        let mut acc = init;
+        for blk in sub.term.blocks.iter() {
+            if let Some(start) = self.reaching_defs_start_of_block.get(&blk.tid) {
+                let mut ctx = start.clone();
+                for df in blk.term.defs.iter() {
+                    acc = f(acc, blk, Some(df), &ctx);
+                    ctx = ctx.apply_def(df);
+                }
+                // the context at the end of the block
+                acc = f(acc, blk, None, &ctx);
+            }
+        }
+        acc
+    }
+
+    /// Collects the definitions derived from the parameter by copies and constant offsets.
+    fn collect_derived_definitions(
+        &self,
+        sub: &Term<Sub>,
+        param: &Variable,
+    ) -> Option<BTreeMap<Definition, i64>> {
+        let entry = sub.term.blocks.first()?;
+        let mut derived: BTreeMap<Definition, i64> = self
+            .reaching_defs_start_of_block
+            .get(&entry.tid)?
+            .get_register_context()
+            .get(param)?
+            .iter()
+            .filter(|def| matches!(def, Definition::EntryFresh(_)))
+            .map(|def| (def.clone(), 0))
+            .collect();
+
+        loop {
+            let new_derived = self.fold_defs(sub, Vec::new(), &mut |mut acc, _blk, df, ctx| {
+                if let Some(Term {
+                    tid,
+                    term: Def::Assign { value, .. },
+                }) = df
+                {
+                    let def = Definition::Normal(tid.clone());
+                    if !derived.contains_key(&def) {
+                        if let Some(off) = must_offset(value, ctx, &derived) {
+                            acc.push((def, off));
+                        }
+                    }
+                }
+                acc
+            });
+
+            if new_derived.is_empty() {
+                return Some(derived);
+            }
+            derived.extend(new_derived);
+        }
+    }
+
+    fn collect_parameter_usage(
+        &self,
+        sub: &Term<Sub>,
+        derived: &BTreeMap<Definition, i64>,
+    ) -> ParameterUsage {
+        self.fold_defs(
+            sub,
+            ParameterUsage::default(),
+            &mut |mut usage, blk, df, ctx| {
+                match df.map(|df| &df.term) {
+                    Some(Def::Load { address, .. }) => {
+                        usage.is_read |= may_derive(address, ctx, derived);
+                    }
+                    Some(Def::Store { address, value }) => {
+                        usage.escapes |= may_derive(value, ctx, derived);
+                        match must_offset(address, ctx, derived) {
+                            Some(off) => usage.stores.push((off, value.bytesize())),
+                            // a store that may or may not go through the pointer cannot be attributed to the result
+                            None => usage.escapes |= may_derive(address, ctx, derived),
+                        }
+                    }
+                    Some(Def::Assign { .. }) => (),
+                    None => {
+                        // the callee may read through the pointer
+                        usage.escapes |= blk
+                            .term
+                            .jmps
+                            .iter()
+                            .any(|jmp| self.call_may_receive_pointer(&jmp.term, ctx, derived));
+
+                        if blk_does_return(blk) {
+                            for ret in sub.term.formal_rets.iter() {
+                                if let Arg::Register { expr, .. } = ret {
+                                    if must_offset(expr, ctx, derived) == Some(0) {
+                                        usage.is_returned = true;
+                                    } else {
+                                        usage.escapes |= may_derive(expr, ctx, derived);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                usage
+            },
+        )
+    }
+
+    fn get_memory_return(
+        &self,
+        sub: &Term<Sub>,
+        param_index: usize,
+        param: &Variable,
+    ) -> Option<MemoryReturn> {
+        let derived = self.collect_derived_definitions(sub, param)?;
+        let usage = self.collect_parameter_usage(sub, &derived);
+
+        if usage.is_read
+            || usage.escapes
+            || usage.stores.is_empty()
+            || usage.stores.iter().any(|(off, _)| *off < 0)
+        {
+            return None;
+        }
+
+        let size = usage
+            .stores
+            .iter()
+            .map(|(off, sz)| *off as u64 + u64::from(*sz))
+            .max()?;
+
+        let kind = if param_index == 0 && usage.is_returned {
+            MemoryReturnKind::StructReturn
+        } else {
+            MemoryReturnKind::OutParameter
+        };
+
+        Some(MemoryReturn {
+            kind,
+            param_index,
+            size: ByteSize::new(size),
+        })
+    }
+
+    fn get_memory_returns_for_sub(&self, sub: &Term<Sub>) -> Vec<MemoryReturn> {
+        sub.term
+            .formal_args
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, arg)| match arg {
+                Arg::Register {
+                    expr: Expression::Var(var),
+                    ..
+                } => self.get_memory_return(sub, idx, var),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Collects the memory returns for each subprocedure that has any.
+    pub fn collect_memory_returns(&self) -> HashMap<Tid, Vec<MemoryReturn>> {
+        self.ir
+            .program
+            .term
+            .subs
+            .iter()
+            .map(|(tid, sub)| (tid.clone(), self.get_memory_returns_for_sub(sub)))
+            .filter(|(_, rets)| !rets.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashSet};
+    use std::rc::Rc;
+
+    use cwe_checker_lib::intermediate_representation::{
+        Arg, BinOpType, Bitvector, ByteSize, Def, Expression, Jmp, Project, Sub, Term, Tid,
+    };
+
+    use crate::{
+        analysis::reaching_definitions::{ClobberPolicy, Definition, TermSet},
+        node_context::register_map::RegisterContext,
+        test_utils::{load_composite_return_project, register, register_arg},
+    };
+
+    use super::{Context, MemoryReturn, MemoryReturnKind};
+
+    const REGISTERS: [&str; 4] = ["RDI", "RSI", "RCX", "RAX"];
+
+    fn var(name: &str) -> Expression {
+        Expression::Var(register(name, 8))
+    }
+
+    fn plus(name: &str, off: i64) -> Expression {
+        Expression::BinOp {
+            op: BinOpType::IntAdd,
+            lhs: Box::new(var(name)),
+            rhs: Box::new(Expression::Const(Bitvector::from_i64(off))),
+        }
+    }
+
+    fn def(sub_name: &str, idx: usize, term: Def) -> Term<Def> {
+        Term {
+            tid: Tid::new(format!("instr_{}_{}", sub_name, idx)),
+            term,
+        }
+    }
+
+    /// Replaces the procedures of the project with single block procedures that return after the given definitions.
+    fn with_subs(mut proj: Project, subs: Vec<(&str, Vec<Arg>, Vec<Def>)>) -> Project {
+        let template = proj
+            .program
+            .term
+            .subs
+            .values()
+            .find(|sub| !sub.term.blocks.is_empty())
+            .expect("should have a procedure with blocks")
+            .clone();
+
+        proj.program.term.subs = subs
+            .into_iter()
+            .map(|(name, params, defs)| {
+                let mut blk = template.term.blocks[0].clone();
+                blk.tid = Tid::new(format!("blk_{}", name));
+                blk.term.defs = defs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, df)| def(name, idx, df))
+                    .collect();
+                blk.term.jmps = vec![Term {
+                    tid: Tid::new(format!("ret_{}", name)),
+                    term: Jmp::Return(var("LR")),
+                }];
+
+                let mut sub: Term<Sub> = template.clone();
+                sub.tid = Tid::new(name);
+                sub.term.name = name.to_owned();
+                sub.term.blocks = vec![blk];
+                sub.term.formal_args = params;
+                sub.term.formal_rets = vec![register_arg("RAX", 8)];
+                (sub.tid.clone(), sub)
+            })
+            .collect();
+        proj
+    }
+
+    /// Each register starts with a fresh definition at the entry of each procedure.
+    fn collect_memory_returns(proj: &Project) -> BTreeMap<Tid, Vec<MemoryReturn>> {
+        let shared = Rc::new(proj.clone());
+        let entry_defs = REGISTERS
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let mut defs = TermSet::new();
+                defs.insert(Definition::EntryFresh(idx));
+                (register(name, 8), defs)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let reaching_defs_start_of_block = proj
+            .program
+            .term
+            .subs
+            .values()
+            .map(|sub| {
+                (
+                    sub.term.blocks[0].tid.clone(),
+                    RegisterContext::new(
+                        entry_defs.clone(),
+                        &shared,
+                        &Rc::new(HashSet::new()),
+                        ClobberPolicy::default(),
+                    ),
+                )
+            })
+            .collect();
+
+        Context::new(proj, reaching_defs_start_of_block)
+            .collect_memory_returns()
+            .into_iter()
+            .collect()
+    }
+
+    fn store(address: Expression, value: Expression) -> Def {
+        Def::Store { address, value }
+    }
+
+    #[test]
+    fn detects_hidden_struct_return_pointer() {
+        let proj = with_subs(
+            load_composite_return_project(),
+            vec![(
+                "sub_sret",
+                vec![register_arg("RDI", 8)],
+                vec![
+                    store(var("RDI"), Expression::Const(Bitvector::from_i32(1))),
+                    store(plus("RDI", 8), Expression::Const(Bitvector::from_i64(2))),
+                    Def::Assign {
+                        var: register("RAX", 8),
+                        value: var("RDI"),
+                    },
+                ],
+            )],
+        );
+
+        let rets = collect_memory_returns(&proj);
+        assert_eq!(
+            rets.get(&Tid::new("sub_sret")),
+            Some(&vec![MemoryReturn {
+                kind: MemoryReturnKind::StructReturn,
+                param_index: 0,
+                size: ByteSize::new(16),
+            }])
+        );
+    }
+
+    #[test]
+    fn detects_pointer_parameters_only_stored_to() {
+        let proj = with_subs(
+            load_composite_return_project(),
+            vec![(
+                "sub_out",
+                vec![register_arg("RDI", 8), register_arg("RSI", 8)],
+                vec![
+                    Def::Assign {
+                        var: register("RAX", 8),
+                        value: Expression::Const(Bitvector::from_i64(0)),
+                    },
+                    // the store goes through a copy of the parameter
+                    Def::Assign {
+                        var: register("RCX", 8),
+                        value: plus("RSI", 4),
+                    },
+                    store(var("RCX"), Expression::Const(Bitvector::from_i32(1))),
+                ],
+            )],
+        );
+
+        let rets = collect_memory_returns(&proj);
+        assert_eq!(
+            rets.get(&Tid::new("sub_out")),
+            Some(&vec![MemoryReturn {
+                kind: MemoryReturnKind::OutParameter,
+                param_index: 1,
+                size: ByteSize::new(8),
+            }])
+        );
+    }
+
+    #[test]
+    fn read_or_escaping_pointers_are_not_memory_returns() {
+        let proj = with_subs(
+            load_composite_return_project(),
+            vec![
+                (
+                    "sub_read",
+                    vec![register_arg("RDI", 8)],
+                    vec![
+                        store(var("RDI"), Expression::Const(Bitvector::from_i64(1))),
+                        Def::Load {
+                            var: register("RAX", 8),
+                            address: var("RDI"),
+                        },
+                    ],
+                ),
+                (
+                    "sub_escape",
+                    vec![register_arg("RDI", 8)],
+                    vec![
+                        store(var("RDI"), Expression::Const(Bitvector::from_i64(1))),
+                        store(Expression::Const(Bitvector::from_i64(0x1000)), var("RDI")),
+                    ],
+                ),
+                (
+                    "sub_negative_offset",
+                    vec![register_arg("RDI", 8)],
+                    vec![store(
+                        plus("RDI", -8),
+                        Expression::Const(Bitvector::from_i64(1)),
+                    )],
+                ),
+            ],
+        );
+
+        assert!(collect_memory_returns(&proj).is_empty());
+    }
+}
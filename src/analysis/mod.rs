@@ -2,6 +2,8 @@
 pub mod callgraph;
 /// Adds returns to the formal return parameters of procedures that tail call procedures with a return value.
 pub mod fixup_returns;
+/// Detects sret pointers and out-parameters that procedures return results through.
+pub mod memory_returns;
 /// Analyzes the reaching definitions for variables in this project. Maps Tids to register contexts.
 pub mod reaching_definitions;
 /// Currently unused but finds the maximum stack depth of a given procedure.
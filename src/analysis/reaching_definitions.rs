@@ -121,6 +121,23 @@ where
 /// should only be added.
 pub type DomVal = ImplicitBottomMappingDomain<Variable, TermSet>;
 
+/// Determines which registers lose their definitions across a call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClobberPolicy {
+    /// Only returns from internal calls clobber the registers that are not callee saved. Definitions flow through
+    /// extern stubs and unresolved calls.
+    InternalCallsOnly,
+    /// Every call clobbers the volatile registers of its calling convention, including extern stubs. Calls without a known
+    /// callee use the project's standard calling convention, or clobber every register but the stack pointer if there is none.
+    CallingConvention,
+}
+
+impl Default for ClobberPolicy {
+    fn default() -> Self {
+        ClobberPolicy::InternalCallsOnly
+    }
+}
+
 /// The context for a reaching definitions analysis. Reaching definitions needs both a project and a mapping from Tid to external symbols.
 /// The external symbols allow reaching definitions to appropriately update based on ABI info.
 pub struct Context<'a> {
@@ -128,6 +145,7 @@ pub struct Context<'a> {
     graph: &'a Graph<'a>,
     extern_symbol_map: &'a BTreeMap<Tid, ExternSymbol>,
     project: &'a Project,
+    clobber_policy: ClobberPolicy,
 }
 
 impl<'a> Context<'a> {
@@ -136,11 +154,13 @@ impl<'a> Context<'a> {
         project: &'a Project,
         graph: &'a Graph<'a>,
         extern_symbol_map: &'a BTreeMap<Tid, ExternSymbol>,
+        clobber_policy: ClobberPolicy,
     ) -> Context<'a> {
         Context {
             project,
             graph,
             extern_symbol_map,
+            clobber_policy,
         }
     }
 }
@@ -303,11 +323,28 @@ fn get_cc_for_jmp<'a>(proj: &'a Project, call_term: &Term<Jmp>) -> Option<&'a Ca
     })
 }
 
+/// Gets the registers a call may overwrite without defining them as a return value. Without a calling convention
+/// every register except the stack pointer is assumed to be overwritten.
+pub(crate) fn get_clobbered_registers<'b>(
+    project: &'b Project,
+    cc: Option<&'b CallingConvention>,
+    returns: BTreeSet<&'b Variable>,
+) -> impl Iterator<Item = &'b Variable> {
+    project.register_set.iter().filter(move |reg| {
+        !returns.contains(reg)
+            && match cc {
+                Some(cc) => !cc.callee_saved_register.contains(*reg),
+                None => **reg != project.stack_pointer_register,
+            }
+    })
+}
+
 /// Applies a return to the fall through of a call term to the passed value.
 pub fn apply_return(
     curr_value: Option<&DomVal>,
     call_term: &Term<Jmp>,
     project: &Project,
+    clobber_policy: ClobberPolicy,
 ) -> DomVal {
     let mut new_value = curr_value
         .cloned()
@@ -329,28 +366,20 @@ pub fn apply_return(
         .collect::<BTreeSet<_>>();
 
     // These are registers that are defined by the function but are not returns, in this case we kill them
-    let cc_killed = get_cc_for_jmp(project, call_term).map(|cc| {
-        let saves = cc
-            .callee_saved_register
-            .iter()
+    let reg_returns_set = register_returns
+        .iter()
+        .map(|(_, v)| v)
+        .collect::<BTreeSet<_>>();
+    let cc = get_cc_for_jmp(project, call_term);
+    let cc_killed: Vec<Variable> = match (cc, clobber_policy) {
+        (None, ClobberPolicy::InternalCallsOnly) => Vec::new(),
+        (cc, _) => get_clobbered_registers(project, cc, reg_returns_set)
             .cloned()
-            .collect::<BTreeSet<_>>();
-
-        let reg_returns_set = register_returns
-            .iter()
-            .map(|(_, v)| v)
-            .collect::<BTreeSet<_>>();
-
-        project
-            .register_set
-            .iter()
-            .filter(move |x| !saves.contains(x) && !reg_returns_set.contains(x))
-    });
-
-    if let Some(cc_killed) = cc_killed {
-        for killed in cc_killed {
-            kill_definition_of_variable(&mut new_value, killed)
-        }
+            .collect(),
+    };
+
+    for killed in cc_killed.iter() {
+        kill_definition_of_variable(&mut new_value, killed)
     }
 
     for (idx, var) in register_returns.iter() {
@@ -386,12 +415,31 @@ impl<'a> cwe_checker_lib::analysis::forward_interprocedural_fixpoint::Context<'a
             Jmp::Call { target, .. } => Some(target),
             _ => None,
         };
-        //TODO(ian) we need to clobber non callee saves here. Any physical register should be clobbered.
         let next_res = if let Some(extern_symb) =
             call_target.and_then(|tid| self.extern_symbol_map.get(tid))
         {
             let mut new_value = value.clone();
 
+            if self.clobber_policy == ClobberPolicy::CallingConvention {
+                let returns = extern_symb
+                    .return_values
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        Arg::Register {
+                            expr: Expression::Var(var),
+                            ..
+                        } => Some(var),
+                        _ => None,
+                    })
+                    .collect::<BTreeSet<_>>();
+                let cc = self
+                    .project
+                    .get_specific_calling_convention(&extern_symb.calling_convention);
+                for killed in get_clobbered_registers(self.project, cc, returns) {
+                    kill_definition_of_variable(&mut new_value, killed);
+                }
+            }
+
             // define the returned values, these should probably be collected by function application type inference into outs so not strictly
             // required but still good to have
             for (idx, arg) in extern_symb.return_values.iter().enumerate() {
@@ -410,6 +458,15 @@ impl<'a> cwe_checker_lib::analysis::forward_interprocedural_fixpoint::Context<'a
                 }
             }
 
+            new_value
+        } else if self.clobber_policy == ClobberPolicy::CallingConvention {
+            // without a stub we dont know what the callee returns so we only kill what the standard calling convention
+            // allows it to overwrite
+            let mut new_value = value.clone();
+            let cc = self.project.get_standard_calling_convention();
+            for killed in get_clobbered_registers(self.project, cc, BTreeSet::new()) {
+                kill_definition_of_variable(&mut new_value, killed);
+            }
             new_value
         } else {
             // if we dont have any info we assume it doesnt define, if we wanted to be sound here we could add it to the set, but im not sure we want to infer
@@ -451,7 +508,12 @@ impl<'a> cwe_checker_lib::analysis::forward_interprocedural_fixpoint::Context<'a
         _return_term: &Term<Jmp>,
         _cc: &Option<String>,
     ) -> Option<Self::Value> {
-        Some(apply_return(value_before_call, call_term, self.project))
+        Some(apply_return(
+            value_before_call,
+            call_term,
+            self.project,
+            self.clobber_policy,
+        ))
     }
 
     fn specialize_conditional(
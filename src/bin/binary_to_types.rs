@@ -1,6 +1,13 @@
 use binary_type_inference::{
-    inference_job::{InferenceJob, JobDefinition, JsonDef, ProtobufDef},
-    solver::type_lattice::NamedLatticeElement,
+    analysis::reaching_definitions::ClobberPolicy,
+    inference_job::{
+        message_reader::DEFAULT_MAX_MESSAGE_SIZE, DelimitedProtobufDef, InferenceJob,
+        JobDefinition, JsonDef, ProtobufDef, TextDef,
+    },
+    interchange,
+    lowering::LoweringPolicy,
+    node_context::points_to::ObjectNamingPolicy,
+    solver::{type_lattice::NamedLatticeElement, type_sketch::PolymorphismPolicy},
 };
 use clap::{App, Arg};
 
@@ -24,16 +31,61 @@ where
 fn main() -> anyhow::Result<()> {
     env_logger::init();
     let matches = App::new("binary_to_types")
-        .arg(Arg::with_name("input_bin").required(true).index(1))
-        .arg(Arg::with_name("input_json").required(true).index(2))
-        .arg(Arg::with_name("lattice_json").required(true))
-        .arg(Arg::with_name("additional_constraints_file").required(true))
-        .arg(Arg::with_name("interesting_tids").required(true))
+        .arg(
+            Arg::with_name("input_bin")
+                .required_unless("manifest")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("input_json")
+                .required_unless("manifest")
+                .index(2),
+        )
+        .arg(Arg::with_name("lattice_json").required_unless("manifest"))
+        .arg(Arg::with_name("additional_constraints_file").required_unless("manifest"))
+        .arg(Arg::with_name("interesting_tids").required_unless("manifest"))
+        .arg(
+            Arg::with_name("manifest")
+                .long("manifest")
+                .required(false)
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "input_bin",
+                    "input_json",
+                    "lattice_json",
+                    "additional_constraints_file",
+                    "interesting_tids",
+                    "human_readable_input",
+                    "text_input",
+                    "delimited_protobuf_input",
+                    "use_aggressive_shared_returns",
+                    "max_message_size",
+                ]),
+        )
         .arg(
             Arg::with_name("human_readable_input")
                 .long("human_readable_input")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("text_input")
+                .long("text_input")
+                .takes_value(false)
+                .conflicts_with("human_readable_input"),
+        )
+        .arg(
+            Arg::with_name("delimited_protobuf_input")
+                .long("delimited_protobuf_input")
+                .takes_value(false)
+                .conflicts_with_all(&["human_readable_input", "text_input"]),
+        )
+        .arg(
+            Arg::with_name("max_message_size")
+                .long("max_message_size")
+                .required(false)
+                .takes_value(true)
+                .help("The maximum size in bytes of a single protobuf input message, defaults to 64 MiB"),
+        )
         .arg(
             Arg::with_name("human_readable_output")
                 .long("human_readable_output")
@@ -50,6 +102,45 @@ fn main() -> anyhow::Result<()> {
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("lowering_policy")
+                .long("lowering_policy")
+                .required(false)
+                .takes_value(true)
+                .possible_values(&["upper", "lower", "tightest"])
+                .default_value("upper"),
+        )
+        .arg(
+            Arg::with_name("polymorphism_policy")
+                .long("polymorphism_policy")
+                .required(false)
+                .takes_value(true)
+                .possible_values(&["refine", "monomorphic", "polymorphic"])
+                .default_value("refine"),
+        )
+        .arg(
+            Arg::with_name("object_naming_policy")
+                .long("object_naming_policy")
+                .required(false)
+                .takes_value(true)
+                .default_value("site")
+                .help("How abstract objects are named: site, summary, or call_string:<k>"),
+        )
+        .arg(
+            Arg::with_name("specialize_callsites")
+                .long("specialize_callsites")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("recover_memory_returns")
+                .long("recover_memory_returns")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("clobber_by_calling_convention")
+                .long("clobber_by_calling_convention")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("debug_out_dir")
                 .long("debug_out_dir")
@@ -58,36 +149,81 @@ fn main() -> anyhow::Result<()> {
         )
         .get_matches();
 
-    let input_bin = matches.value_of("input_bin").unwrap();
-    let input_json = matches.value_of("input_json").unwrap();
-    let lattice_json = matches.value_of("lattice_json").unwrap();
-    let tids_file = matches.value_of("interesting_tids").unwrap();
     let out_file = matches.value_of("out").unwrap();
-    let additional_constraints_file = matches.value_of("additional_constraints_file").unwrap();
+    let dbg_dir = matches.value_of("debug_out_dir").map(|x| x.to_owned());
 
-    let job_def = JobDefinition {
-        binary_path: input_bin.to_owned(),
-        ir_json_path: input_json.to_owned(),
-        lattice_json: lattice_json.to_owned(),
-        interesting_tids: tids_file.to_owned(),
-        additional_constraints_file: additional_constraints_file.to_owned(),
-    };
+    let mut if_job = if let Some(manifest) = matches.value_of("manifest") {
+        InferenceJob::parse_manifest(manifest, dbg_dir)
+    } else {
+        let job_def = JobDefinition {
+            binary_path: matches.value_of("input_bin").unwrap().to_owned(),
+            ir_json_path: matches.value_of("input_json").unwrap().to_owned(),
+            lattice_json: matches.value_of("lattice_json").unwrap().to_owned(),
+            interesting_tids: matches.value_of("interesting_tids").unwrap().to_owned(),
+            additional_constraints_file: matches
+                .value_of("additional_constraints_file")
+                .unwrap()
+                .to_owned(),
+            max_message_size: matches
+                .value_of("max_message_size")
+                .map(|size| size.parse())
+                .transpose()?
+                .unwrap_or(DEFAULT_MAX_MESSAGE_SIZE),
+        };
 
-    let use_aggressive_shared_returns = matches.is_present("use_aggressive_shared_returns");
+        let use_aggressive_shared_returns = matches.is_present("use_aggressive_shared_returns");
 
-    let dbg_dir = matches.value_of("debug_out_dir").map(|x| x.to_owned());
-    let mut if_job = if matches.is_present("human_readable_input") {
-        InferenceJob::parse::<JsonDef>(&job_def, dbg_dir, vec![], use_aggressive_shared_returns)
-    } else {
-        InferenceJob::parse::<ProtobufDef>(&job_def, dbg_dir, vec![], use_aggressive_shared_returns)
+        if matches.is_present("human_readable_input") {
+            InferenceJob::parse::<JsonDef>(&job_def, dbg_dir, vec![], use_aggressive_shared_returns)
+        } else if matches.is_present("text_input") {
+            InferenceJob::parse::<TextDef>(&job_def, dbg_dir, vec![], use_aggressive_shared_returns)
+        } else if matches.is_present("delimited_protobuf_input") {
+            InferenceJob::parse::<DelimitedProtobufDef>(
+                &job_def,
+                dbg_dir,
+                vec![],
+                use_aggressive_shared_returns,
+            )
+        } else {
+            InferenceJob::parse::<ProtobufDef>(
+                &job_def,
+                dbg_dir,
+                vec![],
+                use_aggressive_shared_returns,
+            )
+        }
     }?;
 
-    let (grph, (node_to_type_id, type_id_to_type)) = if_job.infer_ctypes()?;
+    let lowering_policy: LoweringPolicy = matches.value_of("lowering_policy").unwrap().parse()?;
+    if_job.set_lowering_policy(lowering_policy);
+
+    let polymorphism_policy: PolymorphismPolicy =
+        matches.value_of("polymorphism_policy").unwrap().parse()?;
+    if_job.set_polymorphism_policy(polymorphism_policy);
+
+    let object_naming_policy: ObjectNamingPolicy =
+        matches.value_of("object_naming_policy").unwrap().parse()?;
+    if_job.set_object_naming_policy(object_naming_policy);
+
+    if matches.is_present("clobber_by_calling_convention") {
+        if_job.set_clobber_policy(ClobberPolicy::CallingConvention);
+    }
+
+    if matches.is_present("specialize_callsites") {
+        if_job.set_specialize_callsites(true);
+    }
+
+    if matches.is_present("recover_memory_returns") {
+        if_job.recover_memory_returns();
+    }
+
+    let (grph, (node_to_type_id, type_id_to_type), confidence) =
+        if_job.infer_ctypes_with_confidence()?;
 
     let mapped_graph = grph.get_graph().get_graph().map(
         |idx, nd_elem| {
             format!(
-                "{}:{}:{}",
+                "{}:{}:{}:{}",
                 grph.get_graph()
                     .get_group_for_node(idx)
                     .into_iter()
@@ -95,7 +231,8 @@ fn main() -> anyhow::Result<()> {
                     .map(|maybe| format!("{}", maybe))
                     .unwrap_or_else(|| "".to_owned()),
                 idx.index(),
-                nd_elem.get_upper().get_name()
+                lowering_policy.select(nd_elem).get_name(),
+                nd_elem
             )
         },
         |_e, fld_label| format!("{}", fld_label),
@@ -110,35 +247,45 @@ fn main() -> anyhow::Result<()> {
     }
 
     let mut out_file = std::fs::File::create(out_file)?;
+    let type_names = binary_type_inference::lowering::naming::name_types(
+        &type_id_to_type,
+        &node_to_type_id,
+        &if_job.get_graph_labeling(&grph),
+    );
     let mut pb = binary_type_inference::lowering::convert_mapping_to_profobuf(
         type_id_to_type,
         &node_to_type_id,
     );
+    binary_type_inference::lowering::naming::add_names_to_protobuf(&mut pb, &type_names);
+    binary_type_inference::lowering::confidence::add_confidence_to_protobuf(
+        &mut pb,
+        &node_to_type_id,
+        &confidence,
+    );
+    binary_type_inference::lowering::conflicts::add_conflicts_to_protobuf(
+        &mut pb,
+        &if_job.detect_type_conflicts(&grph),
+        &node_to_type_id,
+    );
+    if matches.is_present("specialize_callsites") {
+        binary_type_inference::lowering::add_callsite_types_to_protobuf(
+            &mut pb,
+            &if_job.get_callsite_labeling(&grph),
+            &node_to_type_id,
+        );
+    }
     if !matches.is_present("human_readable_output") {
-        let mapping = if_job.get_graph_labeling(&grph);
-        for (k, v) in mapping {
-            let tid = binary_type_inference::ctypes::Tid {
-                name: k.get_str_repr().to_owned(),
-                address: k.address.clone(),
-            };
-
-            if let Some(tgt_type_id) = node_to_type_id.get(&v) {
-                let tid_to_node_idx = binary_type_inference::ctypes::TidToTypeId {
-                    type_id: Some(binary_type_inference::lowering::convert_typeid(
-                        *tgt_type_id,
-                    )),
-                    tid: Some(tid),
-                };
-
-                pb.type_variable_repr_nodes.push(tid_to_node_idx);
-            }
-        }
+        binary_type_inference::lowering::add_tid_types_to_protobuf(
+            &mut pb,
+            &if_job.get_graph_labeling(&grph),
+            &node_to_type_id,
+        );
 
         let mut buf = Vec::new();
         pb.encode(&mut buf)?;
         out_file.write_all(&buf)?;
     } else {
-        serde_json::to_writer(out_file, &pb)?;
+        interchange::to_writer(out_file, &pb)?;
     }
 
     if let Some(debug_graph_file) = matches.value_of("debug_out_dir") {
@@ -147,7 +294,7 @@ fn main() -> anyhow::Result<()> {
 
         let debug_ctype_fl =
             std::fs::File::create(immutably_push(&pbuf, "debug_ctype_output.json"))?;
-        serde_json::to_writer(debug_ctype_fl, &pb)?;
+        interchange::to_writer(debug_ctype_fl, &pb)?;
     }
 
     Ok(())
@@ -0,0 +1,18 @@
+use binary_type_inference::interchange::generate_schemas;
+use clap::{App, Arg};
+use std::path::Path;
+
+fn main() -> anyhow::Result<()> {
+    let matches = App::new("generate_schemas")
+        .about("Writes the JSON Schema of each json input and output format to a directory")
+        .arg(Arg::with_name("out_dir").required(true).index(1))
+        .get_matches();
+
+    let out_dir = Path::new(matches.value_of("out_dir").unwrap());
+    std::fs::create_dir_all(out_dir)?;
+    for (name, schema) in generate_schemas() {
+        let fl = std::fs::File::create(out_dir.join(name))?;
+        serde_json::to_writer_pretty(fl, &schema)?;
+    }
+    Ok(())
+}
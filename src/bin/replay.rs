@@ -0,0 +1,190 @@
+use anyhow::anyhow;
+use binary_type_inference::{
+    constraint_dsl::parse_tids,
+    constraints::ConstraintSet,
+    inference_job::InferenceJob,
+    solver::replay::{read_repro_file, replay_scc, repro_file_tid, shrink_constraints},
+    util::FileDebugLogger,
+};
+use clap::{App, Arg, ArgMatches};
+
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// The condition a shrunk constraint set must still reproduce.
+enum Predicate {
+    /// The replay panics
+    Panic,
+    /// The replay does not finish before the timeout
+    Timeout,
+    /// The replay's output contains the string
+    Output(String),
+}
+
+struct ReplayRunner<'a> {
+    repro_file: &'a Path,
+    lattice_json: &'a str,
+    tids: Option<&'a str>,
+    timeout: Duration,
+    predicate: Predicate,
+    work_dir: tempdir::TempDir,
+}
+
+impl ReplayRunner<'_> {
+    /// Replays the constraints in a child process so that panics and timeouts can be observed.
+    fn still_fails(&self, cs_set: &ConstraintSet) -> anyhow::Result<bool> {
+        // keep the original file name so the candidate names the same tid
+        let repro_file = self.work_dir.path().join(
+            self.repro_file
+                .file_name()
+                .ok_or_else(|| anyhow!("the repro file must be a file"))?,
+        );
+        std::fs::write(&repro_file, serde_json::to_string(cs_set)?)?;
+
+        let mut cmd = Command::new(std::env::current_exe()?);
+        cmd.arg(&repro_file).arg(self.lattice_json);
+        if let Some(tids) = self.tids {
+            cmd.arg("--tids").arg(tids);
+        }
+
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+
+        // read the output concurrently so that the child cannot block on a full pipe
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let reader = std::thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+
+            if start.elapsed() > self.timeout {
+                child.kill()?;
+                child.wait()?;
+                break None;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        let output = reader
+            .join()
+            .map_err(|_| anyhow!("reading replay output panicked"))??;
+
+        Ok(match (&self.predicate, status) {
+            (Predicate::Timeout, status) => status.is_none(),
+            // rust exits with 101 when the main thread panics
+            (Predicate::Panic, Some(status)) => status.code() == Some(101),
+            (Predicate::Output(expected), Some(_)) => output.contains(expected.as_str()),
+            (_, None) => false,
+        })
+    }
+}
+
+fn get_predicate(matches: &ArgMatches) -> Predicate {
+    match matches.value_of("predicate").unwrap() {
+        "panic" => Predicate::Panic,
+        "timeout" => Predicate::Timeout,
+        _ => Predicate::Output(matches.value_of("expected_output").unwrap().to_owned()),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let matches = App::new("replay")
+        .about("Replays the simplification of an scc from a <tid>_basic_cons_repro_file debug log")
+        .arg(Arg::with_name("repro_file").required(true).index(1))
+        .arg(Arg::with_name("lattice_json").required(true).index(2))
+        .arg(
+            Arg::with_name("tids")
+                .long("tids")
+                .takes_value(true)
+                .help("The tids of the scc, defaults to the tid in the repro file's name"),
+        )
+        .arg(
+            Arg::with_name("debug_out_dir")
+                .long("debug_out_dir")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("shrink")
+                .long("shrink")
+                .takes_value(true)
+                .help(
+                "Shrinks the constraints while the predicate holds and writes them to this file",
+            ),
+        )
+        .arg(
+            Arg::with_name("predicate")
+                .long("predicate")
+                .takes_value(true)
+                .possible_values(&["panic", "timeout", "output"])
+                .default_value("panic"),
+        )
+        .arg(
+            Arg::with_name("expected_output")
+                .long("expected_output")
+                .takes_value(true)
+                .required_if("predicate", "output"),
+        )
+        .arg(
+            Arg::with_name("timeout_ms")
+                .long("timeout_ms")
+                .takes_value(true)
+                .default_value("60000"),
+        )
+        .get_matches();
+
+    let repro_file = matches.value_of("repro_file").unwrap();
+    let lattice_json = matches.value_of("lattice_json").unwrap();
+    let cs_set = read_repro_file(repro_file)?;
+
+    if let Some(shrunk_file) = matches.value_of("shrink") {
+        let runner = ReplayRunner {
+            repro_file: Path::new(repro_file),
+            lattice_json,
+            tids: matches.value_of("tids"),
+            timeout: Duration::from_millis(matches.value_of("timeout_ms").unwrap().parse()?),
+            predicate: get_predicate(&matches),
+            work_dir: tempdir::TempDir::new("replay")?,
+        };
+
+        let shrunk = shrink_constraints(&cs_set, |cons| runner.still_fails(cons))?;
+        std::fs::write(shrunk_file, serde_json::to_string(&shrunk)?)?;
+        println!("Shrunk {} constraints to {}:", cs_set.len(), shrunk.len());
+        print!("{}", shrunk);
+        return Ok(());
+    }
+
+    let scc = match matches.value_of("tids") {
+        Some(tids) => parse_tids(tids)?,
+        None => vec![repro_file_tid(repro_file)
+            .ok_or_else(|| anyhow!("the repro file name does not contain a tid, pass --tids"))?],
+    };
+
+    let (lattice, weakest_integral_type) = InferenceJob::parse_lattice_json(lattice_json, vec![])?;
+    let output = replay_scc(
+        &cs_set,
+        &scc,
+        &lattice,
+        &weakest_integral_type,
+        FileDebugLogger::new(matches.value_of("debug_out_dir").map(|x| x.to_owned())),
+    )?;
+
+    println!("Resolved constraints:");
+    print!("{}", output.resolved_constraints);
+    println!("Simplified constraints:");
+    print!("{}", output.simplified_constraints);
+    println!("Sketch:");
+    print!("{}", output.sketch);
+    Ok(())
+}
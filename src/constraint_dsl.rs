@@ -0,0 +1,370 @@
+//! A textual format for additional constraints and interesting tids built on the constraint parsers in [crate::constraints].
+//!
+//! Additional constraints are grouped into blocks keyed by the subprocedure they apply to:
+//! ```text
+//! // comments run to the end of the line, # also starts a comment
+//! @FUN_00101234 {
+//!     FUN_00101234.in_0.load.σ32@0 <= int
+//!     FUN_00101234.out_1.load.σ64@-8 <= char
+//! }
+//! @sub_00101300:00101300 { sub_00101300.out <= int }
+//! ```
+//! The address of a tid can be given after a colon, otherwise it is taken from the hex suffix of the name.
+//! Interesting tids are written one per line in the same tid syntax, the leading @ is optional.
+//!
+//! A lattice is written as relations between its elements along with a declaration of its top, bottom and weakest integral elements:
+//! ```text
+//! top T
+//! bottom bottom
+//! weakest_integral int
+//! bottom <= char
+//! char <= int
+//! int <= T
+//! ```
+use std::fmt::Display;
+
+use cwe_checker_lib::intermediate_representation::Tid;
+
+use crate::constraints::{
+    parse_identifier, parse_subtype_cons, AdditionalConstraint, TyConstraint,
+};
+use crate::solver::type_lattice::LatticeDefinition;
+
+/// An error in a textual constraint document, located by its line and column starting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DslError {
+    /// The line of the error
+    pub line: usize,
+    /// The column of the error in characters
+    pub column: usize,
+    /// A description of what was expected
+    pub message: String,
+}
+
+impl Display for DslError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for DslError {}
+
+struct Cursor<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Cursor<'a> {
+        Cursor { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn is_eof(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn error(&self, message: impl Into<String>) -> DslError {
+        let before = &self.input[..self.pos];
+        let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+        DslError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+
+    fn advance_to(&mut self, rest: &'a str) {
+        self.pos = self.input.len() - rest.len();
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            let trimmed = self.rest().trim_start();
+            if trimmed.starts_with("//") || trimmed.starts_with('#') {
+                let line_end = trimmed.find('\n').unwrap_or_else(|| trimmed.len());
+                self.advance_to(&trimmed[line_end..]);
+            } else {
+                self.advance_to(trimmed);
+                return;
+            }
+        }
+    }
+
+    /// Skips the separator after an item, which must end the item with whitespace, a comment, or the given closing delimiter.
+    fn expect_separator(&mut self, closing: Option<char>) -> Result<(), DslError> {
+        let rest = self.rest();
+        let is_separated = rest.is_empty()
+            || rest.starts_with(char::is_whitespace)
+            || rest.starts_with("//")
+            || rest.starts_with('#')
+            || closing.map(|c| rest.starts_with(c)).unwrap_or(false);
+        if is_separated {
+            self.skip_whitespace_and_comments();
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "unexpected character '{}'",
+                rest.chars().next().unwrap_or_default()
+            )))
+        }
+    }
+
+    fn expect_char(&mut self, expected: char) -> Result<(), DslError> {
+        if self.rest().starts_with(expected) {
+            self.pos += expected.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", expected)))
+        }
+    }
+
+    fn parse_name(&mut self, expected: &str) -> Result<&'a str, DslError> {
+        let (rest, name) = parse_identifier(self.rest())
+            .map_err(|_| self.error(format!("expected {}", expected)))?;
+        self.advance_to(rest);
+        Ok(name)
+    }
+
+    fn parse_tid(&mut self) -> Result<Tid, DslError> {
+        let (rest, name) =
+            parse_identifier(self.rest()).map_err(|_| self.error("expected a tid name"))?;
+        self.advance_to(rest);
+
+        let address = if self.rest().starts_with(':') {
+            self.pos += 1;
+            let (rest, address) = parse_identifier(self.rest())
+                .map_err(|_| self.error("expected a tid address after ':'"))?;
+            self.advance_to(rest);
+            address.to_owned()
+        } else {
+            default_address(name)
+        };
+
+        Ok(Tid::create(name.to_owned(), address))
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<AdditionalConstraint>, DslError> {
+        self.expect_char('@')?;
+        let tid = self.parse_tid()?;
+        self.skip_whitespace_and_comments();
+        let block_start = self.error("unclosed block");
+        self.expect_char('{')?;
+
+        let mut constraints = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments();
+            if self.is_eof() {
+                return Err(DslError {
+                    message: format!("block for {} is never closed", tid.get_str_repr()),
+                    ..block_start
+                });
+            }
+
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+                return Ok(constraints);
+            }
+
+            match parse_subtype_cons(self.rest()) {
+                Ok((rest, TyConstraint::SubTy(constraint))) => {
+                    self.advance_to(rest);
+                    constraints.push(AdditionalConstraint {
+                        constraint,
+                        associated_variable: tid.clone(),
+                    });
+                    self.expect_separator(Some('}'))?;
+                }
+                _ => return Err(self.error("expected a subtyping constraint 'x <= y'")),
+            }
+        }
+    }
+}
+
+/// Takes the address of a tid from the hex digits after the last underscore in its name, as in sub_00101234.
+pub(crate) fn default_address(name: &str) -> String {
+    match name.rsplit_once('_') {
+        Some((_, suffix))
+            if !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            suffix.to_owned()
+        }
+        _ => "UNKNOWN".to_owned(),
+    }
+}
+
+/// Parses blocks of additional constraints, each keyed by the tid of the subprocedure the constraints apply to.
+pub fn parse_additional_constraints(input: &str) -> Result<Vec<AdditionalConstraint>, DslError> {
+    let mut cursor = Cursor::new(input);
+    let mut constraints = Vec::new();
+    cursor.skip_whitespace_and_comments();
+    while !cursor.is_eof() {
+        constraints.extend(cursor.parse_block()?);
+        cursor.expect_separator(None)?;
+    }
+    Ok(constraints)
+}
+
+/// Parses a whitespace separated list of tids.
+pub fn parse_tids(input: &str) -> Result<Vec<Tid>, DslError> {
+    let mut cursor = Cursor::new(input);
+    let mut tids = Vec::new();
+    cursor.skip_whitespace_and_comments();
+    while !cursor.is_eof() {
+        if cursor.rest().starts_with('@') {
+            cursor.pos += 1;
+        }
+        tids.push(cursor.parse_tid()?);
+        cursor.expect_separator(None)?;
+    }
+    Ok(tids)
+}
+
+/// Parses a lattice from `a <= b` relations between its elements and one declaration each of its top, bottom and weakest integral elements.
+pub fn parse_lattice(input: &str) -> Result<LatticeDefinition, DslError> {
+    let mut cursor = Cursor::new(input);
+    let mut relations = Vec::new();
+    let mut top = None;
+    let mut bottom = None;
+    let mut weakest_integral = None;
+    cursor.skip_whitespace_and_comments();
+    while !cursor.is_eof() {
+        let item_start = cursor.pos;
+        let name = cursor.parse_name("a lattice element or declaration")?;
+        cursor.skip_whitespace_and_comments();
+        if cursor.rest().starts_with("<=") {
+            cursor.pos += 2;
+            cursor.skip_whitespace_and_comments();
+            let greater = cursor.parse_name("a lattice element after '<='")?;
+            relations.push((name.to_owned(), greater.to_owned()));
+        } else {
+            let declared = match name {
+                "top" => &mut top,
+                "bottom" => &mut bottom,
+                "weakest_integral" => &mut weakest_integral,
+                _ => return Err(cursor.error(format!("expected '<=' after {}", name))),
+            };
+            if declared.is_some() {
+                cursor.pos = item_start;
+                return Err(cursor.error(format!("{} is declared more than once", name)));
+            }
+            *declared = Some(cursor.parse_name("a lattice element")?.to_owned());
+        }
+        cursor.expect_separator(None)?;
+    }
+
+    let missing =
+        |declaration: &str| cursor.error(format!("the lattice has no {} declaration", declaration));
+    Ok(LatticeDefinition::new(
+        relations,
+        top.ok_or_else(|| missing("top"))?,
+        bottom.ok_or_else(|| missing("bottom"))?,
+        weakest_integral.ok_or_else(|| missing("weakest_integral"))?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use cwe_checker_lib::intermediate_representation::Tid;
+
+    use crate::constraints::{DerivedTypeVar, Field, FieldLabel, TypeVariable};
+    use crate::solver::type_lattice::LatticeDefinition;
+
+    use super::{parse_additional_constraints, parse_lattice, parse_tids};
+
+    #[test]
+    fn parses_blocks_with_comments() {
+        let cons = parse_additional_constraints(
+            "
+            // the first block
+            @FUN_00101234 { FUN_00101234.in_0.load.σ32@0 <= int }
+            @sub_00101300:00101300 {
+                # an out parameter with a negative offset
+                sub_00101300.out_1.load.σ64@-8 <= char // trailing comment
+            }
+            ",
+        )
+        .expect("should parse");
+
+        assert_eq!(cons.len(), 2);
+        assert_eq!(
+            cons[0].associated_variable,
+            Tid::create("FUN_00101234".to_owned(), "00101234".to_owned())
+        );
+
+        let mut expected_lhs = DerivedTypeVar::new(TypeVariable::new("sub_00101300".to_owned()));
+        expected_lhs.add_field_label(FieldLabel::Out(1));
+        expected_lhs.add_field_label(FieldLabel::Load);
+        expected_lhs.add_field_label(FieldLabel::Field(Field {
+            size: 64,
+            offset: -8,
+        }));
+        assert_eq!(cons[1].constraint.lhs, expected_lhs);
+        assert_eq!(
+            cons[1].associated_variable,
+            Tid::create("sub_00101300".to_owned(), "00101300".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_line_and_column() {
+        let err = parse_additional_constraints("@FUN_00101234 {\n  x <= int\n  y < int\n}")
+            .expect_err("should not parse");
+        assert_eq!((err.line, err.column), (3, 3));
+
+        let err = parse_additional_constraints("\n@FUN_00101234 {\n  x <= int\n")
+            .expect_err("should not parse");
+        assert_eq!((err.line, err.column), (2, 15));
+    }
+
+    #[test]
+    fn parses_tids() {
+        assert_eq!(
+            parse_tids("sub_00001000\n@FUN_00002000:2000 // comment\n").expect("should parse"),
+            vec![
+                Tid::create("sub_00001000".to_owned(), "00001000".to_owned()),
+                Tid::create("FUN_00002000".to_owned(), "2000".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_lattices() {
+        let lattice = parse_lattice(
+            "
+            // declarations may come after the relations that use them
+            bottom <= char
+            char <= int # trailing comment
+            int <= T
+            top T
+            bottom bottom
+            weakest_integral int
+            ",
+        )
+        .expect("should parse");
+        let expected = LatticeDefinition::new(
+            vec![
+                ("bottom".to_owned(), "char".to_owned()),
+                ("char".to_owned(), "int".to_owned()),
+                ("int".to_owned(), "T".to_owned()),
+            ],
+            "T".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        );
+        assert_eq!(
+            serde_json::to_value(&lattice).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+
+        let err = parse_lattice("top T\nbottom b\ntop U\n").expect_err("should not parse");
+        assert_eq!((err.line, err.column), (3, 1));
+        let err = parse_lattice("top T\nb <= T\n").expect_err("should not parse");
+        assert_eq!((err.line, err.column), (3, 1));
+        let err = parse_lattice("top T\nb < T\n").expect_err("should not parse");
+        assert_eq!((err.line, err.column), (2, 3));
+    }
+}
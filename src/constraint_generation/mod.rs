@@ -1,7 +1,8 @@
 use cwe_checker_lib::{
     analysis::graph::{Edge, Graph, Node},
     intermediate_representation::{
-        Arg, BinOpType, Bitvector, Blk, Def, ExternSymbol, Jmp, Sub, Term, UnOpType,
+        Arg, BinOpType, Bitvector, Blk, CastOpType, Def, ExternSymbol, Jmp, Project, Sub, Term,
+        UnOpType,
     },
 };
 
@@ -12,16 +13,90 @@ use cwe_checker_lib::intermediate_representation::{ByteSize, Expression, Variabl
 
 use cwe_checker_lib::intermediate_representation::Tid;
 
+use crate::analysis::memory_returns::MemoryReturn;
 use crate::constraints::{
     AddConstraint, ConstraintSet, DerivedTypeVar, Field, FieldLabel, SubtypeConstraint,
     TyConstraint, TypeVariable, VariableManager,
 };
+use crate::solver::type_lattice::{
+    float_type_for_size, CONST_STRING_TYPE, DOUBLE_TYPE, LONG_DOUBLE_TYPE, STRING_TYPE,
+};
 
 use std::{
     collections::{btree_set::BTreeSet, BTreeMap, HashMap, HashSet},
     convert::TryInto,
 };
 
+/// Gets the bit position and width of a constant mask made up of a single run of set bits.
+fn contiguous_mask(expr: &Expression) -> Option<(usize, usize)> {
+    let mask = match expr {
+        Expression::Const(bv) => bv.try_to_u64().ok()?,
+        _ => return None,
+    };
+    if mask == 0 {
+        return None;
+    }
+
+    let pos = mask.trailing_zeros();
+    let run = mask >> pos;
+    if run & run.wrapping_add(1) != 0 {
+        return None;
+    }
+    Some((pos as usize, run.count_ones() as usize))
+}
+
+/// Splits a masking operation into the masked expression and the mask's position and width.
+fn split_mask<'b>(
+    lhs: &'b Expression,
+    rhs: &'b Expression,
+) -> Option<(&'b Expression, (usize, usize))> {
+    contiguous_mask(rhs)
+        .map(|mask| (lhs, mask))
+        .or_else(|| contiguous_mask(lhs).map(|mask| (rhs, mask)))
+}
+
+/// Recognizes `(x >> s) & m` and `x & m` where m is a run of bits that does not cover whole bytes, giving the bitfield of x that is extracted.
+fn bitfield_extract<'b>(
+    lhs: &'b Expression,
+    rhs: &'b Expression,
+) -> Option<(&'b Expression, Field)> {
+    let (masked, (pos, width)) = split_mask(lhs, rhs)?;
+    let (inner, shift) = match masked {
+        Expression::BinOp {
+            op: BinOpType::IntRight | BinOpType::IntSRight,
+            lhs: shifted,
+            rhs: amount,
+        } => match amount.as_ref() {
+            Expression::Const(amount) => {
+                (shifted.as_ref(), amount.try_to_u64().ok()?.try_into().ok()?)
+            }
+            _ => return None,
+        },
+        _ => (masked, 0),
+    };
+
+    let fld = Field::new_bitfield(0, shift.checked_add(pos)?, width)?;
+    if fld.is_bitfield() {
+        Some((inner, fld))
+    } else {
+        None
+    }
+}
+
+/// Recognizes `x | m` where m is a run of bits that does not cover whole bytes, giving the bitfield of x that is set.
+fn bitfield_insert<'b>(
+    lhs: &'b Expression,
+    rhs: &'b Expression,
+) -> Option<(&'b Expression, Field)> {
+    let (inner, (pos, width)) = split_mask(lhs, rhs)?;
+    let fld = Field::new_bitfield(0, pos, width)?;
+    if fld.is_bitfield() {
+        Some((inner, fld))
+    } else {
+        None
+    }
+}
+
 /// Gets a type variable for a [Tid] where multiple type variables need to exist at that [Tid] which are distinguished by which [Variable] they operate over.
 pub fn tid_indexed_by_variable(tid: &Tid, var: &Variable) -> TypeVariable {
     TypeVariable::new(tid.get_str_repr().to_owned() + "_" + &var.name)
@@ -57,6 +132,263 @@ pub fn arg_tvar(index: usize, target_sub: &Tid) -> TypeVariable {
     TypeVariable::new(format!("arg_{}_{}", target_sub.get_str_repr(), index))
 }
 
+const COMPARISON_PREFIX: &str = "cmp_";
+
+/// Creates the type variable for the values compared against constants at a [Tid].
+/// The constants themselves are collected by [collect_comparison_constants] and annotate the sketch node of this variable.
+pub fn comparison_tvar(tid: &Tid) -> TypeVariable {
+    TypeVariable::new(format!("{}{}", COMPARISON_PREFIX, tid.get_str_repr()))
+}
+
+/// Checks if a type variable was created by [comparison_tvar].
+pub fn is_comparison_tvar(tv: &TypeVariable) -> bool {
+    !tv.is_global() && tv.to_callee().get_name().starts_with(COMPARISON_PREFIX)
+}
+
+/// Gets the value compared for (in)equality with a constant and the constant.
+fn equality_comparison(value: &Expression) -> Option<(&Expression, i64)> {
+    match value {
+        Expression::BinOp {
+            op: BinOpType::IntEqual | BinOpType::IntNotEqual,
+            lhs,
+            rhs,
+        } => match (lhs.as_ref(), rhs.as_ref()) {
+            (Expression::Const(_), Expression::Const(_)) => None,
+            (compared, Expression::Const(c)) | (Expression::Const(c), compared) => {
+                c.try_to_i64().ok().map(|c| (compared, c))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The most cases a switch guard is trusted to bound.
+const MAX_SWITCH_CASES: i64 = 256;
+
+/// Finds the definition in a block that guards a switch, the value it bounds and the largest case of the switch.
+/// A guard jumps to a block ending in an indirect branch after an unsigned comparison of the switched value against the largest case.
+// NOTE(Ian): `x > N` jumping to the default case is assumed, so a guard of the form `x < N` gains one extra case
+fn switch_guard<'a>(
+    blk: &'a Term<Blk>,
+    sub: &Term<Sub>,
+) -> Option<(&'a Term<Def>, &'a Expression, i64)> {
+    let guards_indirect_branch = blk.term.jmps.iter().any(|jmp| {
+        let target = match &jmp.term {
+            Jmp::CBranch { target, .. } | Jmp::Branch(target) => target,
+            _ => return false,
+        };
+        sub.term
+            .blocks
+            .iter()
+            .filter(|tgt_blk| &tgt_blk.tid == target)
+            .flat_map(|tgt_blk| tgt_blk.term.jmps.iter())
+            .any(|tgt_jmp| matches!(tgt_jmp.term, Jmp::BranchInd(_)))
+    });
+    let is_conditional = blk
+        .term
+        .jmps
+        .iter()
+        .any(|jmp| matches!(jmp.term, Jmp::CBranch { .. }));
+    if !guards_indirect_branch || !is_conditional {
+        return None;
+    }
+
+    blk.term.defs.iter().rev().find_map(|df| match &df.term {
+        Def::Assign {
+            value:
+                Expression::BinOp {
+                    op: BinOpType::IntLess | BinOpType::IntLessEqual,
+                    lhs,
+                    rhs,
+                },
+            ..
+        } => match (lhs.as_ref(), rhs.as_ref()) {
+            (Expression::Const(_), Expression::Const(_)) => None,
+            (compared, Expression::Const(c)) | (Expression::Const(c), compared) => c
+                .try_to_i64()
+                .ok()
+                .filter(|c| (0..MAX_SWITCH_CASES).contains(c))
+                .map(|c| (df, compared, c)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Checks if a block can reach itself through the jumps of its subprocedure.
+fn is_in_loop(blk: &Term<Blk>, sub: &Term<Sub>) -> bool {
+    let successors = |blk: &Term<Blk>| -> Vec<Tid> {
+        blk.term
+            .jmps
+            .iter()
+            .filter_map(|jmp| match &jmp.term {
+                Jmp::Branch(target) | Jmp::CBranch { target, .. } => Some(target.clone()),
+                Jmp::Call { return_, .. }
+                | Jmp::CallInd { return_, .. }
+                | Jmp::CallOther { return_, .. } => return_.clone(),
+                _ => None,
+            })
+            .collect()
+    };
+    let blocks: HashMap<&Tid, &Term<Blk>> =
+        sub.term.blocks.iter().map(|blk| (&blk.tid, blk)).collect();
+
+    let mut visited = HashSet::new();
+    let mut worklist = successors(blk);
+    while let Some(tid) = worklist.pop() {
+        if tid == blk.tid {
+            return true;
+        }
+
+        if let Some(succ) = blocks.get(&tid) {
+            if visited.insert(tid) {
+                worklist.extend(successors(succ));
+            }
+        }
+    }
+    false
+}
+
+/// Checks if the value is one of the carriers, or a copy, extension or low byte of one.
+fn carries_value(value: &Expression, carriers: &HashSet<&Variable>) -> bool {
+    match value {
+        Expression::Var(var) => carriers.contains(var),
+        Expression::Cast {
+            op: CastOpType::IntZExt | CastOpType::IntSExt,
+            arg,
+            ..
+        } => carries_value(arg, carriers),
+        Expression::Subpiece { low_byte, arg, .. } => {
+            *low_byte == ByteSize::new(0) && carries_value(arg, carriers)
+        }
+        // `TEST AL, AL` ands the value with itself
+        Expression::BinOp {
+            op: BinOpType::IntAnd,
+            lhs,
+            rhs,
+        } => lhs == rhs && carries_value(lhs, carriers),
+        _ => false,
+    }
+}
+
+/// Finds the loads of a single byte in a loop that is then compared against zero, the characters of a string being scanned for its terminator.
+fn string_scan_loads<'a>(blk: &'a Term<Blk>, sub: &Term<Sub>) -> Vec<&'a Term<Def>> {
+    if !is_in_loop(blk, sub) {
+        return Vec::new();
+    }
+
+    let defs = &blk.term.defs;
+    defs.iter()
+        .enumerate()
+        .filter(|(idx, df)| {
+            let loaded = match &df.term {
+                Def::Load { var, .. } if var.size == ByteSize::new(1) => var,
+                _ => return false,
+            };
+
+            let mut carriers = HashSet::new();
+            carriers.insert(loaded);
+            for later in defs[idx + 1..].iter() {
+                match &later.term {
+                    Def::Assign { var, value } => {
+                        let compared_to_zero = match equality_comparison(value) {
+                            Some((compared, 0)) => carries_value(compared, &carriers),
+                            _ => false,
+                        };
+                        if compared_to_zero {
+                            return true;
+                        }
+
+                        if carries_value(value, &carriers) {
+                            carriers.insert(var);
+                        } else {
+                            carriers.remove(var);
+                        }
+                    }
+                    Def::Load { var, .. } => {
+                        carriers.remove(var);
+                    }
+                    Def::Store { .. } => (),
+                }
+            }
+            false
+        })
+        .map(|(_, df)| df)
+        .collect()
+}
+
+/// The characters of a string are loaded into the register defined by a string scan load.
+fn string_scan_constraint(tid: &Tid, var: &Variable) -> TyConstraint {
+    TyConstraint::SubTy(SubtypeConstraint::new(
+        DerivedTypeVar::new(tid_indexed_by_variable(tid, var)),
+        DerivedTypeVar::new(TypeVariable::new(STRING_TYPE.to_owned())),
+    ))
+}
+
+/// Collects the constants compared at each comparison in the program, keyed by the comparison's [comparison_tvar].
+/// Equality comparisons compare against their constant and switch guards against every case of their switch.
+pub fn collect_comparison_constants(proj: &Project) -> BTreeMap<TypeVariable, BTreeSet<i64>> {
+    let mut constants: BTreeMap<TypeVariable, BTreeSet<i64>> = BTreeMap::new();
+    for sub in proj.program.term.subs.values() {
+        for blk in sub.term.blocks.iter() {
+            for df in blk.term.defs.iter() {
+                if let Def::Assign { value, .. } = &df.term {
+                    if let Some((_, c)) = equality_comparison(value) {
+                        constants
+                            .entry(comparison_tvar(&df.tid))
+                            .or_default()
+                            .insert(c);
+                    }
+                }
+            }
+
+            if let Some((guard, _, largest_case)) = switch_guard(blk, sub) {
+                constants
+                    .entry(comparison_tvar(&guard.tid))
+                    .or_default()
+                    .extend(0..=largest_case);
+            }
+        }
+    }
+    constants
+}
+
+/// Gets the float type of a value of the given size.
+fn float_tvar(sz: ByteSize) -> Option<DerivedTypeVar> {
+    float_type_for_size(u64::from(sz))
+        .map(|ty| DerivedTypeVar::new(TypeVariable::new(ty.to_owned())))
+}
+
+/// Arguments passed in vector registers are floats, and x87 registers only hold extended precision floats.
+fn float_register_type(arg: &Arg) -> Option<&'static str> {
+    let var = match arg {
+        Arg::Register {
+            expr: Expression::Var(var),
+            ..
+        } => var,
+        _ => return None,
+    };
+
+    let name = var.name.to_uppercase();
+    let is_vector = ["XMM", "YMM", "ZMM"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix));
+    let is_x87 =
+        name.len() > 2 && name.starts_with("ST") && name[2..].chars().all(|c| c.is_ascii_digit());
+
+    if is_x87 {
+        Some(LONG_DOUBLE_TYPE)
+    } else if is_vector {
+        // NOTE(Ian): a whole vector register is assumed to hold a scalar double
+        float_type_for_size(u64::from(var.size))
+            .filter(|ty| *ty != LONG_DOUBLE_TYPE)
+            .or(Some(DOUBLE_TYPE))
+    } else {
+        None
+    }
+}
+
 /// A [NodeContextMapping] provides information about the program state at a given CFG node. Because basic blocks contain multiple defs
 /// contexts must be capable of reapplying def terms to compute the intermediate states.
 pub trait NodeContextMapping: Clone {
@@ -72,6 +404,11 @@ pub trait NodeContextMapping: Clone {
 pub trait RegisterMapping: NodeContextMapping {
     /// Returns the set of TypeVariables that define the target variable at this point. We return the set rather than grouped constraints so that the client can decide the direction in which constraints should go
     fn access(&self, var: &Variable) -> BTreeSet<TypeVariable>;
+
+    /// Checks if every definition of the variable that reaches this point loads it from memory.
+    fn is_loaded(&self, _var: &Variable) -> bool {
+        false
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -83,6 +420,61 @@ pub struct TypeVariableAccess {
     pub sz: ByteSize,
     /// The potential constant offset at which the access occurs
     pub offset: Option<i64>,
+    /// The range of the offset when it is not constant, ie. an access into an array
+    pub offset_range: Option<OffsetRange>,
+}
+
+/// Offset ranges spanning more than this come from widened or unbounded offsets rather than from an object.
+const MAX_OFFSET_RANGE_SPAN: i64 = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+/// A bounded range of offsets an access may occur at, such as the elements of an array.
+pub struct OffsetRange {
+    /// The least offset in the range
+    pub lower: i64,
+    /// The greatest offset in the range, inclusive
+    pub upper: i64,
+    /// The distance between consecutive offsets in the range
+    pub stride: u64,
+}
+
+impl OffsetRange {
+    /// Creates an offset range, returning [None] if the bounds are out of order or span further than any object would.
+    pub fn new(lower: i64, upper: i64, stride: u64) -> Option<OffsetRange> {
+        let span = upper.checked_sub(lower)?;
+        if (0..=MAX_OFFSET_RANGE_SPAN).contains(&span) {
+            Some(OffsetRange {
+                lower,
+                upper,
+                stride,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The number of offsets in the range. A range without a stride only holds its lower bound.
+    pub fn element_count(&self) -> u64 {
+        // the span is bounded by the constructor so it fits in a u64
+        let span = (self.upper - self.lower) as u64;
+        span.checked_div(self.stride).map_or(1, |steps| steps + 1)
+    }
+}
+
+impl TypeVariableAccess {
+    /// Gets the field of the type variable reached by this access. An access into a range reaches an array field with an element at every stride of the range.
+    /// A range whose stride is smaller than the access cannot hold separate elements, so it only reaches the field at its lower bound.
+    pub fn accessed_field(&self) -> Option<Field> {
+        let size = self.sz.as_bit_length();
+        if let Some(off) = self.offset {
+            return Some(Field::new(off, size));
+        }
+
+        self.offset_range.map(|range| {
+            Field::new_array(range.lower, size, range.stride, range.element_count())
+                .unwrap_or_else(|| Field::new(range.lower, size))
+        })
+    }
 }
 
 /// Maps an address expression and a size to the possible type variables representing the loaded address at this program point.
@@ -120,6 +512,9 @@ pub trait SubprocedureLocators: NodeContextMapping {
         points_to: &impl PointsToMapping,
         vm: &mut VariableManager,
     ) -> BTreeSet<ArgTvar>;
+
+    /// Gets the results the subprocedure returns through memory pointed to by its parameters.
+    fn get_memory_returns(&self, sub: &Tid) -> Vec<MemoryReturn>;
 }
 
 /// Represents analysis results that can convert accesses to constant addresses to global variables.
@@ -138,6 +533,84 @@ pub trait ConstantResolver: NodeContextMapping {
 
     /// Produces a variable only if there is an exact match, does not force resolution
     fn maybe_resolve_constant_to_variable(&self, target: &Bitvector) -> Option<DerivedTypeVar>;
+
+    /// Checks if a variable produced by this resolver is the address of a string literal in read-only memory.
+    fn is_string_literal(&self, _var: &DerivedTypeVar) -> bool {
+        false
+    }
+}
+
+/// Parameters of known externs that are pointers to strings, by extern name and parameter index.
+const STRING_EXTERN_PARAMS: &[(&str, &[usize])] = &[
+    ("atof", &[0]),
+    ("atoi", &[0]),
+    ("atol", &[0]),
+    ("fopen", &[0, 1]),
+    ("fprintf", &[1]),
+    ("fputs", &[0]),
+    ("getenv", &[0]),
+    ("open", &[0]),
+    ("perror", &[0]),
+    ("printf", &[0]),
+    ("puts", &[0]),
+    ("scanf", &[0]),
+    ("snprintf", &[0, 2]),
+    ("sprintf", &[0, 1]),
+    ("sscanf", &[0, 1]),
+    ("strcat", &[0, 1]),
+    ("strchr", &[0]),
+    ("strcmp", &[0, 1]),
+    ("strcpy", &[0, 1]),
+    ("strdup", &[0]),
+    ("strlen", &[0]),
+    ("strncat", &[0, 1]),
+    ("strncmp", &[0, 1]),
+    ("strncpy", &[0, 1]),
+    ("strrchr", &[0]),
+    ("strstr", &[0, 1]),
+    ("strtol", &[0]),
+    ("strtoul", &[0]),
+    ("system", &[0]),
+];
+
+/// Known externs that return a pointer to a string.
+const STRING_EXTERN_RETURNS: &[&str] = &[
+    "fgets", "getenv", "strcat", "strchr", "strcpy", "strdup", "strncat", "strncpy", "strrchr",
+    "strstr",
+];
+
+/// Relates an out parameter to the object a returned pointer points to. The access size is the size of the pointer rather than of anything in the object,
+/// so the object is related as a whole instead of through a field of that size.
+// NOTE(Ian): a pointer into the middle of an object would need the object's fields shifted by the offset, which is not expressible without
+// knowing the fields, so only pointers to the start of an object are related.
+fn returned_pointer_constraints(
+    out: &DerivedTypeVar,
+    acc: &TypeVariableAccess,
+) -> Vec<TyConstraint> {
+    if acc.offset != Some(0) {
+        return Vec::new();
+    }
+
+    let object = DerivedTypeVar::new(acc.ty_var.clone());
+    let mut loaded = out.clone();
+    loaded.add_field_label(FieldLabel::Load);
+    let mut stored = out.clone();
+    stored.add_field_label(FieldLabel::Store);
+
+    vec![
+        TyConstraint::SubTy(SubtypeConstraint::new(object.clone(), loaded)),
+        TyConstraint::SubTy(SubtypeConstraint::new(stored, object)),
+    ]
+}
+
+/// The characters pointed to by a string pointer are subtypes of the string type.
+fn string_pointer_constraint(mut ptr: DerivedTypeVar, string_type: &str) -> TyConstraint {
+    ptr.add_field_label(FieldLabel::Load);
+    ptr.add_field_label(FieldLabel::Field(Field::new(0, 8)));
+    TyConstraint::SubTy(SubtypeConstraint::new(
+        ptr,
+        DerivedTypeVar::new(TypeVariable::new(string_type.to_owned())),
+    ))
 }
 
 // TODO(ian): this should have some sort of function on it that takes a lambda and basically joins constraints together to acess the derived type variable or something to prevent
@@ -372,6 +845,58 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         }
     }
 
+    /// Bitfields are only recognized in fields loaded from memory, masks over other values such as flags or aligned pointers stay primitive.
+    fn is_loaded_field(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Var(var) => self.reg_map.is_loaded(var),
+            _ => false,
+        }
+    }
+
+    fn eval_bitfield_extract(
+        &self,
+        inner: &Expression,
+        fld: Field,
+        defining_tvars_are_subtype_of_repr: bool,
+        vman: &mut VariableManager,
+    ) -> (DerivedTypeVar, ConstraintSet) {
+        let (mut inner_repr, mut cons) =
+            self.evaluate_expression(inner, defining_tvars_are_subtype_of_repr, vman);
+        inner_repr.add_field_label(FieldLabel::Field(fld));
+
+        let (repr, int_cons) = self.assume_weak_integral(vman);
+        cons.insert_all(&int_cons);
+        cons.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+            inner_repr,
+            repr.clone(),
+        )));
+        (repr, cons)
+    }
+
+    fn eval_bitfield_insert(
+        &self,
+        inner: &Expression,
+        fld: Field,
+        defining_tvars_are_subtype_of_repr: bool,
+        vman: &mut VariableManager,
+    ) -> (DerivedTypeVar, ConstraintSet) {
+        let (inner_repr, mut cons) =
+            self.evaluate_expression(inner, defining_tvars_are_subtype_of_repr, vman);
+        let repr = DerivedTypeVar::new(vman.fresh());
+        let mut inserted = repr.clone();
+        inserted.add_field_label(FieldLabel::Field(fld));
+
+        cons.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+            inner_repr,
+            repr.clone(),
+        )));
+        cons.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+            inserted,
+            DerivedTypeVar::new(self.weakest_integral_type.clone()),
+        )));
+        (repr, cons)
+    }
+
     fn evaluate_binop(
         &self,
         op: &BinOpType,
@@ -402,14 +927,46 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
                 defining_tvars_are_subtype_of_repr,
                 vman,
             ),
-            _ => {
-                let repr = vman.fresh();
-                warn!("Unhandled binop type: {:?}, representing with {}", op, repr);
-                (DerivedTypeVar::new(repr), ConstraintSet::default())
+            BinOpType::FloatAdd
+            | BinOpType::FloatSub
+            | BinOpType::FloatMult
+            | BinOpType::FloatDiv => self.eval_float_op(
+                &[lhs, rhs],
+                Some(lhs.bytesize()),
+                defining_tvars_are_subtype_of_repr,
+                vman,
+            ),
+            BinOpType::FloatEqual
+            | BinOpType::FloatNotEqual
+            | BinOpType::FloatLess
+            | BinOpType::FloatLessEqual => {
+                self.eval_float_op(&[lhs, rhs], None, defining_tvars_are_subtype_of_repr, vman)
             }
+            BinOpType::IntAnd => match bitfield_extract(lhs, rhs) {
+                Some((inner, fld)) if self.is_loaded_field(inner) => {
+                    self.eval_bitfield_extract(inner, fld, defining_tvars_are_subtype_of_repr, vman)
+                }
+                _ => Self::unhandled_binop(op, vman),
+            },
+            BinOpType::IntOr => match bitfield_insert(lhs, rhs) {
+                Some((inner, fld)) if self.is_loaded_field(inner) => {
+                    self.eval_bitfield_insert(inner, fld, defining_tvars_are_subtype_of_repr, vman)
+                }
+                _ => Self::unhandled_binop(op, vman),
+            },
+            _ => Self::unhandled_binop(op, vman),
         }
     }
 
+    fn unhandled_binop(
+        op: &BinOpType,
+        vman: &mut VariableManager,
+    ) -> (DerivedTypeVar, ConstraintSet) {
+        let repr = vman.fresh();
+        warn!("Unhandled binop type: {:?}, representing with {}", op, repr);
+        (DerivedTypeVar::new(repr), ConstraintSet::default())
+    }
+
     fn unhandled_expr(
         value: &Expression,
         vman: &mut VariableManager,
@@ -434,6 +991,64 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         (dtv, cs)
     }
 
+    /// The value is a float of the given size, or an unknown float if the size is not a float size.
+    fn assume_float(
+        &self,
+        sz: ByteSize,
+        vman: &mut VariableManager,
+    ) -> (DerivedTypeVar, ConstraintSet) {
+        let dtv = DerivedTypeVar::new(vman.fresh());
+        let mut cs = ConstraintSet::default();
+        if let Some(float_ty) = float_tvar(sz) {
+            cs.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                dtv.clone(),
+                float_ty,
+            )));
+        }
+        (dtv, cs)
+    }
+
+    /// Evaluates a float operation where each operand is a float of its size. The result is a float of the result size,
+    /// or an integer if the operation has no float result (comparisons and truncation).
+    fn eval_float_op(
+        &self,
+        operands: &[&Expression],
+        result_size: Option<ByteSize>,
+        defining_tvars_are_subtype_of_repr: bool,
+        vman: &mut VariableManager,
+    ) -> (DerivedTypeVar, ConstraintSet) {
+        let mut cons = ConstraintSet::default();
+        for operand in operands {
+            if let Some(float_ty) = float_tvar(operand.bytesize()) {
+                let (operand_repr, operand_cons) =
+                    self.evaluate_expression(operand, defining_tvars_are_subtype_of_repr, vman);
+                cons.insert_all(&operand_cons);
+                cons.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                    operand_repr,
+                    float_ty,
+                )));
+            }
+        }
+
+        let (repr, result_cons) = match result_size {
+            Some(sz) => self.assume_float(sz, vman),
+            None => self.assume_weak_integral(vman),
+        };
+        cons.insert_all(&result_cons);
+        (repr, cons)
+    }
+
+    fn string_literal_constraints(&self, resolved: &DerivedTypeVar) -> ConstraintSet {
+        let mut cons = ConstraintSet::default();
+        if self.constant_resolver.is_string_literal(resolved) {
+            cons.insert(string_pointer_constraint(
+                resolved.clone(),
+                CONST_STRING_TYPE,
+            ));
+        }
+        cons
+    }
+
     fn evaluate_expression(
         &self,
         value: &Expression,
@@ -444,7 +1059,8 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
             .points_to
             .get_pointer_variable(value, &self.constant_resolver)
         {
-            return (gvar, ConstraintSet::default());
+            let cons = self.string_literal_constraints(&gvar);
+            return (gvar, cons);
         }
 
         match &value {
@@ -480,11 +1096,27 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
             Expression::BinOp { op, lhs, rhs } => {
                 self.evaluate_binop(op, lhs, rhs, defining_tvars_are_subtype_of_repr, vman)
             }
-            Expression::Cast {
-                op,
-                size: _,
-                arg: _,
-            } => match op {
+            Expression::UnOp { op, arg } => match op {
+                UnOpType::FloatNegate
+                | UnOpType::FloatAbs
+                | UnOpType::FloatSqrt
+                | UnOpType::FloatCeil
+                | UnOpType::FloatFloor
+                | UnOpType::FloatRound => self.eval_float_op(
+                    &[arg.as_ref()],
+                    Some(arg.bytesize()),
+                    defining_tvars_are_subtype_of_repr,
+                    vman,
+                ),
+                UnOpType::FloatNaN => self.eval_float_op(
+                    &[arg.as_ref()],
+                    None,
+                    defining_tvars_are_subtype_of_repr,
+                    vman,
+                ),
+                _ => Self::unhandled_expr(value, vman),
+            },
+            Expression::Cast { op, size, arg } => match op {
                 cwe_checker_lib::intermediate_representation::CastOpType::IntZExt => {
                     self.assume_weak_integral(vman)
                 }
@@ -492,27 +1124,54 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
                     self.assume_weak_integral(vman)
                 }
                 cwe_checker_lib::intermediate_representation::CastOpType::Int2Float => {
-                    Self::unhandled_expr(value, vman)
-                }
-                cwe_checker_lib::intermediate_representation::CastOpType::Float2Float => {
-                    Self::unhandled_expr(value, vman)
-                }
-                cwe_checker_lib::intermediate_representation::CastOpType::Trunc => {
-                    self.assume_weak_integral(vman)
+                    self.assume_float(*size, vman)
                 }
+                cwe_checker_lib::intermediate_representation::CastOpType::Float2Float => self
+                    .eval_float_op(
+                        &[arg.as_ref()],
+                        Some(*size),
+                        defining_tvars_are_subtype_of_repr,
+                        vman,
+                    ),
+                // Truncates a float to an integer
+                cwe_checker_lib::intermediate_representation::CastOpType::Trunc => self
+                    .eval_float_op(
+                        &[arg.as_ref()],
+                        None,
+                        defining_tvars_are_subtype_of_repr,
+                        vman,
+                    ),
                 cwe_checker_lib::intermediate_representation::CastOpType::PopCount => {
                     Self::unhandled_expr(value, vman)
                 }
             },
-            Expression::Const(value) => (
-                self.constant_resolver
-                    .resolve_constant_to_variable(value, vman),
-                ConstraintSet::default(),
-            ),
+            Expression::Const(value) => {
+                let resolved = self
+                    .constant_resolver
+                    .resolve_constant_to_variable(value, vman);
+                let cons = self.string_literal_constraints(&resolved);
+                (resolved, cons)
+            }
             _ => Self::unhandled_expr(value, vman), // TODO(ian) handle additional constraints, add/sub
         }
     }
 
+    /// Values compared against constants are subtypes of the comparison's variable, so lowering can recover enums from the
+    /// constants annotating that variable's node.
+    fn apply_comparison(
+        &self,
+        tid: &Tid,
+        compared: &Expression,
+        vman: &mut VariableManager,
+    ) -> ConstraintSet {
+        let (compared_repr, mut cons) = self.evaluate_expression(compared, true, vman);
+        cons.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+            compared_repr,
+            DerivedTypeVar::new(comparison_tvar(tid)),
+        )));
+        cons
+    }
+
     fn apply_assign(
         &self,
         tid: &Tid,
@@ -527,6 +1186,9 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         for repr_cons in constraints.iter() {
             info!("{}", repr_cons);
         }
+        if let Some((compared, _)) = equality_comparison(value) {
+            constraints.insert_all(&self.apply_comparison(tid, compared, vman));
+        }
 
         let cons = Self::reg_update(tid, var, value_type_var);
         constraints.insert_all(&cons);
@@ -578,10 +1240,10 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         representation = simplify_path(&representation);
 
         for acc in tv_access.iter() {
-            if let Some(off) = acc.offset {
+            if let Some(fld) = acc.accessed_field() {
                 let mut dt_repr = DerivedTypeVar::new(acc.ty_var.clone());
 
-                dt_repr.add_field_label(FieldLabel::Field(Field::new(off, acc.sz.as_bit_length())));
+                dt_repr.add_field_label(FieldLabel::Field(fld));
 
                 let new_cons = if address_is_subtype {
                     SubtypeConstraint::new(representation.clone(), dt_repr)
@@ -712,6 +1374,12 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
                 index_to_field_label,
                 callsite.sub,
             );
+            if let Some(float_ty) = float_register_type(arg) {
+                start_constraints.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                    formal_tv.clone(),
+                    DerivedTypeVar::new(TypeVariable::new(float_ty.to_owned())),
+                )));
+            }
             let arg_tvars = self
                 .subprocedure_locators
                 .get_type_variables_and_constraints_for_arg(
@@ -756,7 +1424,7 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
 
     /// make each formal the subtype of the addressing info for this parameter within the current state
     fn handle_return_formals(&self, sub: &Term<Sub>, vman: &mut VariableManager) -> ConstraintSet {
-        self.make_constraints(
+        let mut cons = self.make_constraints(
             SubprocedureInvocation {
                 calling_blk: None,
                 sub,
@@ -766,7 +1434,34 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
             false,
             0,
             vman,
-        )
+        );
+        cons.insert_all(&self.handle_memory_return_formals(sub));
+        cons
+    }
+
+    /// Results returned through memory are out parameters numbered after the register returns. Values stored through the
+    /// pointer parameter flow into the out parameter.
+    fn handle_memory_return_formals(&self, sub: &Term<Sub>) -> ConstraintSet {
+        let mut cons = ConstraintSet::default();
+        for (i, mret) in self
+            .subprocedure_locators
+            .get_memory_returns(&sub.tid)
+            .into_iter()
+            .enumerate()
+        {
+            let mut stored = Self::create_formal_tvar(None, mret.param_index, &FieldLabel::In, sub);
+            stored.add_field_label(FieldLabel::Store);
+            let formal_tv = Self::create_formal_tvar(
+                None,
+                sub.term.formal_rets.len() + i,
+                &FieldLabel::Out,
+                sub,
+            );
+            cons.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                stored, formal_tv,
+            )));
+        }
+        cons
     }
 
     /*
@@ -780,7 +1475,7 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         vman: &mut VariableManager,
         return_address_displacement: i64,
     ) -> ConstraintSet {
-        self.make_constraints(
+        let mut cons = self.make_constraints(
             SubprocedureInvocation {
                 calling_blk: Some(calling_blk),
                 sub,
@@ -790,7 +1485,38 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
             true,
             return_address_displacement,
             vman,
-        )
+        );
+        cons.insert_all(&self.handle_returned_pointers(SubprocedureInvocation {
+            calling_blk: Some(calling_blk),
+            sub,
+            args: &sub.term.formal_rets,
+        }));
+        cons
+    }
+
+    /// When a returned register points to the start of an abstract object after the return, the callsite's out parameter can be loaded from and
+    /// stored to, reaching the object itself so that the object's fields are fields of the loaded value.
+    fn handle_returned_pointers<T>(&self, callsite: SubprocedureInvocation<T>) -> ConstraintSet {
+        let mut cons = ConstraintSet::default();
+        for (i, arg) in callsite.args.iter().enumerate() {
+            let var = match arg {
+                Arg::Register {
+                    expr: Expression::Var(var),
+                    ..
+                } => var,
+                _ => continue,
+            };
+
+            let out =
+                Self::create_formal_tvar(callsite.calling_blk, i, &FieldLabel::Out, callsite.sub);
+            for acc in self
+                .points_to
+                .points_to(&Expression::Var(var.clone()), var.size)
+            {
+                cons.extend(returned_pointer_constraints(&out, &acc));
+            }
+        }
+        cons
     }
 
     //TODO(Ian): implement callsite cloning
@@ -821,7 +1547,7 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         vman: &mut VariableManager,
         return_address_displacement: i64,
     ) -> ConstraintSet {
-        self.make_constraints(
+        let mut cons = self.make_constraints(
             SubprocedureInvocation {
                 calling_blk: Some(calling_blk),
                 sub,
@@ -831,7 +1557,20 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
             false,
             return_address_displacement,
             vman,
-        )
+        );
+
+        if let Some((_, params)) = STRING_EXTERN_PARAMS
+            .iter()
+            .find(|(name, _)| *name == sub.term.name)
+        {
+            for i in params.iter().filter(|i| **i < sub.term.parameters.len()) {
+                cons.insert(string_pointer_constraint(
+                    Self::create_formal_tvar(Some(calling_blk), *i, &FieldLabel::In, sub),
+                    STRING_TYPE,
+                ));
+            }
+        }
+        cons
     }
 
     fn handle_extern_actual_rets(
@@ -841,7 +1580,7 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
         vman: &mut VariableManager,
         return_address_displacement: i64,
     ) -> ConstraintSet {
-        self.make_constraints(
+        let mut cons = self.make_constraints(
             SubprocedureInvocation {
                 calling_blk: Some(calling_blk),
                 sub,
@@ -851,7 +1590,21 @@ impl<R: RegisterMapping, P: PointsToMapping, S: SubprocedureLocators, C: Constan
             true,
             return_address_displacement,
             vman,
-        )
+        );
+        cons.insert_all(&self.handle_returned_pointers(SubprocedureInvocation {
+            calling_blk: Some(calling_blk),
+            sub,
+            args: &sub.term.return_values,
+        }));
+        if STRING_EXTERN_RETURNS.contains(&sub.term.name.as_str())
+            && !sub.term.return_values.is_empty()
+        {
+            cons.insert(string_pointer_constraint(
+                Self::create_formal_tvar(Some(calling_blk), 0, &FieldLabel::Out, sub),
+                STRING_TYPE,
+            ));
+        }
+        cons
     }
 }
 
@@ -922,6 +1675,7 @@ where
     fn handle_block_start(
         nd_ctxt: NodeContext<R, P, S, C>,
         blk: &Term<Blk>,
+        sub: &Term<Sub>,
         vman: &mut VariableManager,
     ) -> ConstraintSet {
         info!("Starting block {}", blk.tid);
@@ -929,6 +1683,8 @@ where
             .defs
             .iter()
             .for_each(|x| info!("Has {} {:?}", x.tid, x.term));
+        let guard = switch_guard(blk, sub);
+        let scans = string_scan_loads(blk, sub);
         fold_over_definition_states(
             nd_ctxt,
             blk,
@@ -937,6 +1693,17 @@ where
                   curr_ctxt: &NodeContext<R, P, S, C>,
                   mut curr_constraints: ConstraintSet| {
                 curr_constraints.insert_all(&curr_ctxt.handle_def(df, vman));
+                if let Def::Load { var, .. } = &df.term {
+                    if scans.iter().any(|scan| scan.tid == df.tid) {
+                        curr_constraints.insert(string_scan_constraint(&df.tid, var));
+                    }
+                }
+                if let Some((guard_df, switched, _)) = guard {
+                    if guard_df.tid == df.tid {
+                        curr_constraints
+                            .insert_all(&curr_ctxt.apply_comparison(&df.tid, switched, vman));
+                    }
+                }
                 curr_constraints
             },
         )
@@ -1062,7 +1829,7 @@ where
                         total_cons.insert_all(&ent_cons);
                     }
                     let new_context: NodeContext<R, P, S, C> = (*nd_cont).clone();
-                    total_cons.insert_all(&Self::handle_block_start(new_context, blk, vman));
+                    total_cons.insert_all(&Self::handle_block_start(new_context, blk, sub, vman));
                     total_cons
                 }
                 Node::CallReturn {
@@ -1143,6 +1910,794 @@ where
                     .into_iter(),
             );
         }
+        let overlaps = overlap_constraints(&cs);
+        cs.insert_all(&overlaps);
         cs
     }
 }
+
+/// Gets the byte region shared by two overlapping fields as a path from `fld`, no sub-field is needed when the region is the whole field.
+fn overlap_region_path(fld: &Field, lower: i64, upper: i64) -> Vec<FieldLabel> {
+    let mut path = vec![FieldLabel::Field(fld.clone())];
+    if lower != fld.offset || upper != fld.offset + (fld.size / 8) as i64 {
+        path.push(FieldLabel::Field(Field::new(
+            lower - fld.offset,
+            ((upper - lower) * 8) as usize,
+        )));
+    }
+    path
+}
+
+/// Accesses of different widths that partially overlap at one object are related by equating the bytes they share,
+/// so lowering sees the overlap explicitly and can represent it as a union or a sub-field.
+pub fn overlap_constraints(cs: &ConstraintSet) -> ConstraintSet {
+    let mut fields: BTreeMap<&TypeVariable, BTreeSet<&Field>> = BTreeMap::new();
+    for dtv in cs.variables() {
+        match dtv.get_field_labels().first() {
+            // Bitfields are laid out within their containing field so they are not related by byte overlaps.
+            // Arrays span all of their elements rather than their first element, so they are left out as well.
+            Some(FieldLabel::Field(fld)) if !fld.is_bitfield() && fld.array.is_none() => {
+                fields
+                    .entry(dtv.get_base_variable())
+                    .or_default()
+                    .insert(fld);
+            }
+            _ => (),
+        }
+    }
+
+    let mut overlaps = ConstraintSet::default();
+    for (base, flds) in fields.iter() {
+        let flds: Vec<&Field> = flds.iter().cloned().collect();
+        for (i, fa) in flds.iter().enumerate() {
+            let fa_upper = fa.offset + (fa.size / 8) as i64;
+            // Fields are sorted by offset so once a field starts past the end of this one none of the following overlap.
+            for fb in flds[i + 1..].iter().take_while(|fb| fb.offset < fa_upper) {
+                let lower = std::cmp::max(fa.offset, fb.offset);
+                let upper = std::cmp::min(fa_upper, fb.offset + (fb.size / 8) as i64);
+                if upper <= lower {
+                    continue;
+                }
+
+                let shared_a = DerivedTypeVar::create_with_path(
+                    (*base).clone(),
+                    overlap_region_path(fa, lower, upper),
+                );
+                let shared_b = DerivedTypeVar::create_with_path(
+                    (*base).clone(),
+                    overlap_region_path(fb, lower, upper),
+                );
+                overlaps.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                    shared_a.clone(),
+                    shared_b.clone(),
+                )));
+                overlaps.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                    shared_b, shared_a,
+                )));
+            }
+        }
+    }
+    overlaps
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+    use cwe_checker_lib::intermediate_representation::{
+        Arg, BinOpType, Bitvector, Blk, ByteSize, CastOpType, Def, Expression, Jmp, Project, Term,
+        Tid, UnOpType, Variable,
+    };
+
+    use super::{
+        bitfield_extract, bitfield_insert, collect_comparison_constants, comparison_tvar,
+        float_register_type, is_comparison_tvar, overlap_constraints, returned_pointer_constraints,
+        string_pointer_constraint, string_scan_loads, ArgTvar, ConstantResolver, Context,
+        NodeContext, NodeContextMapping, OffsetRange, PointsToMapping, RegisterMapping,
+        SubprocedureLocators, TypeVariableAccess, STRING_EXTERN_PARAMS, STRING_EXTERN_RETURNS,
+    };
+    use crate::analysis::memory_returns::MemoryReturn;
+    use crate::constraints::{
+        ConstraintSet, DerivedTypeVar, Field, FieldArray, FieldLabel, SubtypeConstraint,
+        TyConstraint, TypeVariable, VariableManager,
+    };
+    use crate::lowering::{CType, LoweringContext, LoweringPolicy};
+    use crate::solver::type_lattice::{
+        LatticeDefinition, NamedLattice, DOUBLE_TYPE, FLOAT_TYPE, LONG_DOUBLE_TYPE, STRING_TYPE,
+    };
+    use crate::solver::type_sketch::{identity_element, insert_dtv, SketchBuilder};
+    use crate::test_utils::{load_composite_return_project, register, register_arg};
+    use crate::util::FileDebugLogger;
+
+    fn access(offset: Option<i64>, offset_range: Option<OffsetRange>) -> TypeVariableAccess {
+        TypeVariableAccess {
+            ty_var: TypeVariable::new("obj".to_owned()),
+            sz: ByteSize::new(4),
+            offset,
+            offset_range,
+        }
+    }
+
+    fn obj_field(path: Vec<Field>) -> DerivedTypeVar {
+        DerivedTypeVar::create_with_path(
+            TypeVariable::new("obj".to_owned()),
+            path.into_iter().map(FieldLabel::Field).collect(),
+        )
+    }
+
+    fn equal(a: DerivedTypeVar, b: DerivedTypeVar) -> Vec<TyConstraint> {
+        vec![
+            TyConstraint::SubTy(SubtypeConstraint::new(a.clone(), b.clone())),
+            TyConstraint::SubTy(SubtypeConstraint::new(b, a)),
+        ]
+    }
+
+    #[test]
+    fn bounded_offsets_access_array_fields() {
+        assert_eq!(
+            access(Some(8), None).accessed_field(),
+            Some(Field::new(8, 32))
+        );
+
+        let strided = access(None, OffsetRange::new(8, 40, 16))
+            .accessed_field()
+            .expect("a bounded range should reach a field");
+        assert_eq!(strided.offset, 8);
+        assert_eq!(strided.size, 32);
+        assert_eq!(
+            strided.array,
+            Some(FieldArray {
+                stride: 16,
+                count: 3
+            })
+        );
+
+        assert_eq!(
+            access(None, OffsetRange::new(0, 4096, 4))
+                .accessed_field()
+                .and_then(|fld| fld.array),
+            Some(FieldArray {
+                stride: 4,
+                count: 1025
+            })
+        );
+        // unaligned strides and singletons are not arrays
+        assert_eq!(
+            access(None, OffsetRange::new(0, 6, 2)).accessed_field(),
+            Some(Field::new(0, 32))
+        );
+        assert_eq!(
+            access(None, OffsetRange::new(4, 4, 0)).accessed_field(),
+            Some(Field::new(4, 32))
+        );
+        assert_eq!(access(None, None).accessed_field(), None);
+    }
+
+    #[test]
+    fn unbounded_offset_ranges_are_dropped() {
+        assert!(OffsetRange::new(12, 0, 4).is_none());
+        // a widened interval
+        assert!(OffsetRange::new(0, i64::MAX, 4).is_none());
+        // the bounds of a top interval overflow when subtracted
+        assert!(OffsetRange::new(i64::MIN, i64::MAX, 1).is_none());
+    }
+
+    #[test]
+    fn mixed_width_accesses_share_overlapping_bytes() {
+        let mut cs = ConstraintSet::default();
+        let target = DerivedTypeVar::new(TypeVariable::new("x".to_owned()));
+        for fld in [Field::new(0, 64), Field::new(4, 32), Field::new(6, 32)] {
+            cs.insert(TyConstraint::SubTy(SubtypeConstraint::new(
+                obj_field(vec![fld]),
+                target.clone(),
+            )));
+        }
+
+        let expected: ConstraintSet = vec![
+            equal(
+                obj_field(vec![Field::new(0, 64), Field::new(4, 32)]),
+                obj_field(vec![Field::new(4, 32)]),
+            ),
+            equal(
+                obj_field(vec![Field::new(0, 64), Field::new(6, 16)]),
+                obj_field(vec![Field::new(6, 32), Field::new(0, 16)]),
+            ),
+            equal(
+                obj_field(vec![Field::new(4, 32), Field::new(2, 16)]),
+                obj_field(vec![Field::new(6, 32), Field::new(0, 16)]),
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<std::collections::BTreeSet<_>>()
+        .into();
+
+        assert_eq!(overlap_constraints(&cs), expected);
+    }
+
+    #[test]
+    fn returned_pointers_make_the_object_loadable_from_the_out_parameter() {
+        let out = DerivedTypeVar::create_with_path(
+            TypeVariable::new("sub_00001000".to_owned()),
+            vec![FieldLabel::Out(0)],
+        );
+
+        // the object's fields are reached through the load, no field of the pointer's size is invented
+        let cons = returned_pointer_constraints(&out, &access(Some(0), None))
+            .iter()
+            .map(|cons| cons.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cons,
+            vec![
+                "obj ⊑ sub_00001000.out_0.load",
+                "sub_00001000.out_0.store ⊑ obj"
+            ]
+        );
+
+        assert!(returned_pointer_constraints(&out, &access(Some(8), None)).is_empty());
+        assert!(
+            returned_pointer_constraints(&out, &access(None, OffsetRange::new(0, 16, 8)))
+                .is_empty()
+        );
+    }
+
+    fn reg() -> Expression {
+        Expression::Var(register("RAX", 4))
+    }
+
+    fn binop(op: BinOpType, lhs: Expression, rhs: u64) -> Expression {
+        Expression::BinOp {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(Expression::Const(Bitvector::from_u64(rhs))),
+        }
+    }
+
+    #[test]
+    fn recognizes_bitfield_idioms() {
+        let shifted = binop(BinOpType::IntRight, reg(), 3);
+        assert_eq!(
+            bitfield_extract(&shifted, &Expression::Const(Bitvector::from_u64(0x7))),
+            Field::new_bitfield(0, 3, 3).map(|fld| (&reg(), fld))
+        );
+        assert_eq!(
+            bitfield_extract(&Expression::Const(Bitvector::from_u64(0x30)), &reg()),
+            Field::new_bitfield(0, 4, 2).map(|fld| (&reg(), fld))
+        );
+        assert_eq!(
+            bitfield_insert(&reg(), &Expression::Const(Bitvector::from_u64(0x40))),
+            Field::new_bitfield(0, 6, 1).map(|fld| (&reg(), fld))
+        );
+
+        // Byte masks and non contiguous masks are not bitfields
+        assert_eq!(
+            bitfield_extract(&reg(), &Expression::Const(Bitvector::from_u64(0xff00))),
+            None
+        );
+        assert_eq!(
+            bitfield_insert(&reg(), &Expression::Const(Bitvector::from_u64(0x5))),
+            None
+        );
+    }
+
+    /// A context where every register has the single definition "x", which may be a load.
+    #[derive(Clone)]
+    struct SingleDefinitionContext {
+        loaded: bool,
+    }
+
+    impl NodeContextMapping for SingleDefinitionContext {
+        fn apply_def(&self, _term: &Term<Def>) -> Self {
+            self.clone()
+        }
+
+        fn apply_return_node(&self, _call_term: &Term<Jmp>, _return_term: &Term<Jmp>) -> Self {
+            self.clone()
+        }
+    }
+
+    impl RegisterMapping for SingleDefinitionContext {
+        fn access(&self, _var: &Variable) -> BTreeSet<TypeVariable> {
+            vec![TypeVariable::new("x".to_owned())]
+                .into_iter()
+                .collect()
+        }
+
+        fn is_loaded(&self, _var: &Variable) -> bool {
+            self.loaded
+        }
+    }
+
+    impl PointsToMapping for SingleDefinitionContext {
+        fn points_to(&self, _address: &Expression, _sz: ByteSize) -> BTreeSet<TypeVariableAccess> {
+            BTreeSet::new()
+        }
+
+        fn get_pointer_variable(
+            &self,
+            _address: &Expression,
+            _constant_resolver: &impl ConstantResolver,
+        ) -> Option<DerivedTypeVar> {
+            None
+        }
+    }
+
+    impl SubprocedureLocators for SingleDefinitionContext {
+        fn get_type_variables_and_constraints_for_arg(
+            &self,
+            _arg: &Arg,
+            _reg: &impl RegisterMapping,
+            _points_to: &impl PointsToMapping,
+            _vm: &mut VariableManager,
+        ) -> BTreeSet<ArgTvar> {
+            vec![ArgTvar::VariableTvar(TypeVariable::new("x".to_owned()))]
+                .into_iter()
+                .collect()
+        }
+
+        fn get_memory_returns(&self, _sub: &Tid) -> Vec<MemoryReturn> {
+            Vec::new()
+        }
+    }
+
+    impl ConstantResolver for SingleDefinitionContext {
+        fn maybe_resolve_constant_to_variable(
+            &self,
+            _target: &Bitvector,
+        ) -> Option<DerivedTypeVar> {
+            None
+        }
+    }
+
+    /// Generates constraints for assigning the value to RDX where every register is defined by x.
+    fn assign_constraints(loaded: bool, size: u64, value: Expression) -> ConstraintSet {
+        let ctx = SingleDefinitionContext { loaded };
+        let nd_ctx = NodeContext::new(
+            ctx.clone(),
+            ctx.clone(),
+            ctx.clone(),
+            ctx,
+            TypeVariable::new("weakint".to_owned()),
+        );
+        let assignment = Term {
+            tid: Tid::create("instr_00001000_0".to_owned(), "00001000".to_owned()),
+            term: Def::Assign {
+                var: register("RDX", size),
+                value,
+            },
+        };
+        nd_ctx.handle_def(&assignment, &mut VariableManager::new())
+    }
+
+    /// Generates constraints for `RDX = x & 1` and lowers the type of x.
+    fn lower_masked_definition(loaded: bool) -> CType {
+        let x = DerivedTypeVar::new(TypeVariable::new("x".to_owned()));
+        let mut cons = assign_constraints(loaded, 4, binop(BinOpType::IntAnd, reg(), 1))
+            .iter()
+            .filter_map(|cons| match cons {
+                TyConstraint::SubTy(sty) => Some(sty.clone()),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+        cons.insert(SubtypeConstraint::new(
+            x.clone(),
+            DerivedTypeVar::new(TypeVariable::new("int".to_owned())),
+        ));
+
+        let def = LatticeDefinition::new(
+            vec![
+                ("int".to_owned(), "weakint".to_owned()),
+                ("weakint".to_owned(), "top".to_owned()),
+                ("bottom".to_owned(), "int".to_owned()),
+            ],
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "weakint".to_owned(),
+        );
+        let lattice = def.generate_lattice();
+        let elems: HashSet<_> = lattice
+            .get_nds()
+            .iter()
+            .map(|(nm, _)| TypeVariable::new(nm.clone()))
+            .collect();
+        let sg = SketchBuilder::new(
+            &lattice,
+            &elems,
+            &|dtv, mpgrph| {
+                insert_dtv(&lattice, mpgrph, dtv.clone());
+                Ok(())
+            },
+            FileDebugLogger::default(),
+        )
+        .build_and_label_constraints(&cons)
+        .expect("should build sketch graph");
+
+        let (node_to_ty, mut types) = LoweringContext::new(
+            &sg,
+            &BTreeMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes()
+        .expect("should lower types");
+        let x_idx = sg
+            .get_node_index_for_variable(&x)
+            .expect("x should have a node");
+        types
+            .remove(&node_to_ty[&x_idx])
+            .expect("x should have a type")
+    }
+
+    fn double_reg() -> Expression {
+        Expression::Var(register("XMM0_Qa", 8))
+    }
+
+    #[test]
+    fn float_operations_are_float_evidence() {
+        let cons = |value| {
+            assign_constraints(false, 8, value)
+                .iter()
+                .map(|cons| cons.to_string())
+                .collect::<BTreeSet<_>>()
+        };
+        let float_binop = |op| Expression::BinOp {
+            op,
+            lhs: Box::new(double_reg()),
+            rhs: Box::new(double_reg()),
+        };
+        let cast = |op, size| Expression::Cast {
+            op,
+            size: ByteSize::new(size),
+            arg: Box::new(double_reg()),
+        };
+
+        let added = cons(float_binop(BinOpType::FloatAdd));
+        assert!(added.contains("x ⊑ double"));
+        assert!(added.contains("τ0 ⊑ double"));
+
+        // comparisons of doubles are integers
+        let compared = cons(float_binop(BinOpType::FloatLess));
+        assert!(compared.contains("x ⊑ double"));
+        assert!(compared.contains("τ0 ⊑ weakint"));
+        assert!(!compared.contains("τ0 ⊑ double"));
+
+        let negated = cons(Expression::UnOp {
+            op: UnOpType::FloatNegate,
+            arg: Box::new(double_reg()),
+        });
+        assert!(negated.contains("x ⊑ double"));
+        assert!(negated.contains("τ0 ⊑ double"));
+
+        let narrowed = cons(cast(CastOpType::Float2Float, 4));
+        assert!(narrowed.contains("x ⊑ double"));
+        assert!(narrowed.contains("τ0 ⊑ float"));
+
+        let truncated = cons(cast(CastOpType::Trunc, 4));
+        assert!(truncated.contains("x ⊑ double"));
+        assert!(truncated.contains("τ0 ⊑ weakint"));
+
+        // the integer operand of a conversion is not a float
+        let converted = cons(cast(CastOpType::Int2Float, 8));
+        assert!(converted.contains("τ0 ⊑ double"));
+        assert!(!converted.contains("x ⊑ double"));
+    }
+
+    #[test]
+    fn only_loaded_fields_have_bitfields() {
+        assert!(matches!(
+            lower_masked_definition(false),
+            CType::Primitive { .. }
+        ));
+        assert!(matches!(lower_masked_definition(true), CType::Structure(_)));
+    }
+
+    fn block(template: &Term<Blk>, name: &str, defs: Vec<Term<Def>>, jmps: Vec<Jmp>) -> Term<Blk> {
+        let mut blk = template.clone();
+        blk.tid = Tid::new(name);
+        blk.term.defs = defs;
+        blk.term.jmps = jmps
+            .into_iter()
+            .enumerate()
+            .map(|(idx, term)| Term {
+                tid: Tid::new(format!("{}_jmp_{}", name, idx)),
+                term,
+            })
+            .collect();
+        blk
+    }
+
+    fn assign(name: &str, value: Expression) -> Term<Def> {
+        Term {
+            tid: Tid::new(name),
+            term: Def::Assign {
+                var: register("CF", 1),
+                value,
+            },
+        }
+    }
+
+    #[test]
+    fn comparisons_collect_equalities_and_switch_cases() {
+        let mut proj = load_composite_return_project();
+        let mut sub = proj
+            .program
+            .term
+            .subs
+            .values()
+            .find(|sub| !sub.term.blocks.is_empty())
+            .expect("should have a procedure with blocks")
+            .clone();
+        let template = sub.term.blocks[0].clone();
+        sub.term.blocks = vec![
+            block(
+                &template,
+                "guard",
+                vec![assign("bound", binop(BinOpType::IntLess, reg(), 4))],
+                vec![
+                    Jmp::CBranch {
+                        target: Tid::new("default"),
+                        condition: reg(),
+                    },
+                    Jmp::Branch(Tid::new("switch")),
+                ],
+            ),
+            block(&template, "switch", vec![], vec![Jmp::BranchInd(reg())]),
+            block(
+                &template,
+                "default",
+                vec![
+                    assign("eq", binop(BinOpType::IntEqual, reg(), 7)),
+                    assign("lt", binop(BinOpType::IntLess, reg(), 9)),
+                ],
+                vec![Jmp::CBranch {
+                    target: Tid::new("guard"),
+                    condition: reg(),
+                }],
+            ),
+        ];
+        proj.program.term.subs = vec![(sub.tid.clone(), sub)].into_iter().collect();
+
+        let constants = collect_comparison_constants(&proj);
+        assert_eq!(
+            constants.get(&comparison_tvar(&Tid::new("bound"))),
+            Some(&(0..=4).collect::<BTreeSet<_>>())
+        );
+        assert_eq!(
+            constants.get(&comparison_tvar(&Tid::new("eq"))),
+            Some(&vec![7].into_iter().collect::<BTreeSet<_>>())
+        );
+        // a bound that does not guard a switch is not a comparison
+        assert_eq!(constants.len(), 2);
+        assert!(is_comparison_tvar(&comparison_tvar(&Tid::new("eq"))));
+        assert!(!is_comparison_tvar(&TypeVariable::new(
+            "sub_00001000".to_owned()
+        )));
+    }
+
+    fn load_byte(name: &str, into: &str) -> Term<Def> {
+        Term {
+            tid: Tid::new(name),
+            term: Def::Load {
+                var: register(into, 1),
+                address: reg(),
+            },
+        }
+    }
+
+    #[test]
+    fn string_scans_load_bytes_in_loops_compared_against_zero() {
+        let proj = load_composite_return_project();
+        let mut sub = proj
+            .program
+            .term
+            .subs
+            .values()
+            .find(|sub| !sub.term.blocks.is_empty())
+            .expect("should have a procedure with blocks")
+            .clone();
+        let template = sub.term.blocks[0].clone();
+        let compared =
+            |var: &str, c| binop(BinOpType::IntEqual, Expression::Var(register(var, 1)), c);
+        let extended = Term {
+            tid: Tid::new("extended"),
+            term: Def::Assign {
+                var: register("DL", 1),
+                value: Expression::Cast {
+                    op: CastOpType::IntZExt,
+                    size: ByteSize::new(1),
+                    arg: Box::new(Expression::Var(register("CL", 1))),
+                },
+            },
+        };
+        sub.term.blocks = vec![
+            block(
+                &template,
+                "scan",
+                vec![
+                    load_byte("chr", "CL"),
+                    extended,
+                    assign("end", compared("DL", 0)),
+                ],
+                vec![
+                    Jmp::CBranch {
+                        target: Tid::new("exit"),
+                        condition: reg(),
+                    },
+                    Jmp::Branch(Tid::new("scan")),
+                ],
+            ),
+            block(
+                &template,
+                "once",
+                vec![load_byte("first", "CL"), assign("empty", compared("CL", 0))],
+                vec![Jmp::Branch(Tid::new("exit"))],
+            ),
+            block(
+                &template,
+                "hash",
+                vec![load_byte("hashed", "CL"), assign("mix", compared("CL", 7))],
+                vec![Jmp::Branch(Tid::new("hash_tail"))],
+            ),
+            block(
+                &template,
+                "hash_tail",
+                vec![],
+                vec![Jmp::Branch(Tid::new("hash"))],
+            ),
+            block(&template, "exit", vec![], vec![Jmp::Return(reg())]),
+        ];
+
+        let scans = |idx: usize| {
+            string_scan_loads(&sub.term.blocks[idx], &sub)
+                .into_iter()
+                .map(|df| df.tid.get_str_repr().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(scans(0), vec!["chr".to_owned()]);
+        // a single load is not a scan
+        assert!(scans(1).is_empty());
+        // bytes in a loop that are not compared against zero are not characters
+        assert!(scans(2).is_empty());
+
+        let ctx = SingleDefinitionContext { loaded: false };
+        let nd_ctx = NodeContext::new(
+            ctx.clone(),
+            ctx.clone(),
+            ctx.clone(),
+            ctx,
+            TypeVariable::new("weakint".to_owned()),
+        );
+        let cons = |idx: usize| {
+            Context::handle_block_start(
+                nd_ctx.clone(),
+                &sub.term.blocks[idx],
+                &sub,
+                &mut VariableManager::new(),
+            )
+            .iter()
+            .map(|cons| cons.to_string())
+            .collect::<BTreeSet<_>>()
+        };
+        assert!(cons(0).contains("chr_CL ⊑ string"));
+        assert!(!cons(2).contains("hashed_CL ⊑ string"));
+    }
+
+    #[test]
+    fn float_registers_are_float_evidence() {
+        assert_eq!(
+            float_register_type(&register_arg("XMM0_Da", 4)),
+            Some(FLOAT_TYPE)
+        );
+        assert_eq!(
+            float_register_type(&register_arg("XMM1_Qa", 8)),
+            Some(DOUBLE_TYPE)
+        );
+        assert_eq!(
+            float_register_type(&register_arg("XMM2", 16)),
+            Some(DOUBLE_TYPE)
+        );
+        assert_eq!(
+            float_register_type(&register_arg("ST0", 10)),
+            Some(LONG_DOUBLE_TYPE)
+        );
+        assert_eq!(float_register_type(&register_arg("RDI", 8)), None);
+        assert_eq!(float_register_type(&register_arg("STACK", 8)), None);
+    }
+
+    #[test]
+    fn string_externs_constrain_the_pointed_to_characters() {
+        let cons = string_pointer_constraint(
+            DerivedTypeVar::create_with_path(
+                TypeVariable::new("strlen".to_owned()),
+                vec![FieldLabel::In(0)],
+            ),
+            "string",
+        );
+        assert_eq!(cons.to_string(), "strlen.in_0.load.σ8@0 ⊑ string");
+
+        // lookups by name expect each extern once
+        let names: BTreeSet<_> = STRING_EXTERN_PARAMS.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names.len(), STRING_EXTERN_PARAMS.len());
+        let returns: BTreeSet<_> = STRING_EXTERN_RETURNS.iter().collect();
+        assert_eq!(returns.len(), STRING_EXTERN_RETURNS.len());
+
+        let proj = load_composite_return_project();
+        let template = proj
+            .program
+            .term
+            .subs
+            .values()
+            .flat_map(|sub| sub.term.blocks.iter())
+            .next()
+            .expect("should have a block")
+            .clone();
+        let calling_blk = block(&template, "call", vec![], vec![]);
+        let mut strncpy = proj
+            .program
+            .term
+            .extern_symbols
+            .values()
+            .next()
+            .expect("should have an extern")
+            .clone();
+        strncpy.name = "strncpy".to_owned();
+        strncpy.parameters = vec![
+            register_arg("RDI", 8),
+            register_arg("RSI", 8),
+            register_arg("RDX", 8),
+        ];
+        strncpy.return_values = vec![register_arg("RAX", 8)];
+        let strncpy = Term {
+            tid: Tid::new("strncpy"),
+            term: strncpy,
+        };
+
+        let ctx = SingleDefinitionContext { loaded: false };
+        let nd_ctx = NodeContext::new(
+            ctx.clone(),
+            ctx.clone(),
+            ctx.clone(),
+            ctx,
+            TypeVariable::new("weakint".to_owned()),
+        );
+        let mut vman = VariableManager::new();
+        let params = nd_ctx.handle_extern_actual_params(&calling_blk, &strncpy, &mut vman, 0);
+        let rets = nd_ctx.handle_extern_actual_rets(&calling_blk, &strncpy, &mut vman, 0);
+
+        let formal = |lbl: FieldLabel| {
+            DerivedTypeVar::create_with_path(
+                TypeVariable::with_tag("strncpy".to_owned(), Tid::new("call")),
+                vec![lbl],
+            )
+        };
+        let characters = |lbl: FieldLabel| {
+            let mut chr = formal(lbl);
+            chr.add_field_label(FieldLabel::Load);
+            chr.add_field_label(FieldLabel::Field(Field::new(0, 8)));
+            TyConstraint::SubTy(SubtypeConstraint::new(
+                chr,
+                DerivedTypeVar::new(TypeVariable::new(STRING_TYPE.to_owned())),
+            ))
+        };
+        let x = DerivedTypeVar::new(TypeVariable::new("x".to_owned()));
+
+        // the actual argument flows into a parameter whose characters are strings
+        assert!(params.contains(&TyConstraint::SubTy(SubtypeConstraint::new(
+            x.clone(),
+            formal(FieldLabel::In(0))
+        ))));
+        assert!(params.contains(&characters(FieldLabel::In(0))));
+        assert!(params.contains(&characters(FieldLabel::In(1))));
+        // the length is not a string
+        assert!(!params.contains(&characters(FieldLabel::In(2))));
+
+        assert!(rets.contains(&TyConstraint::SubTy(SubtypeConstraint::new(
+            formal(FieldLabel::Out(0)),
+            x
+        ))));
+        assert!(rets.contains(&characters(FieldLabel::Out(0))));
+    }
+}
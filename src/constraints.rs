@@ -7,12 +7,13 @@ use cwe_checker_lib::intermediate_representation::Tid;
 use nom::branch::alt;
 use nom::bytes::complete::take_while;
 use nom::character::complete::{digit1, space0};
-use nom::combinator::map_res;
+use nom::combinator::{map_res, opt, recognize};
 use nom::error::ParseError;
 use nom::multi::{many0, separated_list0};
-use nom::sequence::preceded;
+use nom::sequence::{pair, preceded};
 use nom::{bytes::complete::tag, combinator::map, sequence::tuple, IResult};
 use nom::{AsChar, InputTakeAtPosition};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::convert::{TryFrom, TryInto};
@@ -41,6 +42,32 @@ pub fn parse_identifier(input: &str) -> IResult<&str, &str> {
     identifier_char(input)
 }
 
+/// Parses a type variable with a callsite tag written as name:tid@address, the form printed by [TypeVariable]'s Display
+pub fn parse_type_variable_with_tid_tag(input: &str) -> IResult<&str, TypeVariable> {
+    map(
+        tuple((
+            parse_identifier,
+            tag(":"),
+            parse_identifier,
+            tag("@"),
+            parse_identifier,
+        )),
+        |(id, _, tid, _, address): (&str, &str, &str, &str, &str)| {
+            TypeVariable::with_tag(
+                id.to_owned(),
+                Tid::create(tid.to_owned(), address.to_owned()),
+            )
+        },
+    )(input)
+}
+
+/// Parses a global type variable written as global:name
+pub fn parse_global_type_variable(input: &str) -> IResult<&str, TypeVariable> {
+    map(preceded(tag("global:"), parse_identifier), |s: &str| {
+        TypeVariable::new_global(s.to_owned())
+    })(input)
+}
+
 /// Parses a type variable that has a ctr to be used as its callsite tag
 pub fn parse_type_variable_with_cs_tag(input: &str) -> IResult<&str, TypeVariable> {
     map_res::<_, _, _, _, ParseIntError, _, _>(
@@ -66,33 +93,78 @@ pub fn parse_type_variable_without_cs_tag(input: &str) -> IResult<&str, TypeVari
 /// Parses a non-derived type variable. A type variable is just an identifier.
 pub fn parse_type_variable(input: &str) -> IResult<&str, TypeVariable> {
     alt((
+        parse_global_type_variable,
+        parse_type_variable_with_tid_tag,
         parse_type_variable_with_cs_tag,
         parse_type_variable_without_cs_tag,
     ))(input)
 }
 
-fn parse_field_field(input: &str) -> IResult<&str, FieldLabel> {
-    //σ{}@{}
+/// Parses an integer with an optional leading minus sign.
+fn signed_digits(input: &str) -> IResult<&str, &str> {
+    recognize(pair(opt(tag("-")), digit1))(input)
+}
+
+fn parse_field_array(input: &str) -> IResult<&str, FieldArray> {
     map_res::<_, _, _, _, ParseIntError, _, _>(
-        tuple((tag("σ"), digit1, tag("@"), digit1)),
-        |(_, field_size, _, offset): (&str, &str, &str, &str)| {
+        tuple((tag("["), digit1, tag("x"), digit1, tag("]"))),
+        |(_, stride, _, count, _): (&str, &str, &str, &str, &str)| {
+            Ok(FieldArray {
+                stride: stride.parse()?,
+                count: count.parse()?,
+            })
+        },
+    )(input)
+}
+
+fn parse_field_field(input: &str) -> IResult<&str, FieldLabel> {
+    //σ{}@{} or σ{}@{}:{} or σ{}@{}[{}x{}]
+    map_res::<_, _, _, _, anyhow::Error, _, _>(
+        tuple((
+            tag("σ"),
+            digit1,
+            tag("@"),
+            signed_digits,
+            opt(preceded(tag(":"), digit1)),
+            opt(parse_field_array),
+        )),
+        |(_, field_size, _, offset, bit_offset, array): (
+            &str,
+            &str,
+            &str,
+            &str,
+            Option<&str>,
+            Option<FieldArray>,
+        )| {
             let field_size: usize = field_size.parse()?;
             let offset = offset.parse()?;
-            Ok(FieldLabel::Field(Field {
-                size: field_size,
-                offset,
-            }))
+            let bit_offset = bit_offset.map(str::parse).transpose()?.unwrap_or(0);
+            let fld = Field::new_bitfield(offset, bit_offset, field_size)
+                .ok_or(anyhow::anyhow!("Bitfield offset overflows"))?;
+            match array {
+                Some(array) => Field::new_array(fld.offset, fld.size, array.stride, array.count)
+                    .ok_or(anyhow::anyhow!("Field array elements overlap")),
+                None => Ok(fld),
+            }
+            .map(FieldLabel::Field)
         },
     )(input)
 }
 
 fn parse_add_field(input: &str) -> IResult<&str, FieldLabel> {
-    map_res::<_, _, _, _, ParseIntError, _, _>(preceded(tag("+"), digit1), |x: &str| {
+    map_res::<_, _, _, _, ParseIntError, _, _>(preceded(tag("+"), signed_digits), |x: &str| {
         let cons = x.parse()?;
         Ok(FieldLabel::Add(cons))
     })(input)
 }
 
+fn parse_out_field(input: &str) -> IResult<&str, FieldLabel> {
+    map_res::<_, _, _, _, ParseIntError, _, _>(preceded(tag("out_"), digit1), |x: &str| {
+        let cons = x.parse()?;
+        Ok(FieldLabel::Out(cons))
+    })(input)
+}
+
 fn parse_in_field(input: &str) -> IResult<&str, FieldLabel> {
     map_res::<_, _, _, _, ParseIntError, _, _>(preceded(tag("in_"), digit1), |x: &str| {
         let cons = x.parse()?;
@@ -105,6 +177,7 @@ pub fn parse_field_label(input: &str) -> IResult<&str, FieldLabel> {
     alt((
         map(tag("load"), |_| FieldLabel::Load),
         map(tag("store"), |_| FieldLabel::Store),
+        parse_out_field,
         map(tag("out"), |_| FieldLabel::Out(0)),
         parse_in_field,
         parse_field_field,
@@ -129,12 +202,12 @@ pub fn parse_derived_type_variable(input: &str) -> IResult<&str, DerivedTypeVar>
     )(input)
 }
 
-/// Parses a subtyping constraint "a.x <= b.y"
+/// Parses a subtyping constraint "a.x <= b.y", ⊑ is accepted in place of <=
 pub fn parse_subtype_cons(input: &str) -> IResult<&str, TyConstraint> {
     let parser = tuple((
         parse_derived_type_variable,
         space0,
-        tag("<="),
+        alt((tag("<="), tag("⊑"))),
         space0,
         parse_derived_type_variable,
     ));
@@ -151,11 +224,11 @@ pub fn parse_whitespace_delim(input: &str) -> IResult<&str, &str> {
     )(input)
 }
 
-/// parse add constraint
+/// parse add constraint, either AddCons(a,b,c) or the printed form Add(a,b,c)
 pub fn parse_add_cons(input: &str) -> IResult<&str, TyConstraint> {
     map(
         tuple((
-            tag("AddCons("),
+            alt((tag("AddCons("), tag("Add("))),
             parse_derived_type_variable,
             tag(","),
             parse_derived_type_variable,
@@ -178,9 +251,12 @@ pub fn parse_constraint_set(input: &str) -> IResult<&str, ConstraintSet> {
 }
 
 /// A static type variable with a name
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, JsonSchema,
+)]
 pub struct TypeVariable {
     name: String,
+    #[schemars(with = "Option<crate::interchange::TidSchema>")]
     cs_tag: Option<Tid>,
     #[serde(default)]
     is_global: bool,
@@ -247,7 +323,14 @@ impl TypeVariable {
 
 impl Display for TypeVariable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.get_name())?;
+        // NOTE(Ian): the full tag and the global marker are printed so that the output can be parsed back by [parse_type_variable]
+        if self.is_global {
+            f.write_str("global:")?;
+        }
+        f.write_str(&self.name)?;
+        if let Some(cs_tag) = &self.cs_tag {
+            write!(f, ":{}@{}", cs_tag.get_str_repr(), cs_tag.address)?;
+        }
         Ok(())
     }
 }
@@ -294,25 +377,98 @@ impl Default for VariableManager {
     }
 }
 
-/// A field constraint of the form .σN@k where N is the bit-width of the field at byte offset k
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+/// The elements of an array field, repeating the field every stride bytes.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, JsonSchema,
+)]
+pub struct FieldArray {
+    /// Distance in bytes between the starts of consecutive elements.
+    pub stride: u64,
+    /// The number of elements, at least 2.
+    pub count: u64,
+}
+
+/// A field constraint of the form .σN@k where N is the bit-width of the field at byte offset k.
+/// Bitfields additionally start at a bit offset b from their byte offset and are written .σN@k:b
+/// Arrays of count elements every stride bytes starting at k are written .σN@k[stridexcount]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, JsonSchema,
+)]
 pub struct Field {
     /// Offset in bytes of the field.
     pub offset: i64,
     /// Size of the field in bits.
     pub size: usize,
+    /// Offset in bits of the field from its byte offset, always less than 8.
+    #[serde(default)]
+    pub bit_offset: usize,
+    /// The elements of the field if it is an array of fields of this size.
+    #[serde(default)]
+    pub array: Option<FieldArray>,
 }
 
 impl Field {
     /// creates a new field access at a byte offset and bit-width size.
     pub fn new(offset: i64, size: usize) -> Field {
-        Field { offset, size }
+        Field {
+            offset,
+            size,
+            bit_offset: 0,
+            array: None,
+        }
+    }
+
+    /// Creates an array of count elements of the given bit-width, every stride bytes from the byte offset.
+    /// A single element is a plain field. Returns [None] if the elements would overlap.
+    pub fn new_array(offset: i64, size: usize, stride: u64, count: u64) -> Option<Field> {
+        let fld = Field::new(offset, size);
+        if count <= 1 {
+            return Some(fld);
+        }
+
+        let stride_bits = usize::try_from(stride)
+            .ok()
+            .and_then(|stride| stride.checked_mul(8))?;
+        if stride_bits < size || size % 8 != 0 {
+            return None;
+        }
+
+        Some(Field {
+            array: Some(FieldArray { stride, count }),
+            ..fld
+        })
+    }
+
+    /// Creates a field at a bit position relative to a byte offset, normalizing the bit offset to be within a byte.
+    /// Returns [None] if the normalized byte offset does not fit in an i64.
+    pub fn new_bitfield(offset: i64, bit_offset: usize, size: usize) -> Option<Field> {
+        let offset = i64::try_from(bit_offset / 8)
+            .ok()
+            .and_then(|bytes| offset.checked_add(bytes))?;
+        Some(Field {
+            offset,
+            size,
+            bit_offset: bit_offset % 8,
+            array: None,
+        })
+    }
+
+    /// A field is a bitfield if it does not start or end on a byte boundary.
+    pub fn is_bitfield(&self) -> bool {
+        self.bit_offset != 0 || self.size % 8 != 0
     }
 }
 
 impl Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("σ{}@{}", self.size, self.offset))
+        f.write_fmt(format_args!("σ{}@{}", self.size, self.offset))?;
+        if self.bit_offset != 0 {
+            f.write_fmt(format_args!(":{}", self.bit_offset))?;
+        }
+        if let Some(array) = &self.array {
+            f.write_fmt(format_args!("[{}x{}]", array.stride, array.count))?;
+        }
+        Ok(())
     }
 }
 
@@ -326,7 +482,9 @@ pub struct In {
 }
 
 /// A field label specifies the capabilities of a [DerivedTypeVar]
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, JsonSchema,
+)]
 pub enum FieldLabel {
     /// The previous label can be loaded from
     Load,
@@ -416,7 +574,9 @@ impl AbstractSemigroup<Multiplicative> for Variance {}
 /// Variance is determined by the sign monoid of the component [FieldLabel] variances ⊕·⊕ = ⊖·⊖ = ⊕ and ⊕·⊖ = ⊖·⊕ = ⊖
 /// [DerivedTypeVar] forms the expression αw where α ∈ V and w ∈ Σ^*
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash, Ord, Deserialize, Serialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Hash, Ord, Deserialize, Serialize, JsonSchema,
+)]
 pub struct DerivedTypeVar {
     var: TypeVariable,
     labels: Vec<FieldLabel>,
@@ -552,7 +712,16 @@ impl TryFrom<pb_constraints::Field> for Field {
     fn try_from(value: pb_constraints::Field) -> Result<Self, Self::Error> {
         let offset = value.byte_offset.try_into()?;
         let size = value.bit_size.try_into()?;
-        Ok(Field { offset, size })
+        let bit_offset = value.bit_offset.try_into()?;
+        let fld = Field::new_bitfield(offset, bit_offset, size)
+            .ok_or(anyhow::anyhow!("Bitfield offset overflows"))?;
+        Field::new_array(
+            fld.offset,
+            fld.size,
+            value.stride.into(),
+            value.count.into(),
+        )
+        .ok_or(anyhow::anyhow!("Field array elements overlap"))
     }
 }
 
@@ -607,7 +776,7 @@ impl TryFrom<pb_constraints::DerivedTypeVariable> for DerivedTypeVar {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, JsonSchema)]
 /// Represents an additional constraint that is injected before type solving.
 /// Additional constraints have a Tid which is the subprocedure in which they will be applied.
 /// This allows the SCC generation to determine which scc should contain this subtyping constriant.
@@ -615,6 +784,7 @@ pub struct AdditionalConstraint {
     /// The actual constraint (x<=y) to inject.
     pub constraint: SubtypeConstraint,
     /// The sub procedure that this constraint should apply to.
+    #[schemars(with = "crate::interchange::TidSchema")]
     pub associated_variable: Tid,
 }
 
@@ -639,7 +809,7 @@ impl TryFrom<pb_constraints::AdditionalConstraint> for AdditionalConstraint {
 }
 
 /// Expresses a subtyping constraint of the form lhs ⊑ rhs
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, JsonSchema)]
 pub struct SubtypeConstraint {
     /// The left hand side of the subtyping constraint
     pub lhs: DerivedTypeVar,
@@ -812,14 +982,94 @@ impl DerefMut for ConstraintSet {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
+    use cwe_checker_lib::intermediate_representation::Tid;
+    use proptest::prelude::*;
+
     use crate::constraints::TyConstraint;
 
     use super::{
-        parse_derived_type_variable, parse_subtype_cons, DerivedTypeVar, FieldLabel,
-        SubtypeConstraint, TypeVariable,
+        parse_constraint_set, parse_derived_type_variable, parse_subtype_cons, AddConstraint,
+        ConstraintSet, DerivedTypeVar, Field, FieldLabel, SubtypeConstraint, TypeVariable,
     };
 
+    /// Names start with v so that they cannot be confused with keywords like start, end, or global.
+    fn arb_name() -> impl Strategy<Value = String> {
+        "v[a-zA-Z0-9_$]{0,7}"
+    }
+
+    fn arb_tid() -> impl Strategy<Value = Tid> {
+        ("[a-zA-Z][a-zA-Z0-9_]{0,11}", "[0-9a-f]{1,8}|UNKNOWN")
+            .prop_map(|(id, address)| Tid::create(id, address))
+    }
+
+    pub(crate) fn arb_type_variable() -> impl Strategy<Value = TypeVariable> {
+        prop_oneof![
+            arb_name().prop_map(TypeVariable::new),
+            arb_name().prop_map(TypeVariable::new_global),
+            (arb_name(), arb_tid()).prop_map(|(name, tid)| TypeVariable::with_tag(name, tid)),
+        ]
+    }
+
+    pub(crate) fn arb_field_label() -> impl Strategy<Value = FieldLabel> {
+        prop_oneof![
+            Just(FieldLabel::Load),
+            Just(FieldLabel::Store),
+            (0..16usize).prop_map(FieldLabel::In),
+            (0..16usize).prop_map(FieldLabel::Out),
+            (any::<usize>(), any::<i64>(), 0..8usize).prop_map(|(size, offset, bit_offset)| {
+                FieldLabel::Field(Field {
+                    size,
+                    offset,
+                    bit_offset,
+                })
+            }),
+            any::<i128>().prop_map(FieldLabel::Add),
+        ]
+    }
+
+    pub(crate) fn arb_field_labels() -> impl Strategy<Value = Vec<FieldLabel>> {
+        prop::collection::vec(arb_field_label(), 0..5)
+    }
+
+    fn arb_derived_type_variable() -> impl Strategy<Value = DerivedTypeVar> {
+        (arb_type_variable(), arb_field_labels()).prop_map(|(tv, labels)| {
+            let mut dtv = DerivedTypeVar::new(tv);
+            labels.into_iter().for_each(|l| dtv.add_field_label(l));
+            dtv
+        })
+    }
+
+    fn arb_constraint() -> impl Strategy<Value = TyConstraint> {
+        prop_oneof![
+            (arb_derived_type_variable(), arb_derived_type_variable())
+                .prop_map(|(lhs, rhs)| TyConstraint::SubTy(SubtypeConstraint::new(lhs, rhs))),
+            (
+                arb_derived_type_variable(),
+                arb_derived_type_variable(),
+                arb_derived_type_variable()
+            )
+                .prop_map(|(lhs, rhs, repr)| TyConstraint::AddCons(
+                    AddConstraint::new(lhs, rhs, repr)
+                )),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn derived_type_variables_round_trip(dtv in arb_derived_type_variable()) {
+            let printed = dtv.to_string();
+            prop_assert_eq!(parse_derived_type_variable(&printed), Ok(("", dtv)));
+        }
+
+        #[test]
+        fn constraint_sets_round_trip(cons in prop::collection::btree_set(arb_constraint(), 0..6)) {
+            let cs_set = ConstraintSet::from(cons);
+            let printed = cs_set.to_string();
+            prop_assert_eq!(parse_constraint_set(&printed), Ok(("", cs_set)));
+        }
+    }
+
     #[test]
     fn parse_simple_dtv() {
         assert_eq!(
@@ -881,4 +1131,54 @@ mod test {
             parse_subtype_cons("file_descriptor <= sub_00001000.in_0"),
         );
     }
+
+    #[test]
+    fn parse_indexed_out_and_negative_offsets() {
+        let mut dt = DerivedTypeVar::new(TypeVariable::new("sub_00001000".to_owned()));
+        dt.add_field_label(FieldLabel::Out(1));
+        dt.add_field_label(FieldLabel::Load);
+        dt.add_field_label(FieldLabel::Field(Field::new(-4, 32)));
+        dt.add_field_label(FieldLabel::Add(-8));
+        assert_eq!(
+            Ok(("", dt)),
+            parse_derived_type_variable("sub_00001000.out_1.load.σ32@-4.+-8")
+        );
+    }
+
+    #[test]
+    fn parse_bitfields() {
+        let mut dt = DerivedTypeVar::new(TypeVariable::new("flags".to_owned()));
+        dt.add_field_label(FieldLabel::Field(Field::new_bitfield(0, 11, 3).unwrap()));
+        assert_eq!(dt.to_string(), "flags.σ3@1:3");
+        assert_eq!(Ok(("", dt)), parse_derived_type_variable("flags.σ3@0:11"));
+    }
+
+    #[test]
+    fn parse_field_arrays() {
+        let mut dt = DerivedTypeVar::new(TypeVariable::new("buf".to_owned()));
+        dt.add_field_label(FieldLabel::Load);
+        dt.add_field_label(FieldLabel::Field(Field::new_array(8, 32, 16, 3).unwrap()));
+        assert_eq!(dt.to_string(), "buf.load.σ32@8[16x3]");
+        assert_eq!(
+            Ok(("", dt)),
+            parse_derived_type_variable("buf.load.σ32@8[16x3]")
+        );
+
+        assert_eq!(Field::new_array(8, 32, 16, 1), Some(Field::new(8, 32)));
+        // elements closer than their size overlap
+        assert!(Field::new_array(0, 32, 2, 4).is_none());
+        assert!(!matches!(
+            parse_derived_type_variable("buf.σ32@0[2x4]"),
+            Ok(("", _))
+        ));
+    }
+
+    #[test]
+    fn reject_overflowing_bitfield_offsets() {
+        assert!(Field::new_bitfield(i64::MAX, 64, 8).is_none());
+        assert!(!matches!(
+            parse_derived_type_variable("flags.σ8@9223372036854775807:64"),
+            Ok(("", _))
+        ));
+    }
 }
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::{message_reader::DEFAULT_MAX_MESSAGE_SIZE, JobDefinition};
+
+/// The interchange format of an input file.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputFormat {
+    /// A json array of messages
+    Json,
+    /// Protobuf messages each prefixed by a 4 byte big endian length
+    Protobuf,
+    /// Protobuf messages each prefixed by a varint length, as written by writeDelimitedTo
+    DelimitedProtobuf,
+    /// The textual constraint format of [crate::constraint_dsl]
+    Text,
+}
+
+/// An input file along with the format it is stored in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ManifestInput {
+    /// Path to the input, relative paths are resolved against the manifest's directory
+    pub path: String,
+    /// The format of the input
+    pub format: InputFormat,
+}
+
+/// Describes every input and option of an inference job so that a job can be reproduced from one file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JobManifest {
+    /// Path to the original binary
+    pub binary_path: String,
+    /// Path to the cwe checker json IR exported file
+    pub ir_json_path: String,
+    /// Path to json representing the type lattice
+    pub lattice_json: String,
+    /// Additional constraints to inject
+    pub additional_constraints: ManifestInput,
+    /// The interesting tids in the IR to solve types for
+    pub interesting_tids: ManifestInput,
+    /// Paths to lattice json files that are merged into the main lattice
+    #[serde(default)]
+    pub additional_lattices: Vec<String>,
+    /// Whether to recover shared returns aggressively
+    #[serde(default)]
+    pub use_aggressive_shared_returns: bool,
+    /// Overrides the default allocation symbols used by the pointer analysis
+    #[serde(default)]
+    pub allocation_symbols: Option<Vec<String>>,
+    /// The maximum encoded size of a single protobuf message in the inputs
+    #[serde(default = "default_max_message_size")]
+    pub max_message_size: usize,
+    /// The directory relative paths are resolved against, set to the manifest's directory when read from a file
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+fn default_max_message_size() -> usize {
+    DEFAULT_MAX_MESSAGE_SIZE
+}
+
+impl JobManifest {
+    /// Reads a manifest from a json file.
+    pub fn from_file(manifest_path: &str) -> anyhow::Result<JobManifest> {
+        let fl = std::fs::File::open(manifest_path).context("opening manifest")?;
+        let mut manifest: JobManifest = serde_json::from_reader(fl)
+            .map_err(|e| anyhow::Error::from(e).context("manifest json"))?;
+        manifest.base_dir = Path::new(manifest_path)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        Ok(manifest)
+    }
+
+    fn resolve(&self, pth: &str) -> String {
+        self.base_dir.join(pth).to_string_lossy().into_owned()
+    }
+
+    /// Gets the job definition with paths resolved against the manifest's directory.
+    pub fn get_job_definition(&self) -> JobDefinition {
+        JobDefinition {
+            binary_path: self.resolve(&self.binary_path),
+            ir_json_path: self.resolve(&self.ir_json_path),
+            lattice_json: self.resolve(&self.lattice_json),
+            additional_constraints_file: self.resolve(&self.additional_constraints.path),
+            interesting_tids: self.resolve(&self.interesting_tids.path),
+            max_message_size: self.max_message_size,
+        }
+    }
+
+    /// Gets the paths of the additional lattices resolved against the manifest's directory.
+    pub fn get_additional_lattice_paths(&self) -> Vec<String> {
+        self.additional_lattices
+            .iter()
+            .map(|pth| self.resolve(pth))
+            .collect()
+    }
+}
@@ -0,0 +1,275 @@
+use std::{io::Read, marker::PhantomData};
+
+use anyhow::Context;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::ctypes::StreamHeader;
+
+/// The default limit on the encoded size of a single message, 64 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+// a u64 varint is at most 10 bytes
+const MAX_VARINT_LEN: usize = 10;
+
+/// How the length of each message in a stream is encoded.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    /// A 4 byte big endian length before each message
+    BigEndian,
+    /// A varint length before each message, as written by writeDelimitedTo in the Java protobuf library
+    Varint,
+}
+
+/// Reads length delimited protobuf messages one at a time, rejecting messages larger than a maximum size.
+/// A [StreamHeader] at the start of the stream is checked against the supported format version and is not returned as a message.
+/// Errors name the index of the message and the byte offset of its length prefix.
+pub struct MessageReader<R, T> {
+    rdr: R,
+    framing: Framing,
+    max_message_size: usize,
+    index: usize,
+    offset: u64,
+    read_header: bool,
+    done: bool,
+    message_type: PhantomData<T>,
+}
+
+impl<R: Read, T: Message + Default> MessageReader<R, T> {
+    /// Creates a reader for messages with the given framing and the default maximum message size.
+    pub fn new(rdr: R, framing: Framing) -> MessageReader<R, T> {
+        MessageReader {
+            rdr,
+            framing,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            index: 0,
+            offset: 0,
+            read_header: false,
+            done: false,
+            message_type: PhantomData,
+        }
+    }
+
+    /// Sets the maximum encoded size of a single message.
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> MessageReader<R, T> {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Reads a byte, returning [None] at the end of the stream.
+    fn read_byte(&mut self) -> anyhow::Result<Option<u8>> {
+        let mut byte = [0];
+        loop {
+            match self.rdr.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    self.offset += 1;
+                    return Ok(Some(byte[0]));
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(anyhow::Error::from(err)),
+            }
+        }
+    }
+
+    /// Reads the length prefix of the next message, returning [None] if the stream ends before the prefix starts.
+    fn read_length(&mut self) -> anyhow::Result<Option<u64>> {
+        let first = match self.read_byte()? {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+
+        let truncated = || anyhow::anyhow!("Stream ends inside a length prefix");
+        match self.framing {
+            Framing::BigEndian => {
+                let mut len = u64::from(first);
+                for _ in 1..4 {
+                    len = (len << 8) | u64::from(self.read_byte()?.ok_or_else(truncated)?);
+                }
+                Ok(Some(len))
+            }
+            Framing::Varint => {
+                let mut len = 0;
+                let mut byte = first;
+                for shift in (0..MAX_VARINT_LEN).map(|idx| idx * 7) {
+                    len |= u64::from(byte & 0x7f) << shift;
+                    if byte & 0x80 == 0 {
+                        return Ok(Some(len));
+                    }
+                    byte = self.read_byte()?.ok_or_else(truncated)?;
+                }
+                Err(anyhow::anyhow!(
+                    "Length prefix is longer than a 64 bit varint"
+                ))
+            }
+        }
+    }
+
+    /// Reads the encoded bytes of the next message, returning [None] at the end of the stream.
+    fn read_frame(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        let len = match self.read_length()? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+
+        if len > self.max_message_size as u64 {
+            return Err(anyhow::anyhow!(
+                "Message of {} bytes is larger than the maximum of {} bytes",
+                len,
+                self.max_message_size
+            ));
+        }
+
+        // the buffer only grows as bytes are actually read
+        let mut buf = Vec::new();
+        self.rdr.by_ref().take(len).read_to_end(&mut buf)?;
+        self.offset += buf.len() as u64;
+        if buf.len() as u64 != len {
+            return Err(anyhow::anyhow!(
+                "Message of {} bytes is truncated to {} bytes",
+                len,
+                buf.len()
+            ));
+        }
+
+        Ok(Some(buf))
+    }
+
+    fn read_message(&mut self) -> anyhow::Result<Option<T>> {
+        let mut buf = match self.read_frame()? {
+            Some(buf) => buf,
+            None => return Ok(None),
+        };
+
+        if !self.read_header {
+            self.read_header = true;
+            // NOTE(Ian): elements never set the header's field, so a first message that decodes to a header with a version is the header
+            if let Ok(StreamHeader {
+                format_version: Some(version),
+            }) = StreamHeader::decode(buf.as_ref())
+            {
+                crate::interchange::check_format_version_message(&Some(version))?;
+                buf = match self.read_frame()? {
+                    Some(buf) => buf,
+                    None => return Ok(None),
+                };
+            }
+        }
+
+        T::decode(buf.as_ref())
+            .map(Some)
+            .map_err(anyhow::Error::from)
+    }
+}
+
+impl<R: Read, T: Message + Default> Iterator for MessageReader<R, T> {
+    type Item = anyhow::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let start_offset = self.offset;
+        let res = self.read_message().with_context(|| {
+            format!(
+                "Decoding message {} of type {} at byte offset {}",
+                self.index,
+                std::any::type_name::<T>(),
+                start_offset
+            )
+        });
+
+        match res {
+            Ok(Some(msg)) => {
+                self.index += 1;
+                Some(Ok(msg))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+
+    use super::{Framing, MessageReader};
+    use crate::ctypes::Tid;
+
+    fn tids() -> Vec<Tid> {
+        (0..200)
+            .map(|idx| Tid {
+                name: format!("sub_{:08x}", idx),
+                address: format!("{:08x}", idx),
+            })
+            .collect()
+    }
+
+    fn encode(msgs: &[Tid], framing: Framing) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for msg in msgs.iter() {
+            match framing {
+                Framing::BigEndian => {
+                    buf.extend_from_slice(&(msg.encoded_len() as u32).to_be_bytes());
+                    msg.encode(&mut buf).unwrap();
+                }
+                Framing::Varint => msg.encode_length_delimited(&mut buf).unwrap(),
+            }
+        }
+        buf
+    }
+
+    fn read_all(buf: &[u8], framing: Framing) -> anyhow::Result<Vec<Tid>> {
+        MessageReader::new(buf, framing).collect()
+    }
+
+    #[test]
+    fn reads_both_framings() {
+        for framing in [Framing::BigEndian, Framing::Varint].iter() {
+            let msgs = tids();
+            let buf = encode(&msgs, *framing);
+            assert_eq!(read_all(&buf, *framing).unwrap(), msgs);
+            assert_eq!(read_all(&[], *framing).unwrap(), Vec::new());
+        }
+    }
+
+    #[test]
+    fn errors_locate_the_failing_message() {
+        let msgs = tids();
+        let mut buf = encode(&msgs[..2], Framing::Varint);
+        let second_offset = encode(&msgs[..1], Framing::Varint).len();
+        buf.truncate(buf.len() - 1);
+
+        let err = read_all(&buf, Framing::Varint).unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(msg.contains("message 1 "), "{}", msg);
+        assert!(
+            msg.contains(&format!("byte offset {}", second_offset)),
+            "{}",
+            msg
+        );
+        assert!(msg.contains("truncated"), "{}", msg);
+    }
+
+    #[test]
+    fn rejects_messages_over_the_maximum_size() {
+        let buf = encode(&tids()[..1], Framing::BigEndian);
+        let res: anyhow::Result<Vec<Tid>> = MessageReader::new(buf.as_slice(), Framing::BigEndian)
+            .with_max_message_size(4)
+            .collect();
+        assert!(format!("{:#}", res.unwrap_err()).contains("larger than the maximum"));
+
+        // a huge length must not be allocated up front
+        let huge = u32::MAX.to_be_bytes();
+        assert!(read_all(&huge, Framing::BigEndian).is_err());
+    }
+}
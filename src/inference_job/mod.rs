@@ -2,12 +2,16 @@ use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     convert::TryFrom,
     iter::FromIterator,
+    marker::PhantomData,
 };
 
 use anyhow::Context;
 use cwe_checker_lib::{
-    analysis::graph::{Graph, Node},
-    intermediate_representation::{Arg, Project, RuntimeMemoryImage, Tid},
+    analysis::{
+        graph::{Graph, Node},
+        pointer_inference::Config,
+    },
+    intermediate_representation::{Arg, Jmp, Project, RuntimeMemoryImage, Tid},
     AnalysisResults,
 };
 
@@ -15,34 +19,55 @@ use petgraph::graph::NodeIndex;
 use serde::de::DeserializeOwned;
 
 use crate::{
-    analysis::{callgraph, fixup_returns},
+    analysis::{
+        callgraph, fixup_returns,
+        memory_returns::{self, MemoryReturn},
+        reaching_definitions::ClobberPolicy,
+    },
+    constraint_dsl,
     constraint_generation::NodeContext,
     constraints::{
         AdditionalConstraint, ConstraintSet, SubtypeConstraint, TyConstraint, TypeVariable,
         VariableManager,
     },
-    lowering::{CType, LoweringContext, TypeId},
+    lowering::{
+        confidence::{self, ConstraintEvidence, TypeConfidence},
+        conflicts::{ConflictDetector, TypeConflict},
+        CType, LoweringContext, LoweringPolicy, TypeId,
+    },
     node_context::{
-        points_to::PointsToContext,
+        points_to::{ObjectNamingPolicy, PointsToContext, DEFAULT_PTR_CONFIG},
         register_map::{self, RegisterContext},
         subproc_loc::ProcedureContext,
         GhidraConstantResolver,
     },
     solver::{
         constraint_graph::RuleContext,
-        scc_constraint_generation::{self, LatticeInfo, ProgramInfo},
+        scc_constraint_generation::{
+            self, CacheStats, LatticeInfo, ProgramInfo, SimplificationCache,
+        },
         type_lattice::{
             CustomLatticeElement, EnumeratedNamedLattice, LatticeDefinition, NamedLattice,
         },
-        type_sketch::{identity_element, LatticeBounds, SCCSketchsBuilder, SketchGraph},
+        type_sketch::{
+            identity_element, LatticeBounds, PolymorphismPolicy, SCCSketchsBuilder, SketchGraph,
+        },
     },
     util::FileDebugLogger,
 };
 use crate::{ctypes, pb_constraints};
-use byteorder::{BigEndian, ReadBytesExt};
 use prost::Message;
 use std::io::Read;
 
+/// A single manifest file that describes all inputs and options of an inference job.
+pub mod manifest;
+
+/// Streaming readers for length delimited protobuf messages.
+pub mod message_reader;
+
+use self::manifest::{InputFormat, JobManifest};
+use self::message_reader::{Framing, MessageReader};
+
 /// Defines a type inference job in terms of the input files.
 /// The interchange format can be protobuf or json depending on
 /// wether human readable input and output is required.
@@ -57,6 +82,8 @@ pub struct JobDefinition {
     pub additional_constraints_file: String,
     /// The interesting tids in the IR to solve types for.
     pub interesting_tids: String,
+    /// The maximum encoded size of a single protobuf message in the inputs.
+    pub max_message_size: usize,
 }
 
 /// A type inference job that has been parsed into its in memory representation.
@@ -70,53 +97,85 @@ pub struct InferenceJob {
     vman: VariableManager,
     debug_dir: FileDebugLogger,
     should_use_aggressive_shared_returns: bool,
+    simplification_cache: SimplificationCache,
+    changed_constraint_tids: BTreeSet<Tid>,
+    lowering_policy: LoweringPolicy,
+    pointer_config: Config,
+    clobber_policy: ClobberPolicy,
+    polymorphism_policy: PolymorphismPolicy,
+    object_naming_policy: ObjectNamingPolicy,
+    specialize_callsites: bool,
+    memory_returns: HashMap<Tid, Vec<MemoryReturn>>,
 }
 
 /// A way to parse readers into a given representation type
 pub trait InferenceParsing<T> {
-    /// Parse a collection of messages from a reader
-    fn parse_collection<R: Read>(rdr: R) -> anyhow::Result<Vec<T>>;
+    /// Parse a collection of messages from a reader, rejecting protobuf messages larger than the maximum message size
+    fn parse_collection<R: Read>(rdr: R, max_message_size: usize) -> anyhow::Result<Vec<T>>;
 }
 
-struct ProtobufParsing;
+/// Selects how the messages of a protobuf input are delimited.
+pub trait ProtobufFraming {
+    /// The framing of each message
+    const FRAMING: Framing;
+}
 
-impl<T: Message + Default> InferenceParsing<T> for ProtobufParsing {
-    fn parse_collection<R: Read>(rdr: R) -> anyhow::Result<Vec<T>> {
-        parse_collection_from_file(rdr)
+struct ProtobufParsing<F>(PhantomData<F>);
+
+impl<F: ProtobufFraming, T: Message + Default> InferenceParsing<T> for ProtobufParsing<F> {
+    fn parse_collection<R: Read>(rdr: R, max_message_size: usize) -> anyhow::Result<Vec<T>> {
+        MessageReader::new(rdr, F::FRAMING)
+            .with_max_message_size(max_message_size)
+            .collect()
     }
 }
 
 fn try_from_collection<V, R: TryFrom<V>, T: InferenceParsing<V>, X: Read>(
     rdr: X,
+    max_message_size: usize,
 ) -> anyhow::Result<Vec<R>>
 where
     anyhow::Error: From<<R as TryFrom<V>>::Error>,
 {
-    let coll = T::parse_collection(rdr)?;
+    let coll = T::parse_collection(rdr, max_message_size)?;
 
     coll.into_iter()
         .map(|x| R::try_from(x).map_err(anyhow::Error::from))
         .collect()
 }
 
-/// A struct the represents parsing input as protobuf.
+/// A struct the represents parsing input as protobuf messages each prefixed by a 4 byte big endian length.
 pub struct ProtobufDef;
 
-impl InferenceParsing<SubtypeConstraint> for ProtobufDef {
-    fn parse_collection<X: Read>(rdr: X) -> anyhow::Result<Vec<SubtypeConstraint>> {
+impl ProtobufFraming for ProtobufDef {
+    const FRAMING: Framing = Framing::BigEndian;
+}
+
+/// A struct that represents parsing input as protobuf messages each prefixed by a varint length, as written by writeDelimitedTo.
+pub struct DelimitedProtobufDef;
+
+impl ProtobufFraming for DelimitedProtobufDef {
+    const FRAMING: Framing = Framing::Varint;
+}
+
+impl<F: ProtobufFraming> InferenceParsing<SubtypeConstraint> for F {
+    fn parse_collection<X: Read>(
+        rdr: X,
+        max_message_size: usize,
+    ) -> anyhow::Result<Vec<SubtypeConstraint>> {
         try_from_collection::<
             pb_constraints::SubtypingConstraint,
             SubtypeConstraint,
-            ProtobufParsing,
+            ProtobufParsing<F>,
             X,
-        >(rdr)
+        >(rdr, max_message_size)
     }
 }
 
 // TODO(ian): fix this code dup somehow, problem is we dont own Tid to implement a standard conversion
-impl InferenceParsing<Tid> for ProtobufDef {
-    fn parse_collection<X: Read>(rdr: X) -> anyhow::Result<Vec<Tid>> {
-        let pb = ProtobufParsing::parse_collection(rdr)?;
+impl<F: ProtobufFraming> InferenceParsing<Tid> for F {
+    fn parse_collection<X: Read>(rdr: X, max_message_size: usize) -> anyhow::Result<Vec<Tid>> {
+        let pb = ProtobufParsing::<F>::parse_collection(rdr, max_message_size)?;
         Ok(pb
             .into_iter()
             .map(|x: ctypes::Tid| Tid::create(x.name, x.address))
@@ -124,60 +183,132 @@ impl InferenceParsing<Tid> for ProtobufDef {
     }
 }
 
-impl InferenceParsing<AdditionalConstraint> for ProtobufDef {
-    fn parse_collection<R: Read>(rdr: R) -> anyhow::Result<Vec<AdditionalConstraint>> {
+impl<F: ProtobufFraming> InferenceParsing<AdditionalConstraint> for F {
+    fn parse_collection<R: Read>(
+        rdr: R,
+        max_message_size: usize,
+    ) -> anyhow::Result<Vec<AdditionalConstraint>> {
         try_from_collection::<
             pb_constraints::AdditionalConstraint,
             AdditionalConstraint,
-            ProtobufParsing,
+            ProtobufParsing<F>,
             R,
-        >(rdr)
+        >(rdr, max_message_size)
     }
 }
 
+/// A way to parse a reader into the definition of a type lattice
+pub trait LatticeParsing {
+    /// Parses a lattice definition from the reader
+    fn parse_lattice<R: Read>(rdr: R) -> anyhow::Result<LatticeDefinition>;
+}
+
 /// A struct that represents parsing input as json.
 pub struct JsonDef;
 
 impl<T: DeserializeOwned> InferenceParsing<T> for JsonDef {
-    fn parse_collection<R: Read>(rdr: R) -> anyhow::Result<Vec<T>> {
-        serde_json::from_reader(rdr).map_err(anyhow::Error::from)
+    fn parse_collection<R: Read>(rdr: R, _max_message_size: usize) -> anyhow::Result<Vec<T>> {
+        crate::interchange::from_reader(rdr)
     }
 }
 
-type LoweredTypeMap = (HashMap<NodeIndex, TypeId>, BTreeMap<TypeId, CType>);
-type UserDefinedSketches = SketchGraph<LatticeBounds<CustomLatticeElement>>;
+impl LatticeParsing for JsonDef {
+    fn parse_lattice<R: Read>(rdr: R) -> anyhow::Result<LatticeDefinition> {
+        crate::interchange::from_reader(rdr).context("lattice json")
+    }
+}
 
-fn parse_collection_from_file<T: Message + Default, R: Read>(mut r: R) -> anyhow::Result<Vec<T>> {
-    let mut total = Vec::new();
-    loop {
-        let res = r.read_u32::<BigEndian>();
-
-        match res {
-            Err(err) => {
-                if matches!(err.kind(), std::io::ErrorKind::UnexpectedEof) {
-                    return Ok(total);
-                } else {
-                    return Err(anyhow::Error::from(err));
-                }
-            }
-            Ok(sz) => {
-                let mut buf = vec![0; sz as usize];
-                r.read_exact(&mut buf)?;
+// NOTE(Ian): there is no protobuf lattice message, jobs with protobuf inputs take a json lattice
+impl<F: ProtobufFraming> LatticeParsing for F {
+    fn parse_lattice<R: Read>(rdr: R) -> anyhow::Result<LatticeDefinition> {
+        JsonDef::parse_lattice(rdr)
+    }
+}
 
-                let res = T::decode(buf.as_ref())
-                    .map_err(|_err| anyhow::anyhow!("Decoding error for type T"))?;
-                total.push(res);
-            }
+/// A struct that represents parsing input in the textual format of [crate::constraint_dsl].
+pub struct TextDef;
+
+fn read_to_string<R: Read>(mut rdr: R) -> anyhow::Result<String> {
+    let mut buf = String::new();
+    rdr.read_to_string(&mut buf)?;
+    Ok(buf)
+}
+
+impl InferenceParsing<AdditionalConstraint> for TextDef {
+    fn parse_collection<R: Read>(
+        rdr: R,
+        _max_message_size: usize,
+    ) -> anyhow::Result<Vec<AdditionalConstraint>> {
+        constraint_dsl::parse_additional_constraints(&read_to_string(rdr)?)
+            .map_err(anyhow::Error::from)
+    }
+}
+
+impl InferenceParsing<Tid> for TextDef {
+    fn parse_collection<R: Read>(rdr: R, _max_message_size: usize) -> anyhow::Result<Vec<Tid>> {
+        constraint_dsl::parse_tids(&read_to_string(rdr)?).map_err(anyhow::Error::from)
+    }
+}
+
+/// Json lattices are also accepted so that existing lattice files can be used with text inputs.
+impl LatticeParsing for TextDef {
+    fn parse_lattice<R: Read>(rdr: R) -> anyhow::Result<LatticeDefinition> {
+        let text = read_to_string(rdr)?;
+        if text.trim_start().starts_with('{') {
+            JsonDef::parse_lattice(text.as_bytes())
+        } else {
+            constraint_dsl::parse_lattice(&text)
+                .map_err(anyhow::Error::from)
+                .context("lattice text")
         }
     }
 }
 
+type LoweredTypeMap = (HashMap<NodeIndex, TypeId>, BTreeMap<TypeId, CType>);
+type UserDefinedSketches = SketchGraph<LatticeBounds<CustomLatticeElement>>;
+type TypeConfidenceMap = HashMap<NodeIndex, TypeConfidence>;
+
 impl InferenceJob {
     /// Get the lattice for this inference job
     pub fn get_lattice(&self) -> &EnumeratedNamedLattice {
         &self.lattice
     }
 
+    /// Sets the policy used to select primitive types from lattice bounds when lowering.
+    pub fn set_lowering_policy(&mut self, policy: LoweringPolicy) {
+        self.lowering_policy = policy;
+    }
+
+    /// Gets the policy used to select primitive types from lattice bounds when lowering.
+    pub fn get_lowering_policy(&self) -> LoweringPolicy {
+        self.lowering_policy
+    }
+
+    /// Sets the policy that determines which registers lose their definitions across calls in the reaching definitions analysis.
+    pub fn set_clobber_policy(&mut self, policy: ClobberPolicy) {
+        self.clobber_policy = policy;
+    }
+
+    /// Sets the policy that determines how callsite types are bound to the formal types of callees.
+    pub fn set_polymorphism_policy(&mut self, policy: PolymorphismPolicy) {
+        self.polymorphism_policy = policy;
+    }
+
+    /// Sets the policy that determines how abstract objects from the pointer analysis are named as type variables.
+    pub fn set_object_naming_policy(&mut self, policy: ObjectNamingPolicy) {
+        self.object_naming_policy = policy;
+    }
+
+    /// Sets whether the sketch graph keeps the callee's type instantiated at each callsite, see [InferenceJob::get_callsite_labeling].
+    pub fn set_specialize_callsites(&mut self, specialize_callsites: bool) {
+        self.specialize_callsites = specialize_callsites;
+    }
+
+    /// Sets the pointer analysis configuration, including the allocator symbols, used to compute node contexts.
+    pub fn set_pointer_config(&mut self, config: Config) {
+        self.pointer_config = config;
+    }
+
     /// Gets the logger struct associated with this job.
     pub fn get_file_logger(&self) -> FileDebugLogger {
         self.debug_dir.clone()
@@ -205,23 +336,40 @@ impl InferenceJob {
     pub fn parse_lattice_json_to_lattice_def(
         lattice_json: &str,
     ) -> anyhow::Result<LatticeDefinition> {
-        let lattice_fl = std::fs::File::open(lattice_json)?;
-        let lattice_def: LatticeDefinition = serde_json::from_reader(lattice_fl)
-            .map_err(|e| anyhow::Error::from(e).context("lattice json"))?;
-        Ok(lattice_def)
+        Self::parse_lattice_def::<JsonDef>(lattice_json)
+    }
+
+    /// Parses a lattice definition from a file in the given format.
+    pub fn parse_lattice_def<L: LatticeParsing>(
+        lattice_file: &str,
+    ) -> anyhow::Result<LatticeDefinition> {
+        let lattice_fl = std::fs::File::open(lattice_file)?;
+        L::parse_lattice(lattice_fl)
     }
 
     /// Parses the lattice to a [EnumeratedLattice] and the type variable representing the weakest possible integer type (the greatest integer type on the lattice).
+    /// The float types are added to the lattice if it does not already have them, and the string types are merged into it.
     pub fn parse_lattice_json(
         lattice_json: &str,
         additional_lattices: Vec<LatticeDefinition>,
     ) -> anyhow::Result<(EnumeratedNamedLattice, TypeVariable)> {
-        let mut lattice_def = Self::parse_lattice_json_to_lattice_def(lattice_json)?;
+        Self::parse_lattice::<JsonDef>(lattice_json, additional_lattices)
+    }
+
+    /// Parses the lattice as in [InferenceJob::parse_lattice_json] from a file in the given format.
+    pub fn parse_lattice<L: LatticeParsing>(
+        lattice_file: &str,
+        additional_lattices: Vec<LatticeDefinition>,
+    ) -> anyhow::Result<(EnumeratedNamedLattice, TypeVariable)> {
+        let mut lattice_def = Self::parse_lattice_def::<L>(lattice_file)?.with_float_types();
+        let string_types = lattice_def.string_types();
+        lattice_def = lattice_def.merge_with_other(string_types)?;
 
         for lat in additional_lattices {
             lattice_def = lattice_def.merge_with_other(lat)?;
         }
 
+        lattice_def.validate().context("lattice json")?;
         let named_lattice = lattice_def.generate_lattice();
         Ok((
             named_lattice,
@@ -229,30 +377,40 @@ impl InferenceJob {
         ))
     }
 
-    /// Parses a set of additional subtyping constraints
+    /// Parses a set of additional subtyping constraints, rejecting protobuf messages larger than the maximum message size
     pub fn parse_additional_constraints<T: InferenceParsing<AdditionalConstraint>>(
         additional_constraints_file: &str,
+        max_message_size: usize,
     ) -> anyhow::Result<BTreeMap<Tid, ConstraintSet>> {
         let constraint_file =
             std::fs::File::open(additional_constraints_file).context("additional constraints")?;
-        let constraints = T::parse_collection(constraint_file)?;
+        let constraints = T::parse_collection(constraint_file, max_message_size)?;
 
         Ok(constraints
             .into_iter()
             .fold(BTreeMap::new(), |mut acc, add_cons| {
-                acc.entry(add_cons.associated_variable)
-                    .or_insert_with(ConstraintSet::default)
-                    .insert(TyConstraint::SubTy(add_cons.constraint));
+                Self::insert_additional_constraint(&mut acc, add_cons);
                 acc
             }))
     }
 
+    fn insert_additional_constraint(
+        additional_constraints: &mut BTreeMap<Tid, ConstraintSet>,
+        add_cons: AdditionalConstraint,
+    ) {
+        additional_constraints
+            .entry(add_cons.associated_variable)
+            .or_insert_with(ConstraintSet::default)
+            .insert(TyConstraint::SubTy(add_cons.constraint));
+    }
+
     fn parse_tid_set<T: InferenceParsing<Tid>>(
         interesting_tid_file: &str,
+        max_message_size: usize,
     ) -> anyhow::Result<HashSet<Tid>> {
         let constraint_file =
             std::fs::File::open(interesting_tid_file).context("parsing interesting tids")?;
-        let tids = T::parse_collection(constraint_file)?;
+        let tids = T::parse_collection(constraint_file, max_message_size)?;
         Ok(HashSet::from_iter(tids.into_iter()))
     }
 
@@ -309,7 +467,10 @@ impl InferenceJob {
 
         let nd_context = crate::node_context::create_default_context(
             &analysis_results,
-            crate::node_context::points_to::DEFAULT_PTR_CONFIG.clone(),
+            self.pointer_config.clone(),
+            self.clobber_policy,
+            self.object_naming_policy,
+            self.memory_returns.clone(),
             self.weakest_integral_type.clone(),
             self.debug_dir.clone(),
         )?;
@@ -330,12 +491,10 @@ impl InferenceJob {
         &self.additional_constraints
     }
 
-    /// Fix up the returns for the project owned by this job by inserting returns
-    /// Ghidra missed related to tail calls.
-    pub fn recover_additional_shared_returns(&mut self) {
+    fn get_reaching_defs_start_of_block(&self) -> HashMap<Tid, RegisterContext> {
         let grph = Self::graph_from_project(&self.proj);
-        let reg_context = register_map::run_analysis(&self.proj, &grph);
-        let reaching_defs_start_of_block = reg_context
+        let reg_context = register_map::run_analysis(&self.proj, &grph, self.clobber_policy);
+        reg_context
             .iter()
             .filter_map(|(k, v)| {
                 let nd = grph[(*k)];
@@ -344,11 +503,30 @@ impl InferenceJob {
                     _ => None,
                 }
             })
-            .collect();
+            .collect()
+    }
+
+    /// Fix up the returns for the project owned by this job by inserting returns
+    /// Ghidra missed related to tail calls.
+    pub fn recover_additional_shared_returns(&mut self) {
+        let reaching_defs_start_of_block = self.get_reaching_defs_start_of_block();
         let mut rets = fixup_returns::Context::new(&mut self.proj, reaching_defs_start_of_block);
         rets.apply_psuedo_returns();
     }
 
+    /// Detects sret pointers and out-parameters, which are then typed as out parameters of their procedures.
+    pub fn recover_memory_returns(&mut self) {
+        let reaching_defs_start_of_block = self.get_reaching_defs_start_of_block();
+        self.memory_returns =
+            memory_returns::Context::new(&self.proj, reaching_defs_start_of_block)
+                .collect_memory_returns();
+    }
+
+    /// Gets the results each procedure returns through memory, found by [InferenceJob::recover_memory_returns].
+    pub fn get_memory_returns(&self) -> &HashMap<Tid, Vec<MemoryReturn>> {
+        &self.memory_returns
+    }
+
     /// Get the contextual information needed for the weighted pushdown automata rules
     /// including interesting variables and type lattice information.
     pub fn get_rule_context(&self) -> RuleContext {
@@ -374,10 +552,48 @@ impl InferenceJob {
         &mut self.vman
     }
 
+    /// Adds constraints to this job. The sccs containing the associated tids are resimplified on the next incremental run.
+    pub fn add_additional_constraints(
+        &mut self,
+        constraints: impl IntoIterator<Item = AdditionalConstraint>,
+    ) {
+        for add_cons in constraints {
+            self.changed_constraint_tids
+                .insert(add_cons.associated_variable.clone());
+            Self::insert_additional_constraint(&mut self.additional_constraints, add_cons);
+        }
+    }
+
+    /// Gets how many sccs the last simplification reused from the cache and how many it resimplified.
+    pub fn get_simplification_stats(&self) -> CacheStats {
+        self.simplification_cache.get_stats()
+    }
+
+    /// Gets the type variable for the weakest integral type in the lattice.
+    pub fn get_weakest_integral_type(&self) -> &TypeVariable {
+        &self.weakest_integral_type
+    }
+
     /// Computes simplified type constraints for each scc in this project
     pub fn get_simplified_constraints(
         &mut self,
     ) -> anyhow::Result<Vec<scc_constraint_generation::SCCConstraints>> {
+        self.simplification_cache = SimplificationCache::default();
+        self.simplify_constraints()
+    }
+
+    /// Computes simplified type constraints for each scc in this project, only resimplifying sccs affected by
+    /// constraints added since the last run. Results for all other sccs are reused from the previous run.
+    pub fn get_simplified_constraints_incremental(
+        &mut self,
+    ) -> anyhow::Result<Vec<scc_constraint_generation::SCCConstraints>> {
+        self.simplify_constraints()
+    }
+
+    fn simplify_constraints(
+        &mut self,
+    ) -> anyhow::Result<Vec<scc_constraint_generation::SCCConstraints>> {
+        // NOTE(Ian): node contexts are recomputed every run since the cfg borrows the project.
         let grph = Self::graph_from_project(&self.proj);
         let node_ctxt = self.get_node_context(&grph)?;
 
@@ -410,12 +626,16 @@ impl InferenceJob {
             self.debug_dir.clone(),
             &self.additional_constraints,
         );
-        let res = context.get_simplified_constraints();
+        let res = context.get_simplified_constraints_incremental(
+            &mut self.simplification_cache,
+            &self.changed_constraint_tids,
+        )?;
+        self.changed_constraint_tids.clear();
         println!(
             "Num generated recursive variables: {}",
             self.vman.num_generated_loop_breakers()
         );
-        res
+        Ok(res)
     }
 
     /// Converts simplified scc constraints into a single type supergraph with labels
@@ -430,19 +650,25 @@ impl InferenceJob {
             scc_constraints,
             &self.lattice,
             self.get_lattice_elems().collect(),
+            crate::constraint_generation::collect_comparison_constants(&self.proj),
             self.debug_dir.clone(),
+            self.polymorphism_policy,
         );
 
         bldr.build()?;
-        bldr.build_global_type_graph()
+        if self.specialize_callsites {
+            bldr.build_global_type_graph_with_callsites()
+        } else {
+            bldr.build_global_type_graph()
+        }
     }
 
     /// For a given sketch supergraph, build a mapping from interesting type variables to the node that represents them.
     pub fn get_graph_labeling(
         &self,
         grph: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
-    ) -> HashMap<Tid, NodeIndex> {
-        let mut tot = HashMap::new();
+    ) -> BTreeMap<Tid, NodeIndex> {
+        let mut tot = BTreeMap::new();
         self.get_interesting_tids().iter().for_each(|x| {
             let tvar = crate::constraint_generation::tid_to_tvar(x);
             if let Some(idx) =
@@ -454,6 +680,44 @@ impl InferenceJob {
         tot
     }
 
+    /// Collects the direct calls in the program as the call's [Tid], the callee's [Tid], and the callsite tagged type variable of the callee.
+    fn get_callsite_instantiations(&self) -> Vec<(Tid, Tid, TypeVariable)> {
+        self.proj
+            .program
+            .term
+            .subs
+            .values()
+            .flat_map(|sub| sub.term.blocks.iter())
+            .flat_map(|blk| {
+                blk.term.jmps.iter().filter_map(move |jmp| {
+                    if let Jmp::Call { target, .. } = &jmp.term {
+                        Some((
+                            jmp.tid.clone(),
+                            target.clone(),
+                            crate::constraint_generation::tid_to_tvar_with_tag(target, &blk.tid),
+                        ))
+                    } else {
+                        None
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// For a sketch supergraph built with callsite specialization, build a mapping from each call to the node representing the callee's type at that callsite.
+    pub fn get_callsite_labeling(
+        &self,
+        grph: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
+    ) -> BTreeMap<Tid, NodeIndex> {
+        self.get_callsite_instantiations()
+            .into_iter()
+            .filter_map(|(call, _, tvar)| {
+                grph.get_node_index_for_variable(&crate::constraints::DerivedTypeVar::new(tvar))
+                    .map(|idx| (call, idx))
+            })
+            .collect()
+    }
+
     fn get_out_parameter_mapping(&self) -> HashMap<Tid, Vec<Arg>> {
         self.proj
             .program
@@ -470,13 +734,29 @@ impl InferenceJob {
         sg: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
     ) -> anyhow::Result<LoweredTypeMap> {
         let id = identity_element(&self.lattice);
-        LoweringContext::new(
+        let out_params = self.get_out_parameter_mapping();
+        let mut ctx = LoweringContext::new(
             sg,
             &self.get_graph_labeling(sg),
-            &self.get_out_parameter_mapping(),
+            &out_params,
+            &self.memory_returns,
             id,
-        )
-        .collect_ctypes()
+            self.lowering_policy,
+        );
+
+        if self.specialize_callsites {
+            let callsite_nodes = self
+                .get_callsite_instantiations()
+                .into_iter()
+                .filter_map(|(_, callee, tvar)| {
+                    sg.get_node_index_for_variable(&crate::constraints::DerivedTypeVar::new(tvar))
+                        .map(|idx| (idx, callee))
+                })
+                .collect();
+            ctx.add_instantiated_nodes(&callsite_nodes, &out_params, &self.memory_returns);
+        }
+
+        ctx.collect_ctypes()
     }
 
     /// Infer the universal type graph, joining all sketches together.
@@ -484,15 +764,41 @@ impl InferenceJob {
         &mut self,
         // debug_dir: &PathBuf,
     ) -> anyhow::Result<SketchGraph<LatticeBounds<CustomLatticeElement>>> {
+        self.infer_labeled_graph_with_evidence()
+            .map(|(labeled_graph, _evidence)| labeled_graph)
+    }
+
+    /// Infer the universal type graph, additionally collecting the constraint evidence for each type variable.
+    pub fn infer_labeled_graph_with_evidence(
+        &mut self,
+    ) -> anyhow::Result<(
+        SketchGraph<LatticeBounds<CustomLatticeElement>>,
+        ConstraintEvidence,
+    )> {
         if self.should_use_aggressive_shared_returns {
             self.recover_additional_shared_returns();
         }
         let cons = self.get_simplified_constraints()?;
+        let evidence = ConstraintEvidence::from_scc_constraints(&cons);
 
         // Insert additional constraints, additional constraints are now mapped to a tid, and inserted into the scc that has that tid.
 
         let labeled_graph = self.get_labeled_sketch_graph(cons)?;
-        Ok(labeled_graph)
+        Ok((labeled_graph, evidence))
+    }
+
+    /// Infer the universal type graph after adding constraints with [InferenceJob::add_additional_constraints].
+    /// Only the sccs containing the tids of added constraints and their transitive callers are resimplified,
+    /// then the global sketch graph is rebuilt. Falls back to a full run if nothing has been inferred yet.
+    pub fn infer_labeled_graph_incremental(
+        &mut self,
+    ) -> anyhow::Result<SketchGraph<LatticeBounds<CustomLatticeElement>>> {
+        if self.simplification_cache.is_empty() {
+            return self.infer_labeled_graph();
+        }
+
+        let cons = self.get_simplified_constraints_incremental()?;
+        self.get_labeled_sketch_graph(cons)
     }
 
     /// Applies all default analyses to compute types. First, tailcall returns are fixed, then simplified scc constraints are generated.
@@ -508,13 +814,79 @@ impl InferenceJob {
         Ok((labeled_graph, lowered))
     }
 
+    /// Computes a confidence for the type of each node in a sketch graph from constraint evidence and the tightness of the node's bounds.
+    pub fn compute_type_confidence(
+        &self,
+        sg: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
+        evidence: &ConstraintEvidence,
+    ) -> TypeConfidenceMap {
+        let lattice_elems = self.lattice.get_nds().values().cloned().collect::<Vec<_>>();
+        confidence::compute_confidence(sg, evidence, &lattice_elems)
+    }
+
+    /// Detects conflicting type information in a sketch graph, attributing each conflict to the interesting tids that reach it.
+    pub fn detect_type_conflicts(
+        &self,
+        sg: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
+    ) -> Vec<TypeConflict> {
+        let weakest_integral_type = self
+            .lattice
+            .get_elem(&self.weakest_integral_type.get_name())
+            .expect("the weak integer type is always in the lattice");
+        let conflicts = ConflictDetector::new(sg, &weakest_integral_type)
+            .detect_conflicts(&self.get_graph_labeling(sg));
+
+        conflicts.iter().for_each(|conflict| {
+            log::warn!(
+                "Type conflict {:?} at node {}: {}",
+                conflict.kind,
+                conflict.node.index(),
+                conflict.description
+            )
+        });
+
+        conflicts
+    }
+
+    /// Infers ctypes as in [InferenceJob::infer_ctypes] and additionally computes a confidence for each node's type.
+    pub fn infer_ctypes_with_confidence(
+        &mut self,
+    ) -> anyhow::Result<(UserDefinedSketches, LoweredTypeMap, TypeConfidenceMap)> {
+        let (labeled_graph, evidence) = self.infer_labeled_graph_with_evidence()?;
+
+        let lowered = self.lower_labeled_sketch_graph(&labeled_graph)?;
+        let confidence = self.compute_type_confidence(&labeled_graph, &evidence);
+        Ok((labeled_graph, lowered, confidence))
+    }
+
     /// Gets the set of interesting terms that are solved for.
     pub fn get_interesting_tids(&self) -> &HashSet<Tid> {
         &self.interesting_tids
     }
 
     /// Parses a job definition to an [InferenceJob] using a marker type that impelments the parsing.
-    pub fn parse<T: InferenceParsing<AdditionalConstraint> + InferenceParsing<Tid>>(
+    pub fn parse<
+        T: InferenceParsing<AdditionalConstraint> + InferenceParsing<Tid> + LatticeParsing,
+    >(
+        def: &JobDefinition,
+        debug_dir: Option<String>,
+        additional_lattices: Vec<LatticeDefinition>,
+        should_use_aggressive_shared_returns: bool,
+    ) -> anyhow::Result<InferenceJob> {
+        Self::parse_with::<T, T, T>(
+            def,
+            debug_dir,
+            additional_lattices,
+            should_use_aggressive_shared_returns,
+        )
+    }
+
+    /// Parses a job definition to an [InferenceJob] where additional constraints, interesting tids and the lattice may use different formats.
+    pub fn parse_with<
+        C: InferenceParsing<AdditionalConstraint>,
+        I: InferenceParsing<Tid>,
+        L: LatticeParsing,
+    >(
         def: &JobDefinition,
         debug_dir: Option<String>,
         additional_lattices: Vec<LatticeDefinition>,
@@ -524,13 +896,16 @@ impl InferenceJob {
         let proj = Self::parse_project(&def.ir_json_path, &bin)
             .with_context(|| "Trying to parse project")?;
         let (lat, weakest_integral_type) =
-            Self::parse_lattice_json(&def.lattice_json, additional_lattices)
+            Self::parse_lattice::<L>(&def.lattice_json, additional_lattices)
                 .with_context(|| "Trying to parse lattice")?;
-        let additional_constraints =
-            Self::parse_additional_constraints::<T>(&def.additional_constraints_file)
-                .with_context(|| "Trying to parse additional constraints")?;
-        let interesting_tids = Self::parse_tid_set::<T>(&def.interesting_tids)
-            .with_context(|| "Trying to parse interesting tids")?;
+        let additional_constraints = Self::parse_additional_constraints::<C>(
+            &def.additional_constraints_file,
+            def.max_message_size,
+        )
+        .with_context(|| "Trying to parse additional constraints")?;
+        let interesting_tids =
+            Self::parse_tid_set::<I>(&def.interesting_tids, def.max_message_size)
+                .with_context(|| "Trying to parse interesting tids")?;
 
         Ok(InferenceJob {
             binary_bytes: bin,
@@ -542,6 +917,107 @@ impl InferenceJob {
             vman: VariableManager::new(),
             debug_dir: FileDebugLogger::new(debug_dir),
             should_use_aggressive_shared_returns,
+            simplification_cache: SimplificationCache::default(),
+            changed_constraint_tids: BTreeSet::new(),
+            lowering_policy: LoweringPolicy::default(),
+            pointer_config: DEFAULT_PTR_CONFIG.clone(),
+            clobber_policy: ClobberPolicy::default(),
+            polymorphism_policy: PolymorphismPolicy::default(),
+            object_naming_policy: ObjectNamingPolicy::default(),
+            specialize_callsites: false,
+            memory_returns: HashMap::new(),
         })
     }
+
+    fn parse_manifest_tids_with<C: InferenceParsing<AdditionalConstraint>>(
+        tids_format: InputFormat,
+        def: &JobDefinition,
+        debug_dir: Option<String>,
+        additional_lattices: Vec<LatticeDefinition>,
+        should_use_aggressive_shared_returns: bool,
+    ) -> anyhow::Result<InferenceJob> {
+        match tids_format {
+            InputFormat::Json => Self::parse_with::<C, JsonDef, JsonDef>(
+                def,
+                debug_dir,
+                additional_lattices,
+                should_use_aggressive_shared_returns,
+            ),
+            InputFormat::Protobuf => Self::parse_with::<C, ProtobufDef, JsonDef>(
+                def,
+                debug_dir,
+                additional_lattices,
+                should_use_aggressive_shared_returns,
+            ),
+            InputFormat::DelimitedProtobuf => Self::parse_with::<C, DelimitedProtobufDef, JsonDef>(
+                def,
+                debug_dir,
+                additional_lattices,
+                should_use_aggressive_shared_returns,
+            ),
+            InputFormat::Text => Self::parse_with::<C, TextDef, JsonDef>(
+                def,
+                debug_dir,
+                additional_lattices,
+                should_use_aggressive_shared_returns,
+            ),
+        }
+    }
+
+    /// Parses a [JobManifest] file to an [InferenceJob], loading each input in the format the manifest declares for it.
+    pub fn parse_manifest(
+        manifest_path: &str,
+        debug_dir: Option<String>,
+    ) -> anyhow::Result<InferenceJob> {
+        let manifest = JobManifest::from_file(manifest_path)
+            .with_context(|| format!("Trying to parse manifest {}", manifest_path))?;
+        let def = manifest.get_job_definition();
+        let additional_lattices = manifest
+            .get_additional_lattice_paths()
+            .iter()
+            .map(|pth| Self::parse_lattice_json_to_lattice_def(pth))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| "Trying to parse additional lattices")?;
+        let aggressive_returns = manifest.use_aggressive_shared_returns;
+
+        let tids_format = manifest.interesting_tids.format;
+        let mut job = match manifest.additional_constraints.format {
+            InputFormat::Json => Self::parse_manifest_tids_with::<JsonDef>(
+                tids_format,
+                &def,
+                debug_dir,
+                additional_lattices,
+                aggressive_returns,
+            ),
+            InputFormat::Protobuf => Self::parse_manifest_tids_with::<ProtobufDef>(
+                tids_format,
+                &def,
+                debug_dir,
+                additional_lattices,
+                aggressive_returns,
+            ),
+            InputFormat::DelimitedProtobuf => {
+                Self::parse_manifest_tids_with::<DelimitedProtobufDef>(
+                    tids_format,
+                    &def,
+                    debug_dir,
+                    additional_lattices,
+                    aggressive_returns,
+                )
+            }
+            InputFormat::Text => Self::parse_manifest_tids_with::<TextDef>(
+                tids_format,
+                &def,
+                debug_dir,
+                additional_lattices,
+                aggressive_returns,
+            ),
+        }?;
+
+        if let Some(allocation_symbols) = manifest.allocation_symbols {
+            job.set_pointer_config(Config { allocation_symbols });
+        }
+
+        Ok(job)
+    }
 }
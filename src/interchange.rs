@@ -0,0 +1,298 @@
+//! Json inputs and outputs are wrapped in an [Envelope] that records the version of the format they were written in.
+//! Payloads without an envelope are from before versioning (version 0) and are upgraded when loaded.
+//! Protobuf outputs carry the same version in a [crate::ctypes::FormatVersion] message on the top level [CTypeMapping], and length delimited
+//! protobuf streams start with a [crate::ctypes::StreamHeader] holding it. Streams without a header are from before versioning.
+use std::{
+    convert::TryFrom,
+    io::{Read, Write},
+};
+
+use anyhow::Context;
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    constraints::{AdditionalConstraint, SubtypeConstraint},
+    ctypes::{self, CTypeMapping},
+    solver::type_lattice::LatticeDefinition,
+};
+
+/// The current version of the interchange formats, bumped on every incompatible change.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A versioned wrapper around a json payload.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Envelope<T> {
+    /// The version of the format the payload was written in
+    pub version: u32,
+    /// The wrapped input or output
+    pub payload: T,
+}
+
+/// The json representation of a [cwe_checker_lib::intermediate_representation::Tid], used to describe tids in schemas.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TidSchema {
+    /// The unique identifier of the term
+    pub id: String,
+    /// The address of the term
+    pub address: String,
+}
+
+/// Gets the protobuf message for the current format version.
+pub fn format_version_message() -> ctypes::FormatVersion {
+    ctypes::FormatVersion {
+        version: FORMAT_VERSION,
+    }
+}
+
+/// Gets the header written before the elements of a length delimited protobuf stream.
+pub fn stream_header_message() -> ctypes::StreamHeader {
+    ctypes::StreamHeader {
+        format_version: Some(format_version_message()),
+    }
+}
+
+/// Checks that a protobuf input was not written in a newer format than this version understands.
+/// Inputs without a version predate versioning and are accepted.
+pub fn check_format_version_message(version: &Option<ctypes::FormatVersion>) -> anyhow::Result<()> {
+    match version {
+        Some(version) if version.version > FORMAT_VERSION => {
+            Err(newer_version_error(version.version))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn newer_version_error(version: u32) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Format version {} is newer than the supported version {}",
+        version,
+        FORMAT_VERSION
+    )
+}
+
+/// Splits an envelope into its version and payload. A value that is not an envelope is a version 0 payload.
+fn open_envelope(value: Value) -> anyhow::Result<(u32, Value)> {
+    match value {
+        Value::Object(mut obj) if obj.contains_key("version") && obj.contains_key("payload") => {
+            let version = obj
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow::anyhow!("Envelope version is not an integer"))?;
+            Ok((
+                u32::try_from(version).map_err(|_| newer_version_error(u32::MAX))?,
+                obj.remove("payload").unwrap_or(Value::Null),
+            ))
+        }
+        payload => Ok((0, payload)),
+    }
+}
+
+/// Upgrades a payload written in an older version to the current version.
+fn upgrade(version: u32, payload: Value) -> anyhow::Result<Value> {
+    if version > FORMAT_VERSION {
+        return Err(newer_version_error(version));
+    }
+
+    // NOTE(Ian): version 0 primitives are still accepted by [crate::lowering::CType]'s deserializer so there is nothing to rewrite yet.
+    Ok(payload)
+}
+
+/// Reads a json payload that is either wrapped in an [Envelope] or a bare version 0 payload, upgrading it to the current version.
+pub fn from_reader<T: DeserializeOwned, R: Read>(rdr: R) -> anyhow::Result<T> {
+    let value: Value = serde_json::from_reader(rdr)?;
+    let (version, payload) = open_envelope(value)?;
+    let payload = upgrade(version, payload)?;
+    serde_json::from_value(payload)
+        .with_context(|| format!("Reading a payload of format version {}", version))
+}
+
+/// Writes a json payload wrapped in an [Envelope] with the current version.
+pub fn to_writer<T: Serialize, W: Write>(wtr: W, payload: &T) -> anyhow::Result<()> {
+    serde_json::to_writer(
+        wtr,
+        &Envelope {
+            version: FORMAT_VERSION,
+            payload,
+        },
+    )
+    .map_err(anyhow::Error::from)
+}
+
+/// Generates the JSON Schema of each enveloped interchange format, named by the file the schema is published as.
+pub fn generate_schemas() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        (
+            "subtype_constraints.schema.json",
+            schema_for!(Envelope<Vec<SubtypeConstraint>>),
+        ),
+        (
+            "additional_constraints.schema.json",
+            schema_for!(Envelope<Vec<AdditionalConstraint>>),
+        ),
+        (
+            "interesting_tids.schema.json",
+            schema_for!(Envelope<Vec<TidSchema>>),
+        ),
+        (
+            "lattice.schema.json",
+            schema_for!(Envelope<LatticeDefinition>),
+        ),
+        (
+            "ctype_mapping.schema.json",
+            schema_for!(Envelope<CTypeMapping>),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use cwe_checker_lib::intermediate_representation::Tid;
+    use prost::Message;
+
+    use super::{from_reader, generate_schemas, stream_header_message, to_writer, FORMAT_VERSION};
+    use crate::{
+        constraints::{AdditionalConstraint, SubtypeConstraint, TypeVariable},
+        ctypes,
+        inference_job::{
+            message_reader::DEFAULT_MAX_MESSAGE_SIZE, DelimitedProtobufDef, InferenceParsing,
+        },
+        lowering::CType,
+        pb_constraints,
+    };
+
+    const V0_CONSTRAINTS: &str = r#"[{
+        "constraint": {
+            "lhs": {"var": {"name": "sub_00001000"}, "labels": [{"Out": 0}]},
+            "rhs": {"var": {"name": "int"}, "labels": []}
+        },
+        "associated_variable": {"id": "sub_00001000", "address": "00001000"}
+    }]"#;
+
+    #[test]
+    fn loads_bare_and_enveloped_payloads() {
+        let bare: Vec<AdditionalConstraint> = from_reader(V0_CONSTRAINTS.as_bytes()).unwrap();
+        assert_eq!(bare.len(), 1);
+        assert_eq!(
+            bare[0].associated_variable,
+            Tid::create("sub_00001000".to_owned(), "00001000".to_owned())
+        );
+        assert_eq!(
+            bare[0].constraint.rhs.get_base_variable(),
+            &TypeVariable::new("int".to_owned())
+        );
+
+        let mut written = Vec::new();
+        to_writer(&mut written, &bare).unwrap();
+        let enveloped: serde_json::Value = serde_json::from_slice(&written).unwrap();
+        assert_eq!(enveloped["version"], FORMAT_VERSION);
+        assert_eq!(
+            enveloped["payload"][0]["constraint"]["lhs"]["var"]["is_global"],
+            false
+        );
+
+        let reloaded: Vec<AdditionalConstraint> = from_reader(written.as_slice()).unwrap();
+        assert_eq!(reloaded, bare);
+    }
+
+    #[test]
+    fn upgrades_v0_primitives() {
+        const V0_CTYPES: &str = r#"[{"Primitive": "int"}]"#;
+        let bare: Vec<CType> = serde_json::from_str(V0_CTYPES).unwrap();
+        let upgraded: Vec<CType> = from_reader(V0_CTYPES.as_bytes()).unwrap();
+        assert_eq!(bare, upgraded);
+        assert_eq!(
+            upgraded,
+            vec![CType::Primitive {
+                type_constant: "int".to_owned(),
+                lower_bound: "".to_owned(),
+                upper_bound: "int".to_owned(),
+                is_const: false,
+            }]
+        );
+
+        let current = serde_json::to_string(&upgraded).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<CType>>(&current).unwrap(),
+            upgraded
+        );
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let newer = format!(r#"{{"version": {}, "payload": []}}"#, FORMAT_VERSION + 1);
+        assert!(from_reader::<Vec<AdditionalConstraint>, _>(newer.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn stream_headers_version_protobuf_streams() {
+        let newer = ctypes::StreamHeader {
+            format_version: Some(ctypes::FormatVersion {
+                version: FORMAT_VERSION + 1,
+            }),
+        };
+
+        let parse_tids = |header: Option<ctypes::StreamHeader>| {
+            let mut buf = Vec::new();
+            if let Some(header) = header {
+                header.encode_length_delimited(&mut buf).unwrap();
+            }
+            ctypes::Tid {
+                name: "sub_00001000".to_owned(),
+                address: "00001000".to_owned(),
+            }
+            .encode_length_delimited(&mut buf)
+            .unwrap();
+            <DelimitedProtobufDef as InferenceParsing<Tid>>::parse_collection(
+                buf.as_slice(),
+                DEFAULT_MAX_MESSAGE_SIZE,
+            )
+        };
+        let tid = Tid::create("sub_00001000".to_owned(), "00001000".to_owned());
+        assert_eq!(parse_tids(None).unwrap(), vec![tid.clone()]);
+        assert_eq!(
+            parse_tids(Some(stream_header_message())).unwrap(),
+            vec![tid]
+        );
+        assert!(parse_tids(Some(newer.clone())).is_err());
+
+        let dtv = |base_var: &str| pb_constraints::DerivedTypeVariable {
+            base_var: base_var.to_owned(),
+            field_labels: vec![],
+        };
+        let parse_constraints = |header: ctypes::StreamHeader| {
+            let mut buf = Vec::new();
+            header.encode_length_delimited(&mut buf).unwrap();
+            pb_constraints::SubtypingConstraint {
+                lhs: Some(dtv("sub_00001000")),
+                rhs: Some(dtv("int")),
+            }
+            .encode_length_delimited(&mut buf)
+            .unwrap();
+            <DelimitedProtobufDef as InferenceParsing<SubtypeConstraint>>::parse_collection(
+                buf.as_slice(),
+                DEFAULT_MAX_MESSAGE_SIZE,
+            )
+        };
+        assert_eq!(
+            parse_constraints(stream_header_message())
+                .unwrap()
+                .iter()
+                .map(|cons| cons.to_string())
+                .collect::<Vec<_>>(),
+            vec!["sub_00001000 ⊑ int".to_owned()]
+        );
+        assert!(parse_constraints(newer).is_err());
+    }
+
+    #[test]
+    fn schemas_require_the_envelope() {
+        for (name, schema) in generate_schemas() {
+            let schema = serde_json::to_value(&schema).unwrap();
+            let required = schema["required"].as_array().expect(name);
+            assert!(required.contains(&serde_json::json!("version")), "{}", name);
+            assert!(required.contains(&serde_json::json!("payload")), "{}", name);
+        }
+    }
+}
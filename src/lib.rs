@@ -16,6 +16,9 @@ pub mod constraint_generation;
 /// Our model of subtyping constraints
 pub mod constraints;
 
+/// A textual format for additional constraints and interesting tids.
+pub mod constraint_dsl;
+
 /// Node contexts handle flow/context sensitive information for a given node's type constraints.
 pub mod node_context;
 
@@ -46,6 +49,9 @@ pub mod pb_constraints {
     include!(concat!(env!("OUT_DIR"), "/constraints.rs"));
 }
 
+/// Versioned envelopes, compatibility with older versions, and JSON Schemas for the json inputs and outputs.
+pub mod interchange;
+
 /// Parses a context of file inputs into an inference job which can be run to retrieve generated constraints,
 /// simplified constraints, and lowered types.
 pub mod inference_job;
@@ -59,20 +65,27 @@ mod tests {
     };
 
     use crate::{
+        analysis::reaching_definitions::ClobberPolicy,
         constraints::{self, DerivedTypeVar, Field, FieldLabel, TypeVariable},
         graph_algos::find_node,
         inference_job::{self, ProtobufDef},
-        solver::{type_lattice::CustomLatticeElement, type_sketch::SketchGraph},
+        solver::{
+            scc_constraint_generation::CacheStats, type_lattice::CustomLatticeElement,
+            type_sketch::SketchGraph,
+        },
     };
     use crate::{
         constraints::{SubtypeConstraint, TyConstraint},
-        inference_job::{InferenceJob, JobDefinition, JsonDef},
-        lowering::CType,
+        inference_job::{
+            message_reader::DEFAULT_MAX_MESSAGE_SIZE, InferenceJob, JobDefinition, JsonDef, TextDef,
+        },
+        lowering::{self, CType},
         solver::type_sketch::LatticeBounds,
     };
     use cwe_checker_lib::intermediate_representation::{self, Tid};
     use petgraph::{graph::NodeIndex, visit::EdgeRef, Direction::Outgoing};
     use pretty_assertions::assert_eq;
+    use prost::Message;
     use std::convert::TryFrom;
 
     fn init() {
@@ -110,8 +123,7 @@ mod tests {
 
     fn parse_ctype_mapping(fname: &str) -> anyhow::Result<HashMap<NodeIndex, CType>> {
         let f = std::fs::File::open(fname)?;
-        let content: HashMap<NodeIndex, CType> = serde_json::from_reader(f)?;
-        Ok(content)
+        crate::interchange::from_reader(f)
     }
 
     struct ExpectedOutputs {
@@ -346,6 +358,7 @@ mod tests {
                     interesting_tids: Self::test_data_dir(
                         self.interesting_tids_file.expect("need initeresting tids"),
                     ),
+                    max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
                 },
                 expected_outputs: ExpectedOutputFiles {
                     constraint_gen: self
@@ -372,94 +385,421 @@ mod tests {
     }
 
     #[test]
-    fn test_mooosl_globals() {
+    fn text_inputs_match_json_inputs() {
+        init();
+        let list_test_case =
+            |additional_constraints: &str, interesting_tids: &str, lattice: &str| {
+                let mut bldr = TestCaseBuilder::new();
+                bldr.set_binary_path("list_test/list_test.so".to_owned())
+                    .set_ir_json_path("list_test/ir.json".to_owned())
+                    .set_additional_constraints(additional_constraints.to_owned())
+                    .set_lattice_json(lattice.to_owned())
+                    .set_interesting_tids_file(interesting_tids.to_owned());
+                bldr.build()
+            };
+
+        let json_tc = list_test_case(
+            "list_test_additional_constraints.json",
+            "list_test_interesting_tids.json",
+            "list_test_lattice.json",
+        );
+        let json_job = InferenceJob::parse::<JsonDef>(&json_tc.job_def, None, vec![], false)
+            .expect("json inputs should parse");
+
+        let text_tc = list_test_case(
+            "list_test_additional_constraints.txt",
+            "list_test_interesting_tids.txt",
+            "list_test_lattice.txt",
+        );
+        let text_job = InferenceJob::parse::<TextDef>(&text_tc.job_def, None, vec![], false)
+            .expect("text inputs should parse");
+
+        assert_eq!(
+            json_job.get_additional_constraints(),
+            text_job.get_additional_constraints()
+        );
+        assert_eq!(
+            json_job.get_interesting_tids(),
+            text_job.get_interesting_tids()
+        );
+
+        let lattice = |format_path: &str| {
+            let pth = TestCaseBuilder::test_data_dir(format_path);
+            let def = if format_path.ends_with(".txt") {
+                InferenceJob::parse_lattice_def::<TextDef>(&pth)
+            } else {
+                InferenceJob::parse_lattice_def::<JsonDef>(&pth)
+            };
+            serde_json::to_value(def.expect("lattice should parse")).unwrap()
+        };
+        assert_eq!(
+            lattice("list_test_lattice.json"),
+            lattice("list_test_lattice.txt")
+        );
+        // text jobs still accept json lattices
+        assert!(
+            InferenceJob::parse_lattice_def::<TextDef>(&TestCaseBuilder::test_data_dir(
+                "list_test_lattice.json"
+            ))
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn job_definition_limits_protobuf_message_size() {
+        init();
+        let mut tc = mooosl_test_case().build();
+        InferenceJob::parse::<ProtobufDef>(&tc.job_def, None, vec![], false)
+            .expect("protobuf inputs should parse under the default limit");
+
+        tc.job_def.max_message_size = 1;
+        let err = InferenceJob::parse::<ProtobufDef>(&tc.job_def, None, vec![], false)
+            .err()
+            .expect("messages over the limit should be rejected");
+        assert!(format!("{:#}", err).contains("larger than the maximum"));
+    }
+
+    fn assert_equivalent_sketches(
+        job: &InferenceJob,
+        expected: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
+        actual: &SketchGraph<LatticeBounds<CustomLatticeElement>>,
+    ) {
+        for tid in job.get_interesting_tids() {
+            let dtv = DerivedTypeVar::new(crate::constraint_generation::tid_to_tvar(tid));
+            let expected_reprs = expected.get_representing_sketch(dtv.clone());
+            let actual_reprs = actual.get_representing_sketch(dtv);
+            assert_eq!(expected_reprs.len(), actual_reprs.len());
+
+            for ((_, expected_sk), (_, actual_sk)) in expected_reprs.iter().zip(actual_reprs.iter())
+            {
+                assert!(
+                    expected_sk
+                        .difference(actual_sk)
+                        .empty_language_or_epsilon()
+                        && actual_sk
+                            .difference(expected_sk)
+                            .empty_language_or_epsilon(),
+                    "Sketches for {} differ:\n{}\n{}",
+                    tid,
+                    expected_sk,
+                    actual_sk
+                );
+
+                assert_eq!(
+                    expected_sk.get_graph().get_graph()[expected_sk.get_entry()],
+                    actual_sk.get_graph().get_graph()[actual_sk.get_entry()]
+                );
+            }
+        }
+    }
+
+    fn assert_incremental_matches_full_run<P>(tc: TestCase)
+    where
+        P: inference_job::InferenceParsing<constraints::AdditionalConstraint>,
+        P: inference_job::InferenceParsing<intermediate_representation::Tid>,
+    {
+        init();
+        let mut incremental_job = InferenceJob::parse::<P>(&tc.job_def, None, vec![], false)
+            .expect("inputs should parse");
+        let sccs = incremental_job
+            .get_simplified_constraints()
+            .expect("initial simplification should succeed");
+        assert_eq!(
+            incremental_job.get_simplification_stats(),
+            CacheStats {
+                reused: 0,
+                resimplified: sccs.len()
+            }
+        );
+
+        // constrain the first parameter of the first interesting subprocedure, falling back to any subprocedure
+        let mut interesting = incremental_job
+            .get_interesting_tids()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        interesting.sort();
+        let changed_tid = interesting
+            .into_iter()
+            .find(|tid| sccs.iter().any(|scc| scc.scc.contains(tid)))
+            .or_else(|| sccs.iter().flat_map(|scc| scc.scc.iter()).next().cloned())
+            .expect("the program should have a subprocedure");
+        let mut param =
+            DerivedTypeVar::new(crate::constraint_generation::tid_to_tvar(&changed_tid));
+        param.add_field_label(FieldLabel::In(0));
+        let new_constraint = constraints::AdditionalConstraint {
+            constraint: SubtypeConstraint::new(
+                param,
+                DerivedTypeVar::new(incremental_job.get_weakest_integral_type().clone()),
+            ),
+            associated_variable: changed_tid,
+        };
+
+        let mut full_job = InferenceJob::parse::<P>(&tc.job_def, None, vec![], false)
+            .expect("inputs should parse");
+        full_job.add_additional_constraints(vec![new_constraint.clone()]);
+        let full_graph = full_job
+            .infer_labeled_graph()
+            .expect("full inference should succeed");
+
+        incremental_job.add_additional_constraints(vec![new_constraint]);
+        let incremental_graph = incremental_job
+            .infer_labeled_graph_incremental()
+            .expect("incremental inference should succeed");
+        let stats = incremental_job.get_simplification_stats();
+        assert_eq!(stats.reused + stats.resimplified, sccs.len());
+        assert!(stats.resimplified >= 1);
+        assert_equivalent_sketches(&full_job, &full_graph, &incremental_graph);
+
+        // nothing changed since the last run so every scc comes from the cache
+        let cached_graph = incremental_job
+            .infer_labeled_graph_incremental()
+            .expect("cached inference should succeed");
+        assert_eq!(
+            incremental_job.get_simplification_stats(),
+            CacheStats {
+                reused: sccs.len(),
+                resimplified: 0
+            }
+        );
+        assert_equivalent_sketches(&full_job, &full_graph, &cached_graph);
+    }
+
+    #[test]
+    fn incremental_inference_matches_full_run() {
+        let mut bldr = TestCaseBuilder::new();
+        bldr.set_binary_path("list_test/list_test.so".to_owned())
+            .set_ir_json_path("list_test/ir.json".to_owned())
+            .set_additional_constraints("list_test_additional_constraints.json".to_owned())
+            .set_lattice_json("list_test_lattice.json".to_owned())
+            .set_interesting_tids_file("list_test_interesting_tids.json".to_owned());
+        assert_incremental_matches_full_run::<JsonDef>(bldr.build());
+
         let mut bldr = TestCaseBuilder::new();
         bldr.set_binary_path("mooosl".to_owned())
             .set_ir_json_path("mooosl_artifacts/ir.json".to_owned())
             .set_additional_constraints("mooosl_artifacts/additional_constraints.pb".to_owned())
             .set_lattice_json("mooosl_artifacts/lattice.json".to_owned())
-            .set_interesting_tids_file("mooosl_artifacts/interesting_tids.pb".to_owned())
-            .add_sketch_property(Box::new(|skg| {
-                let reprs = skg.get_representing_sketch(DerivedTypeVar::new(
-                    TypeVariable::new_global("glb_00104040_DAT_00104040".to_owned()),
-                ));
-
-                assert_eq!(reprs.len(), 1);
-
-                let (_glob_idx, r_sk) = &reprs[0];
-                let target_path = vec![
-                    FieldLabel::Load,
-                    FieldLabel::Field(constraints::Field {
-                        offset: 0,
-                        size: 64,
-                    }),
-                    FieldLabel::Load,
-                    FieldLabel::Field(constraints::Field {
-                        offset: 24,
-                        size: 64,
-                    }),
-                ];
-                let tnode = find_node(
-                    r_sk.get_graph().get_graph(),
-                    r_sk.get_entry(),
-                    target_path.iter(),
-                )
-                .expect("should find target_node");
+            .set_interesting_tids_file("mooosl_artifacts/interesting_tids.pb".to_owned());
+        assert_incremental_matches_full_run::<ProtobufDef>(bldr.build());
 
-                let nd = &r_sk.get_graph().get_graph()[tnode];
-                assert_eq!(
-                    nd.get_upper().to_string(),
-                    "data_type_with_id_3193700096615474490"
-                );
+        let mut bldr = TestCaseBuilder::new();
+        bldr.set_binary_path("polymorphism_tests/test_prevent_unification.o".to_owned())
+            .set_ir_json_path("polymorphism_tests/test_prevent_unfication_ir.json".to_owned())
+            .set_additional_constraints(
+                "polymorphism_tests/prevent_unification_additional_constraints.json".to_owned(),
+            )
+            .set_lattice_json("polymorphism_tests/simple_lattice.json".to_owned())
+            .set_interesting_tids_file(
+                "polymorphism_tests/unification_interesting_tids.json".to_owned(),
+            );
+        assert_incremental_matches_full_run::<JsonDef>(bldr.build());
 
-                // check that lookups out param has the same node index as the global sub.in should be the same as glb.load.@0_size_64
+        let mut bldr = TestCaseBuilder::new();
+        bldr.set_binary_path("composite_return/composite_return.so".to_owned())
+            .set_ir_json_path("composite_return/composite_return.json".to_owned())
+            .set_additional_constraints("composite_return/additional_cons.json".to_owned())
+            .set_lattice_json("composite_return/lattice.json".to_owned())
+            .set_interesting_tids_file("composite_return/interesting_tids.json".to_owned());
+        assert_incremental_matches_full_run::<JsonDef>(bldr.build());
 
-                for k in skg.get_graph().get_node_mapping() {
-                    println!("has {}", k.0);
-                }
+        let mut bldr = TestCaseBuilder::new();
+        bldr.set_binary_path("simple_recursion/simple_recursion.so".to_owned())
+            .set_ir_json_path("simple_recursion/ir.json".to_owned())
+            .set_additional_constraints("simple_recursion/additional_constraints.pb".to_owned())
+            .set_lattice_json("simple_recursion/lattice.json".to_owned())
+            .set_interesting_tids_file("simple_recursion/interesting_tids.pb".to_owned());
+        assert_incremental_matches_full_run::<ProtobufDef>(bldr.build());
+    }
 
-                let lookup_reprs = skg.get_representing_sketch(DerivedTypeVar::new(
-                    TypeVariable::new("sub_001014fb".to_owned()),
-                ));
-
-                assert_eq!(lookup_reprs.len(), 1);
-                let (lookup_idx, _) = &lookup_reprs[0];
-                println!("lookup idx: {}", lookup_idx.index());
-                let lookup_in_idx = skg
-                    .get_graph()
-                    .get_graph()
-                    .edges_directed(*lookup_idx, Outgoing)
-                    .filter(|e| *e.weight() == FieldLabel::Out(0))
-                    .next()
-                    .expect("should have outgoing edge to out param")
-                    .target();
-
-                let glb_idx = skg
-                    .get_node_index_for_variable(&DerivedTypeVar::new(TypeVariable::new_global(
-                        "glb_00104040_DAT_00104040".to_owned(),
-                    )))
-                    .expect("should still have index");
-
-                let res = find_node(
-                    skg.get_graph().get_graph(),
-                    glb_idx,
-                    vec![
-                        FieldLabel::Load,
-                        FieldLabel::Field(Field {
-                            offset: 0,
-                            size: 64,
-                        }),
-                    ]
-                    .iter(),
-                )
-                .expect("should find global pointer load type");
+    #[test]
+    fn manifest_with_mixed_formats_runs_inference() {
+        init();
+        let manifest = TestCaseBuilder::test_data_dir("list_test_manifest.json");
+        let mut job =
+            InferenceJob::parse_manifest(&manifest, None).expect("manifest inputs should parse");
 
-                assert_eq!(res, lookup_in_idx);
-            }));
+        assert!(job.get_interesting_tids().contains(&Tid::create(
+            "sub_00001000".to_owned(),
+            "00001000".to_owned()
+        )));
+
+        job.infer_labeled_graph()
+            .expect("inference from manifest should succeed");
+    }
+
+    fn check_mooosl_global_types(skg: &SketchGraph<LatticeBounds<CustomLatticeElement>>) {
+        let reprs = skg.get_representing_sketch(DerivedTypeVar::new(TypeVariable::new_global(
+            "glb_00104040_DAT_00104040".to_owned(),
+        )));
+
+        assert_eq!(reprs.len(), 1);
+
+        let (_glob_idx, r_sk) = &reprs[0];
+        let target_path = vec![
+            FieldLabel::Load,
+            FieldLabel::Field(constraints::Field::new(0, 64)),
+            FieldLabel::Load,
+            FieldLabel::Field(constraints::Field::new(24, 64)),
+        ];
+        let tnode = find_node(
+            r_sk.get_graph().get_graph(),
+            r_sk.get_entry(),
+            target_path.iter(),
+        )
+        .expect("should find target_node");
+
+        let nd = &r_sk.get_graph().get_graph()[tnode];
+        assert_eq!(
+            nd.get_upper().to_string(),
+            "data_type_with_id_3193700096615474490"
+        );
+
+        // check that lookups out param has the same node index as the global sub.in should be the same as glb.load.@0_size_64
+
+        for k in skg.get_graph().get_node_mapping() {
+            println!("has {}", k.0);
+        }
+
+        let lookup_reprs = skg.get_representing_sketch(DerivedTypeVar::new(TypeVariable::new(
+            "sub_001014fb".to_owned(),
+        )));
+
+        assert_eq!(lookup_reprs.len(), 1);
+        let (lookup_idx, _) = &lookup_reprs[0];
+        println!("lookup idx: {}", lookup_idx.index());
+        let lookup_in_idx = skg
+            .get_graph()
+            .get_graph()
+            .edges_directed(*lookup_idx, Outgoing)
+            .filter(|e| *e.weight() == FieldLabel::Out(0))
+            .next()
+            .expect("should have outgoing edge to out param")
+            .target();
+
+        let glb_idx = skg
+            .get_node_index_for_variable(&DerivedTypeVar::new(TypeVariable::new_global(
+                "glb_00104040_DAT_00104040".to_owned(),
+            )))
+            .expect("should still have index");
+
+        let res = find_node(
+            skg.get_graph().get_graph(),
+            glb_idx,
+            vec![FieldLabel::Load, FieldLabel::Field(Field::new(0, 64))].iter(),
+        )
+        .expect("should find global pointer load type");
+
+        assert_eq!(res, lookup_in_idx);
+    }
+
+    fn mooosl_test_case() -> TestCaseBuilder {
+        let mut bldr = TestCaseBuilder::new();
+        bldr.set_binary_path("mooosl".to_owned())
+            .set_ir_json_path("mooosl_artifacts/ir.json".to_owned())
+            .set_additional_constraints("mooosl_artifacts/additional_constraints.pb".to_owned())
+            .set_lattice_json("mooosl_artifacts/lattice.json".to_owned())
+            .set_interesting_tids_file("mooosl_artifacts/interesting_tids.pb".to_owned());
+        bldr
+    }
+
+    #[test]
+    fn test_mooosl_globals() {
+        let mut bldr = mooosl_test_case();
+        bldr.add_sketch_property(Box::new(check_mooosl_global_types));
 
         run_test_case::<ProtobufDef>(bldr.build());
     }
 
+    #[test]
+    fn mooosl_calling_convention_clobbering() {
+        init();
+        let tc = mooosl_test_case().build();
+
+        // clobbering by calling convention only removes stale definitions, so the expected global types still hold
+        for policy in [
+            ClobberPolicy::InternalCallsOnly,
+            ClobberPolicy::CallingConvention,
+        ]
+        .iter()
+        {
+            let mut job =
+                InferenceJob::parse::<ProtobufDef>(&tc.job_def, None, vec![], false).unwrap();
+            job.recover_additional_shared_returns();
+            job.set_clobber_policy(*policy);
+            let labeled_graph = job
+                .infer_labeled_graph()
+                .expect("inference should succeed under each clobber policy");
+
+            check_mooosl_global_types(&labeled_graph);
+        }
+    }
+
+    #[test]
+    fn callsites_have_specialized_types() {
+        init();
+        let mut bldr = TestCaseBuilder::new();
+        bldr.set_binary_path("polymorphism_tests/test_prevent_unification.o".to_owned())
+            .set_ir_json_path("polymorphism_tests/test_prevent_unfication_ir.json".to_owned())
+            .set_additional_constraints(
+                "polymorphism_tests/prevent_unification_additional_constraints.json".to_owned(),
+            )
+            .set_lattice_json("polymorphism_tests/simple_lattice.json".to_owned())
+            .set_interesting_tids_file(
+                "polymorphism_tests/unification_interesting_tids.json".to_owned(),
+            );
+        let tc = bldr.build();
+
+        let mut job = InferenceJob::parse::<JsonDef>(&tc.job_def, None, vec![], false).unwrap();
+        job.set_specialize_callsites(true);
+        let (labeled_graph, (node_to_ty, _)) = job
+            .infer_ctypes()
+            .expect("inference with callsite specialization should succeed");
+
+        let callsites = job.get_callsite_labeling(&labeled_graph);
+        let callsite_node = |call: &str| {
+            callsites
+                .iter()
+                .find(|(tid, _)| tid.get_str_repr() == call)
+                .map(|(_, idx)| *idx)
+                .expect("call should have a specialized type")
+        };
+
+        // caller1 and caller2 both call alias_id
+        let caller1_call = callsite_node("instr_00000013_2");
+        let caller2_call = callsite_node("instr_0000001f_2");
+        assert_ne!(caller1_call, caller2_call);
+
+        // the type of the loaded field of alias_id's parameter at a callsite, if the instantiation has that field
+        let graph = labeled_graph.get_graph().get_graph();
+        let loaded_field = |call_idx: NodeIndex, size: usize| {
+            let path = vec![
+                FieldLabel::In(0),
+                FieldLabel::Load,
+                FieldLabel::Field(constraints::Field::new(0, size)),
+            ];
+            find_node(graph, call_idx, path.iter()).map(|idx| graph[idx].get_upper().to_string())
+        };
+        // each instantiation only has the field its caller loads
+        assert_eq!(loaded_field(caller1_call, 8), Some("char".to_owned()));
+        assert_eq!(loaded_field(caller1_call, 64), None);
+        assert_eq!(loaded_field(caller2_call, 64), Some("int".to_owned()));
+        assert_eq!(loaded_field(caller2_call, 8), None);
+
+        // the formal is refined by both callsites, so neither instantiation lowers to the formal's type
+        let alias_id = labeled_graph
+            .get_node_index_for_variable(&DerivedTypeVar::new(TypeVariable::new(
+                "sub_00000004".to_owned(),
+            )))
+            .expect("alias_id should have a node");
+        assert_eq!(loaded_field(alias_id, 8), Some("char".to_owned()));
+        assert_eq!(loaded_field(alias_id, 64), Some("int".to_owned()));
+        let formal_ty = node_to_ty[&alias_id];
+        assert_ne!(node_to_ty[&caller1_call], formal_ty);
+        assert_ne!(node_to_ty[&caller2_call], formal_ty);
+        assert_ne!(node_to_ty[&caller1_call], node_to_ty[&caller2_call]);
+    }
+
     #[test]
     fn test_composite_params_and_return() {
         let mut bldr = TestCaseBuilder::new();
@@ -471,6 +811,50 @@ mod tests {
         run_test_case::<JsonDef>(bldr.build());
     }
 
+    #[test]
+    fn lowered_output_is_deterministic() {
+        init();
+        let encode_lowered_types = || {
+            let mut bldr = TestCaseBuilder::new();
+            bldr.set_binary_path("composite_return/composite_return.so".to_owned())
+                .set_ir_json_path("composite_return/composite_return.json".to_owned())
+                .set_additional_constraints("composite_return/additional_cons.json".to_owned())
+                .set_lattice_json("composite_return/lattice.json".to_owned())
+                .set_interesting_tids_file("composite_return/interesting_tids.json".to_owned());
+            let tc = bldr.build();
+
+            let mut job = InferenceJob::parse::<JsonDef>(&tc.job_def, None, vec![], false).unwrap();
+            let (labeled_graph, (node_to_ty, types), confidence) = job
+                .infer_ctypes_with_confidence()
+                .expect("inference should succeed");
+
+            let names = lowering::naming::name_types(
+                &types,
+                &node_to_ty,
+                &job.get_graph_labeling(&labeled_graph),
+            );
+            let mut pb = lowering::convert_mapping_to_profobuf(types, &node_to_ty);
+            lowering::naming::add_names_to_protobuf(&mut pb, &names);
+            lowering::confidence::add_confidence_to_protobuf(&mut pb, &node_to_ty, &confidence);
+            lowering::conflicts::add_conflicts_to_protobuf(
+                &mut pb,
+                &job.detect_type_conflicts(&labeled_graph),
+                &node_to_ty,
+            );
+            lowering::add_tid_types_to_protobuf(
+                &mut pb,
+                &job.get_graph_labeling(&labeled_graph),
+                &node_to_ty,
+            );
+
+            let mut buf = Vec::new();
+            pb.encode(&mut buf).expect("should encode mapping");
+            buf
+        };
+
+        assert_eq!(encode_lowered_types(), encode_lowered_types());
+    }
+
     #[test]
     fn recursion_regression_test() {
         let mut bldr = TestCaseBuilder::new();
@@ -0,0 +1,357 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+};
+
+use petgraph::{graph::NodeIndex, EdgeDirection};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constraints::{DerivedTypeVar, SubtypeConstraint},
+    ctypes::{self, CTypeMapping},
+    graph_algos::find_node,
+    solver::{
+        scc_constraint_generation::SCCConstraints,
+        type_lattice::NamedLatticeElement,
+        type_sketch::{LatticeBounds, SketchGraph},
+    },
+};
+
+use super::{convert_typeid, TypeId};
+
+/// The number of simplified constraints that mention each derived type variable (or a variable derived from it).
+/// Variables are recorded without callsite tags so that callsite evidence supports the callee's formals.
+#[derive(Debug, Default, Clone)]
+pub struct ConstraintEvidence(BTreeMap<DerivedTypeVar, usize>);
+
+impl ConstraintEvidence {
+    fn record(&mut self, dtv: &DerivedTypeVar) {
+        let mut curr = DerivedTypeVar::new(dtv.get_base_variable().to_callee());
+        *self.0.entry(curr.clone()).or_insert(0) += 1;
+        for lbl in dtv.get_field_labels() {
+            curr.add_field_label(lbl.clone());
+            *self.0.entry(curr.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// Collects evidence from a set of subtyping constraints
+    pub fn from_constraints<'a>(cons: impl Iterator<Item = &'a SubtypeConstraint>) -> Self {
+        let mut evidence = ConstraintEvidence::default();
+        for c in cons {
+            evidence.record(&c.lhs);
+            evidence.record(&c.rhs);
+        }
+        evidence
+    }
+
+    /// Collects evidence from the simplified constraints of each scc
+    pub fn from_scc_constraints(sccs: &[SCCConstraints]) -> Self {
+        Self::from_constraints(sccs.iter().flat_map(|scc| scc.constraints.iter()))
+    }
+
+    /// Maps the evidence for each derived type variable to the node that represents it in the sketch graph.
+    pub fn get_node_evidence<U: PartialEq>(
+        &self,
+        grph: &SketchGraph<U>,
+    ) -> HashMap<NodeIndex, usize> {
+        let mut node_evidence = HashMap::new();
+        for (dtv, cnt) in self.0.iter() {
+            let maybe_idx = grph
+                .get_node_index_for_variable(&DerivedTypeVar::new(dtv.get_base_variable().clone()))
+                .and_then(|base_idx| {
+                    find_node(
+                        grph.get_graph().get_graph(),
+                        base_idx,
+                        dtv.get_field_labels().iter(),
+                    )
+                });
+
+            if let Some(idx) = maybe_idx {
+                *node_evidence.entry(idx).or_insert(0) += cnt;
+            }
+        }
+        node_evidence
+    }
+}
+
+/// How confident the solver is in the type lowered for a node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeConfidence {
+    /// The number of simplified constraints supporting this node's capabilities.
+    pub evidence_count: usize,
+    /// 1.0 when the lattice bounds pin a single type constant, 0.0 when they are unconstrained or unsatisfiable.
+    pub bounds_tightness: f32,
+    /// Combined score in [0, 1].
+    pub score: f32,
+}
+
+/// Computes the tightness of a node's bounds from the number of lattice elements between the lower and upper bound.
+pub fn bounds_tightness<U: NamedLatticeElement>(
+    bounds: &LatticeBounds<U>,
+    lattice_elems: &[U],
+) -> f32 {
+    if lattice_elems.len() <= 1 {
+        return 1.0;
+    }
+
+    let num_between = lattice_elems
+        .iter()
+        .filter(|&elem| bounds.get_lower() <= elem && elem <= bounds.get_upper())
+        .count();
+
+    if num_between == 0 {
+        // the lower bound is not below the upper bound
+        return 0.0;
+    }
+
+    1.0 - (num_between - 1) as f32 / (lattice_elems.len() - 1) as f32
+}
+
+/// Computes a confidence for each node in the sketch graph. Terminal nodes are lowered to primitives so
+/// their bounds are considered, other nodes are lowered structurally so only constraint evidence counts.
+pub fn compute_confidence<U: NamedLatticeElement>(
+    grph: &SketchGraph<LatticeBounds<U>>,
+    evidence: &ConstraintEvidence,
+    lattice_elems: &[U],
+) -> HashMap<NodeIndex, TypeConfidence> {
+    let node_evidence = evidence.get_node_evidence(grph);
+    let act_graph = grph.get_graph().get_graph();
+    act_graph
+        .node_indices()
+        .map(|idx| {
+            let evidence_count = node_evidence.get(&idx).cloned().unwrap_or(0);
+            let bounds_tightness = bounds_tightness(&act_graph[idx], lattice_elems);
+            let evidence_factor = evidence_count as f32 / (evidence_count as f32 + 1.0);
+            let is_terminal = act_graph
+                .edges_directed(idx, EdgeDirection::Outgoing)
+                .next()
+                .is_none();
+
+            let score = if is_terminal {
+                (evidence_factor + bounds_tightness) / 2.0
+            } else {
+                evidence_factor
+            };
+
+            (
+                idx,
+                TypeConfidence {
+                    evidence_count,
+                    bounds_tightness,
+                    score,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Adds the confidence of each node's type to the protobuf mapping. Types shared by multiple nodes (primitives) get the
+/// lowest confidence of the nodes that share them.
+pub fn add_confidence_to_protobuf(
+    mapping: &mut CTypeMapping,
+    node_to_ty: &HashMap<NodeIndex, TypeId>,
+    confidence: &HashMap<NodeIndex, TypeConfidence>,
+) {
+    for (idx, conf) in confidence.iter() {
+        if let Some(ty) = node_to_ty.get(idx) {
+            let pb_conf = ctypes::TypeConfidence {
+                evidence_count: u32::try_from(conf.evidence_count).unwrap_or(u32::MAX),
+                bounds_tightness: conf.bounds_tightness,
+                score: conf.score,
+            };
+
+            let entry = mapping
+                .type_id_to_confidence
+                .entry(convert_typeid(*ty).type_id)
+                .or_insert_with(|| pb_conf.clone());
+            // merged types keep the weakest confidence, ties are broken by evidence so the result does not depend on node order
+            if (
+                pb_conf.score,
+                pb_conf.evidence_count,
+                pb_conf.bounds_tightness,
+            ) < (entry.score, entry.evidence_count, entry.bounds_tightness)
+            {
+                *entry = pb_conf;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeSet, HashMap};
+
+    use cwe_checker_lib::intermediate_representation::Tid;
+    use petgraph::graph::NodeIndex;
+
+    use crate::{
+        constraints::{
+            parse_constraint_set, DerivedTypeVar, Field, FieldLabel, SubtypeConstraint,
+            TypeVariable,
+        },
+        ctypes::CTypeMapping,
+        graph_algos::find_node,
+        solver::type_sketch::identity_element,
+        test_utils::{sketch_for, test_lattice},
+        util::constraint_set_to_subtys,
+    };
+
+    use super::{
+        add_confidence_to_protobuf, bounds_tightness, compute_confidence, ConstraintEvidence,
+        TypeConfidence, TypeId,
+    };
+
+    fn dtv(base: TypeVariable, lbls: Vec<FieldLabel>) -> DerivedTypeVar {
+        DerivedTypeVar::create_with_path(base, lbls)
+    }
+
+    fn tv(name: &str) -> TypeVariable {
+        TypeVariable::new(name.to_owned())
+    }
+
+    #[test]
+    fn unconstrained_bounds_are_not_tight() {
+        let def = test_lattice(
+            &[("int", "weakint"), ("weakint", "top"), ("bottom", "int")],
+            "weakint",
+        );
+        let lattice = def.generate_lattice();
+        let elems = lattice.get_nds().values().cloned().collect::<Vec<_>>();
+
+        let id = identity_element(&lattice);
+        assert_eq!(bounds_tightness(&id, &elems), 0.0);
+    }
+
+    #[test]
+    fn evidence_counts_field_prefixes_of_callee_variables() {
+        let loaded = vec![
+            FieldLabel::In(0),
+            FieldLabel::Load,
+            FieldLabel::Field(Field::new(0, 32)),
+        ];
+        let cons = vec![
+            SubtypeConstraint::new(dtv(tv("f"), loaded.clone()), dtv(tv("a"), vec![])),
+            SubtypeConstraint::new(
+                dtv(
+                    TypeVariable::with_tag("f".to_owned(), Tid::new("call")),
+                    vec![FieldLabel::In(0)],
+                ),
+                dtv(tv("b"), vec![]),
+            ),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+
+        let evidence = ConstraintEvidence::from_constraints(cons.iter());
+        // the callsite's actual supports the callee's formal
+        assert!(evidence
+            .0
+            .keys()
+            .all(|var| var.get_base_variable() == &var.get_base_variable().to_callee()));
+        let count = |lbls: &[FieldLabel]| evidence.0[&dtv(tv("f"), lbls.to_vec())];
+        assert_eq!(count(&[]), 2);
+        assert_eq!(count(&loaded[..1]), 2);
+        assert_eq!(count(&loaded[..2]), 1);
+        assert_eq!(count(&loaded), 1);
+
+        let (_lattice, sg) = sketch_for(
+            &cons,
+            test_lattice(&[("int", "top"), ("bottom", "int")], "int"),
+        );
+        let node_evidence = evidence.get_node_evidence(&sg);
+        let f_idx = sg
+            .get_node_index_for_variable(&dtv(tv("f"), vec![]))
+            .expect("f should have a node");
+        let node = |lbls: &[FieldLabel]| {
+            find_node(sg.get_graph().get_graph(), f_idx, lbls.iter())
+                .expect("path should exist in the sketch")
+        };
+        assert_eq!(node_evidence[&node(&loaded[..1])], 2);
+        assert_eq!(node_evidence[&node(&loaded[..2])], 1);
+        // the field is unified with a, so both variables count toward its node
+        assert_eq!(node_evidence[&node(&loaded)], 2);
+    }
+
+    #[test]
+    fn tighter_and_better_supported_nodes_are_more_confident() {
+        let (_rem, cs_set) = parse_constraint_set(
+            "
+            s.store.σ32@0 <= weakint
+            m.load.σ32@0 <= int
+            int <= m.load.σ32@0
+        ",
+        )
+        .expect("should parse cs_set");
+        let cons = constraint_set_to_subtys(&cs_set);
+        let def = test_lattice(
+            &[("int", "weakint"), ("weakint", "top"), ("bottom", "int")],
+            "weakint",
+        );
+        let (lattice, sg) = sketch_for(&cons, def);
+        let elems = lattice.get_nds().values().cloned().collect::<Vec<_>>();
+        let confidence = compute_confidence(
+            &sg,
+            &ConstraintEvidence::from_constraints(cons.iter()),
+            &elems,
+        );
+
+        let node = |base: &str, lbls: Vec<FieldLabel>| {
+            let idx = sg
+                .get_node_index_for_variable(&dtv(tv(base), vec![]))
+                .expect("variable should have a node");
+            find_node(sg.get_graph().get_graph(), idx, lbls.iter())
+                .expect("path should exist in the sketch")
+        };
+        let field = FieldLabel::Field(Field::new(0, 32));
+        let stored = &confidence[&node("s", vec![FieldLabel::Store, field.clone()])];
+        let loaded = &confidence[&node("m", vec![FieldLabel::Load, field.clone()])];
+        let pointer = &confidence[&node("m", vec![FieldLabel::Load])];
+        let evidence_factor =
+            |conf: &TypeConfidence| conf.evidence_count as f32 / (conf.evidence_count as f32 + 1.0);
+
+        // terminal nodes combine evidence with the tightness of their bounds
+        assert_eq!(loaded.bounds_tightness, 1.0);
+        assert_eq!(
+            loaded.score,
+            (evidence_factor(loaded) + loaded.bounds_tightness) / 2.0
+        );
+        // other nodes are lowered structurally so only evidence counts
+        assert_eq!(pointer.score, evidence_factor(pointer));
+
+        assert!(stored.evidence_count < loaded.evidence_count);
+        assert!(stored.bounds_tightness < loaded.bounds_tightness);
+        assert!(stored.score < loaded.score);
+    }
+
+    #[test]
+    fn merged_types_keep_the_weakest_confidence() {
+        let conf = |evidence_count: usize, score: f32| TypeConfidence {
+            evidence_count,
+            bounds_tightness: 1.0,
+            score,
+        };
+        let node_to_ty = vec![
+            (NodeIndex::new(0), TypeId(1)),
+            (NodeIndex::new(1), TypeId(1)),
+            (NodeIndex::new(2), TypeId(1)),
+            (NodeIndex::new(3), TypeId(2)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let confidence = vec![
+            (NodeIndex::new(0), conf(3, 0.875)),
+            (NodeIndex::new(1), conf(1, 0.75)),
+            (NodeIndex::new(2), conf(7, 0.9375)),
+            (NodeIndex::new(3), conf(3, 0.875)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+        let mut mapping = CTypeMapping::default();
+        add_confidence_to_protobuf(&mut mapping, &node_to_ty, &confidence);
+        let merged = &mapping.type_id_to_confidence[&1];
+        assert_eq!(merged.score, 0.75);
+        assert_eq!(merged.evidence_count, 1);
+        assert_eq!(mapping.type_id_to_confidence[&2].score, 0.875);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use cwe_checker_lib::intermediate_representation::Tid;
+use petgraph::{graph::NodeIndex, visit::EdgeRef, EdgeDirection};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constraints::{self, FieldLabel},
+    ctypes::{self, CTypeMapping},
+    solver::{
+        type_lattice::NamedLatticeElement,
+        type_sketch::{LatticeBounds, SketchGraph},
+    },
+};
+
+use super::{convert_typeid, count_lowered_alternatives, TypeId};
+
+/// The kinds of type conflicts that are silently resolved by lowering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ConflictKind {
+    /// The lower bound of a node is not below its upper bound
+    UnsatisfiableBounds,
+    /// A node is loaded from or stored to but is bounded above by an integer type
+    PointerIntegerClash,
+    /// Fields of different sizes overlap, only one can be kept in a structure
+    OverlappingFields,
+    /// A node is used as several kinds of types, such as a structure and a function, and is lowered as a union
+    LoweredUnion,
+}
+
+/// A conflict found at a node in the sketch graph.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TypeConflict {
+    /// The kind of conflict
+    pub kind: ConflictKind,
+    /// The node where the conflict occurs
+    pub node: NodeIndex,
+    /// A human readable description of the conflict
+    pub description: String,
+    /// The interesting tids whose types reach the conflicting node
+    pub tids: BTreeSet<Tid>,
+}
+
+/// The bits covered by a field, widened so that large offsets cannot overflow.
+fn field_bit_range(fld: &constraints::Field) -> (i128, i128) {
+    let start = i128::from(fld.offset) * 8 + fld.bit_offset as i128;
+    (start, start + fld.size as i128)
+}
+
+fn fields_overlap(a: &constraints::Field, b: &constraints::Field) -> bool {
+    let (a_start, a_end) = field_bit_range(a);
+    let (b_start, b_end) = field_bit_range(b);
+    a_start < b_end && b_start < a_end
+}
+
+/// Finds conflicts in a sketch graph that would otherwise be resolved silently during lowering.
+pub struct ConflictDetector<'a, U: NamedLatticeElement> {
+    grph: &'a SketchGraph<LatticeBounds<U>>,
+    weakest_integral_type: &'a U,
+}
+
+impl<'a, U: NamedLatticeElement> ConflictDetector<'a, U> {
+    /// Creates a detector for the given graph where integers are the types below the weakest integral type.
+    pub fn new(
+        grph: &'a SketchGraph<LatticeBounds<U>>,
+        weakest_integral_type: &'a U,
+    ) -> ConflictDetector<'a, U> {
+        ConflictDetector {
+            grph,
+            weakest_integral_type,
+        }
+    }
+
+    fn outgoing_labels(&self, nd: NodeIndex) -> impl Iterator<Item = (&FieldLabel, NodeIndex)> {
+        self.grph
+            .get_graph()
+            .get_graph()
+            .edges_directed(nd, EdgeDirection::Outgoing)
+            .map(|e| (e.weight(), e.target()))
+    }
+
+    fn unsatisfiable_bounds(&self, nd: NodeIndex) -> Option<String> {
+        let bounds = &self.grph.get_graph().get_graph()[nd];
+        if bounds.get_lower() <= bounds.get_upper() {
+            None
+        } else {
+            Some(format!(
+                "lower bound {} is not below upper bound {}",
+                bounds.get_lower().get_name(),
+                bounds.get_upper().get_name()
+            ))
+        }
+    }
+
+    fn pointer_integer_clash(&self, nd: NodeIndex) -> Option<String> {
+        let bounds = &self.grph.get_graph().get_graph()[nd];
+        let is_pointer = self
+            .outgoing_labels(nd)
+            .any(|(lbl, _)| matches!(lbl, FieldLabel::Load | FieldLabel::Store));
+        let is_integer =
+            !bounds.get_upper().is_bot() && bounds.get_upper() <= self.weakest_integral_type;
+
+        if is_pointer && is_integer {
+            Some(format!(
+                "dereferenced as a pointer but bounded by integer type {}",
+                bounds.get_upper().get_name()
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn overlapping_fields(&self, nd: NodeIndex) -> Vec<String> {
+        let flds = self
+            .outgoing_labels(nd)
+            .filter_map(|(lbl, _)| {
+                if let FieldLabel::Field(fld) = lbl {
+                    Some(fld.clone())
+                } else {
+                    None
+                }
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut res = Vec::new();
+        for (i, fst) in flds.iter().enumerate() {
+            for snd in flds[i + 1..].iter() {
+                if fields_overlap(fst, snd) {
+                    res.push(format!(
+                        "field {} overlaps field {}",
+                        FieldLabel::Field(fst.clone()),
+                        FieldLabel::Field(snd.clone())
+                    ));
+                }
+            }
+        }
+        res
+    }
+
+    fn lowered_union(&self, nd: NodeIndex) -> Option<String> {
+        let alternatives = count_lowered_alternatives(nd, self.grph);
+        if alternatives > 1 {
+            Some(format!("lowered as a union of {} types", alternatives))
+        } else {
+            None
+        }
+    }
+
+    fn conflicts_at_node(&self, nd: NodeIndex) -> Vec<(ConflictKind, String)> {
+        let mut res = Vec::new();
+        res.extend(
+            self.unsatisfiable_bounds(nd)
+                .map(|desc| (ConflictKind::UnsatisfiableBounds, desc)),
+        );
+        res.extend(
+            self.pointer_integer_clash(nd)
+                .map(|desc| (ConflictKind::PointerIntegerClash, desc)),
+        );
+        res.extend(
+            self.overlapping_fields(nd)
+                .into_iter()
+                .map(|desc| (ConflictKind::OverlappingFields, desc)),
+        );
+        res.extend(
+            self.lowered_union(nd)
+                .map(|desc| (ConflictKind::LoweredUnion, desc)),
+        );
+        res
+    }
+
+    /// Detects all conflicts in the graph, attributing each to the interesting tids whose types reach the conflicting node.
+    pub fn detect_conflicts(&self, tid_to_node: &BTreeMap<Tid, NodeIndex>) -> Vec<TypeConflict> {
+        let reachable_from_tid = tid_to_node
+            .iter()
+            .map(|(tid, idx)| (tid, self.grph.get_graph().get_reachable_idxs(*idx)))
+            .collect::<Vec<_>>();
+
+        let mut conflicts = self
+            .grph
+            .get_graph()
+            .get_graph()
+            .node_indices()
+            .flat_map(|nd| {
+                self.conflicts_at_node(nd)
+                    .into_iter()
+                    .map(move |(kind, description)| (nd, kind, description))
+            })
+            .map(|(node, kind, description)| TypeConflict {
+                kind,
+                node,
+                description,
+                tids: reachable_from_tid
+                    .iter()
+                    .filter(|(_, reachable)| reachable.contains(&node))
+                    .map(|(tid, _)| (*tid).clone())
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        conflicts.sort();
+        conflicts
+    }
+}
+
+fn convert_conflict_kind(kind: ConflictKind) -> ctypes::ConflictKind {
+    match kind {
+        ConflictKind::UnsatisfiableBounds => ctypes::ConflictKind::UnsatisfiableBounds,
+        ConflictKind::PointerIntegerClash => ctypes::ConflictKind::PointerIntegerClash,
+        ConflictKind::OverlappingFields => ctypes::ConflictKind::OverlappingFields,
+        ConflictKind::LoweredUnion => ctypes::ConflictKind::LoweredUnion,
+    }
+}
+
+/// Adds conflicts to the protobuf mapping, referencing the type lowered for the conflicting node.
+pub fn add_conflicts_to_protobuf(
+    mapping: &mut CTypeMapping,
+    conflicts: &[TypeConflict],
+    node_to_ty: &HashMap<NodeIndex, TypeId>,
+) {
+    for conflict in conflicts {
+        mapping.conflicts.push(ctypes::TypeConflict {
+            kind: convert_conflict_kind(conflict.kind) as i32,
+            description: conflict.description.clone(),
+            tids: conflict
+                .tids
+                .iter()
+                .map(|tid| ctypes::Tid {
+                    name: tid.get_str_repr().to_owned(),
+                    address: tid.address.clone(),
+                })
+                .collect(),
+            type_id: node_to_ty.get(&conflict.node).map(|ty| convert_typeid(*ty)),
+        });
+    }
+
+    // conflicts are found in node order, so order them by the types they refer to instead
+    mapping.conflicts.sort_by_cached_key(|conflict| {
+        (
+            conflict.type_id.as_ref().map(|ty| ty.type_id),
+            conflict.kind,
+            conflict.description.clone(),
+            conflict
+                .tids
+                .iter()
+                .map(|tid| tid.name.clone())
+                .collect::<Vec<_>>(),
+        )
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashSet};
+
+    use crate::{
+        constraints::parse_constraint_set,
+        solver::type_lattice::NamedLattice,
+        test_utils::{sketch_for, test_lattice},
+        util::constraint_set_to_subtys,
+    };
+
+    use super::{ConflictDetector, ConflictKind};
+
+    fn conflict_kinds(cons: &str) -> HashSet<ConflictKind> {
+        let (_rem, cs_set) = parse_constraint_set(cons).expect("should parse cs_set");
+
+        let (lattice, sg) = sketch_for(
+            &constraint_set_to_subtys(&cs_set),
+            test_lattice(
+                &[("int", "weakint"), ("weakint", "top"), ("bottom", "int")],
+                "weakint",
+            ),
+        );
+
+        let weak_int = lattice
+            .get_elem("weakint")
+            .expect("should be part of lattice");
+        ConflictDetector::new(&sg, &weak_int)
+            .detect_conflicts(&BTreeMap::new())
+            .into_iter()
+            .map(|conflict| conflict.kind)
+            .collect::<HashSet<_>>()
+    }
+
+    #[test]
+    fn detects_pointer_integer_clash_and_overlapping_fields() {
+        let kinds = conflict_kinds(
+            "
+            x <= int
+            x.load.σ64@0 <= int
+            x.load.σ32@4 <= int
+        ",
+        );
+
+        assert!(kinds.contains(&ConflictKind::PointerIntegerClash));
+        assert!(kinds.contains(&ConflictKind::OverlappingFields));
+    }
+
+    #[test]
+    fn detects_partial_overlaps_of_equal_sizes() {
+        let kinds = conflict_kinds(
+            "
+            x.load.σ32@0 <= int
+            x.load.σ32@2 <= int
+        ",
+        );
+        assert!(kinds.contains(&ConflictKind::OverlappingFields));
+
+        let kinds = conflict_kinds(
+            "
+            x.load.σ32@0 <= int
+            x.load.σ32@4 <= int
+        ",
+        );
+        assert!(!kinds.contains(&ConflictKind::OverlappingFields));
+    }
+
+    #[test]
+    fn detects_nodes_lowered_as_unions() {
+        let kinds = conflict_kinds(
+            "
+            x.load.σ32@0 <= int
+            x.in_0 <= int
+        ",
+        );
+        assert!(kinds.contains(&ConflictKind::LoweredUnion));
+
+        let kinds = conflict_kinds(
+            "
+            x.in_0 <= int
+            x.in_1 <= int
+        ",
+        );
+        assert!(!kinds.contains(&ConflictKind::LoweredUnion));
+    }
+}
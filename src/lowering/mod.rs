@@ -1,8 +1,9 @@
-use cwe_checker_lib::intermediate_representation::{Arg, Tid};
+use cwe_checker_lib::intermediate_representation::{Arg, ByteSize, Tid};
 
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use crate::{
+    analysis::memory_returns::{MemoryReturn, MemoryReturnKind},
     constraints,
     ctypes::{self, CTypeMapping},
     solver::type_sketch::LatticeBounds,
@@ -15,25 +16,82 @@ use petgraph::{graph::NodeIndex, visit::EdgeRef, EdgeDirection};
 
 use crate::{
     constraints::FieldLabel,
-    solver::{type_lattice::NamedLatticeElement, type_sketch::SketchGraph},
+    solver::{
+        type_lattice::{NamedLatticeElement, CONST_STRING_TYPE},
+        type_sketch::SketchGraph,
+    },
 };
 
 use std::collections::BinaryHeap;
 use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Computes how much evidence supports each lowered type.
+pub mod confidence;
+
+/// Detects type conflicts that lowering would otherwise resolve silently.
+pub mod conflicts;
+
+/// Generates stable names for lowered types.
+pub mod naming;
 
 #[derive(Debug, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 /// A unique identifier for a type
 pub struct TypeId(usize);
 
+/// Primitives used to be lowered to only the name of their upper bound, so a bare name is still accepted.
+/// The lower bound was not recorded so it is left empty.
+fn deserialize_primitive<'de, D>(
+    deserializer: D,
+) -> Result<(String, String, String, bool), D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PrimitiveRepr {
+        Bounded {
+            type_constant: String,
+            lower_bound: String,
+            upper_bound: String,
+            #[serde(default)]
+            is_const: bool,
+        },
+        Name(String),
+    }
+
+    Ok(match PrimitiveRepr::deserialize(deserializer)? {
+        PrimitiveRepr::Bounded {
+            type_constant,
+            lower_bound,
+            upper_bound,
+            is_const,
+        } => (type_constant, lower_bound, upper_bound, is_const),
+        PrimitiveRepr::Name(upper) => (upper.clone(), String::new(), upper, false),
+    })
+}
+
 /// Representation of a automata type lowered to a ctype
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum CType {
     /// Primitive means the node has a primitive type associated with its label
-    Primitive(String),
+    #[serde(deserialize_with = "deserialize_primitive")]
+    Primitive {
+        /// The type constant selected by the [LoweringPolicy]
+        type_constant: String,
+        /// The lower bound on the node's type
+        lower_bound: String,
+        /// The upper bound on the node's type
+        upper_bound: String,
+        /// Only values in read-only memory flow into this primitive
+        is_const: bool,
+    },
     /// A pointer to another ctype
     Pointer {
         /// The target type
         target: TypeId,
+        /// The pointer is a parameter whose target is never stored to through it, including by callees the pointer is passed to
+        const_target: bool,
     },
     /// An alias to the type of a different node
     Alias(NodeIndex),
@@ -48,6 +106,84 @@ pub enum CType {
     },
     /// A union of several ctypes
     Union(BTreeSet<TypeId>),
+    /// An integral type that only takes on the constants it is compared against
+    Enum {
+        /// The primitive type of the enumerators
+        underlying_type: TypeId,
+        /// The constants the value was observed to be compared against
+        constants: BTreeSet<i64>,
+    },
+    /// Elements of the same type repeating at a fixed distance
+    Array {
+        /// The type of each element
+        element: TypeId,
+        /// The distance in bytes between the starts of consecutive elements
+        stride: usize,
+        /// The number of elements
+        count: usize,
+    },
+}
+
+/// The fewest distinct constants a value must be compared against to be lowered as an enum.
+/// A pair of constants is as often a range check or a sentinel as an enum.
+const MIN_ENUM_CONSTANTS: usize = 3;
+
+/// Selects which lattice bound of a node is used as its primitive type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoweringPolicy {
+    /// Use the upper bound, the weakest type the node is used as
+    Upper,
+    /// Use the lower bound, the strongest type flowing into the node
+    Lower,
+    /// Use the most specific bound that is neither top nor bottom. The lower bound is preferred if it is below the upper bound,
+    /// the upper bound is used for unsatisfiable bounds. If neither bound is informative top is preferred over bottom.
+    Tightest,
+}
+
+impl Default for LoweringPolicy {
+    fn default() -> Self {
+        LoweringPolicy::Upper
+    }
+}
+
+impl FromStr for LoweringPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upper" => Ok(LoweringPolicy::Upper),
+            "lower" => Ok(LoweringPolicy::Lower),
+            "tightest" => Ok(LoweringPolicy::Tightest),
+            _ => Err(anyhow::anyhow!("Unknown lowering policy: {}", s)),
+        }
+    }
+}
+
+impl LoweringPolicy {
+    /// Selects the lattice element that represents the given bounds under this policy.
+    pub fn select<'a, U: NamedLatticeElement>(&self, bounds: &'a LatticeBounds<U>) -> &'a U {
+        match self {
+            LoweringPolicy::Upper => bounds.get_upper(),
+            LoweringPolicy::Lower => bounds.get_lower(),
+            LoweringPolicy::Tightest => {
+                let lower = bounds.get_lower();
+                let upper = bounds.get_upper();
+                let is_informative = |elem: &U| !elem.is_top() && !elem.is_bot();
+                match (is_informative(lower), is_informative(upper)) {
+                    (true, true) if lower <= upper => lower,
+                    (_, true) => upper,
+                    (true, false) => lower,
+                    (false, false) => {
+                        if lower.is_top() {
+                            lower
+                        } else {
+                            upper
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -58,16 +194,24 @@ pub struct Parameter {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
-/// Represents a field with an offset and type.
+/// Represents a field with an offset and type. Bitfields start at a bit offset from their byte offset.
 pub struct Field {
     byte_offset: usize,
     bit_sz: usize,
     type_index: TypeId,
+    bit_offset: usize,
+}
+
+impl Field {
+    fn start_bit(&self) -> usize {
+        self.byte_offset * 8 + self.bit_offset
+    }
 }
 
 #[derive(PartialEq, Eq)]
 struct Classroom {
     scheduled: Vec<Field>,
+    // NOTE(Ian): offsets are tracked in bits so that bitfields sharing a byte can be scheduled together
     covering: BTreeMap<usize, usize>,
 }
 
@@ -80,11 +224,11 @@ impl Classroom {
     }
 
     fn compute_upper_bound_exclusive(base: usize, size: usize) -> usize {
-        base + size / 8
+        base + size
     }
 
     fn compute_fld_upper_bound_exlcusive(fld: &Field) -> usize {
-        Classroom::compute_upper_bound_exclusive(fld.byte_offset, fld.bit_sz)
+        Classroom::compute_upper_bound_exclusive(fld.start_bit(), fld.bit_sz)
     }
 
     fn get_next_scheduluable_offset(&self) -> usize {
@@ -97,22 +241,22 @@ impl Classroom {
     fn superscedes_fld(&self, fld: &Field) -> bool {
         let mut overlapping_flds = self
             .covering
-            .range(fld.byte_offset..Classroom::compute_fld_upper_bound_exlcusive(fld));
+            .range(fld.start_bit()..Classroom::compute_fld_upper_bound_exlcusive(fld));
 
         // we can assume if any fld compeltely contains this fld then this fld is already covered
         overlapping_flds.any(|(overlapping_base, overlapping_size)| {
-            *overlapping_base < fld.byte_offset
+            *overlapping_base < fld.start_bit()
                 && Self::compute_upper_bound_exclusive(*overlapping_base, *overlapping_size)
                     >= Self::compute_fld_upper_bound_exlcusive(fld)
         })
     }
 
     fn schedule_fld(&mut self, fld: Field) -> bool {
-        if self.get_next_scheduluable_offset() > fld.byte_offset {
+        if self.get_next_scheduluable_offset() > fld.start_bit() {
             return false;
         }
 
-        self.covering.insert(fld.byte_offset, fld.bit_sz);
+        self.covering.insert(fld.start_bit(), fld.bit_sz);
 
         self.scheduled.push(fld);
         true
@@ -135,10 +279,18 @@ impl PartialOrd for Classroom {
 }
 
 fn translate_field(field: &constraints::Field, idx: TypeId) -> Option<Field> {
+    // an array field covers every element
+    let bit_sz = match &field.array {
+        Some(array) => {
+            usize::try_from(array.stride.checked_mul(array.count)?.checked_mul(8)?).ok()?
+        }
+        None => field.size,
+    };
     usize::try_from(field.offset).ok().map(|off| Field {
         byte_offset: off,
-        bit_sz: field.size,
+        bit_sz,
         type_index: idx,
+        bit_offset: field.bit_offset,
     })
 }
 
@@ -151,7 +303,8 @@ fn schedule_structures(fields: &[Field]) -> Vec<CType> {
     // This is simply interval scheduling with one caveat. If a time block overlaps but is completely contained within the ending field we can just ignore
     // That field
     let mut sorted_fields = fields.to_vec();
-    sorted_fields.sort_by_key(|x| x.byte_offset);
+    // break ties between fields at the same offset by size so that rooms are filled the same way regardless of edge order
+    sorted_fields.sort_by_key(|fld| (fld.start_bit(), fld.bit_sz));
 
     let mut hp: BinaryHeap<Classroom> = BinaryHeap::new();
 
@@ -189,7 +342,7 @@ fn has_non_zero_fields<U: NamedLatticeElement>(
         .edges_directed(nd, EdgeDirection::Outgoing)
         .any(|e| {
             if let FieldLabel::Field(fld) = e.weight() {
-                fld.offset != 0
+                fld.offset != 0 || fld.is_bitfield() || fld.array.is_some()
             } else {
                 false
             }
@@ -215,11 +368,202 @@ fn build_alias_types<U: NamedLatticeElement>(
     unique_tgts.into_iter().map(CType::Alias).collect()
 }
 
+/// The type ids a ctype refers to, in the order they appear in the type. An alias refers to the type of its node.
+fn referenced_types(ty: &CType, node_to_ty: &HashMap<NodeIndex, TypeId>) -> Vec<TypeId> {
+    match ty {
+        CType::Primitive { .. } => Vec::new(),
+        CType::Pointer { target, .. } => vec![*target],
+        CType::Alias(nd) => node_to_ty.get(nd).copied().into_iter().collect(),
+        CType::Structure(flds) => flds.iter().map(|fld| fld.type_index).collect(),
+        CType::Function { params, return_ty } => params
+            .iter()
+            .map(|param| param.type_index)
+            .chain(return_ty.iter().copied())
+            .collect(),
+        CType::Union(children) => children.iter().copied().collect(),
+        CType::Enum {
+            underlying_type, ..
+        } => vec![*underlying_type],
+        CType::Array { element, .. } => vec![*element],
+    }
+}
+
+/// Replaces every type id a ctype refers to with its image under `f`. Aliases refer to nodes and are left as is.
+fn map_referenced_types(ty: &CType, f: &impl Fn(TypeId) -> TypeId) -> CType {
+    match ty {
+        CType::Primitive { .. } | CType::Alias(_) => ty.clone(),
+        CType::Pointer {
+            target,
+            const_target,
+        } => CType::Pointer {
+            target: f(*target),
+            const_target: *const_target,
+        },
+        CType::Structure(flds) => CType::Structure(
+            flds.iter()
+                .map(|fld| Field {
+                    type_index: f(fld.type_index),
+                    ..fld.clone()
+                })
+                .collect(),
+        ),
+        CType::Function { params, return_ty } => CType::Function {
+            params: params
+                .iter()
+                .map(|param| Parameter {
+                    index: param.index,
+                    type_index: f(param.type_index),
+                })
+                .collect(),
+            return_ty: return_ty.map(f),
+        },
+        CType::Union(children) => CType::Union(children.iter().map(|x| f(*x)).collect()),
+        CType::Enum {
+            underlying_type,
+            constants,
+        } => CType::Enum {
+            underlying_type: f(*underlying_type),
+            constants: constants.clone(),
+        },
+        CType::Array {
+            element,
+            stride,
+            count,
+        } => CType::Array {
+            element: f(*element),
+            stride: *stride,
+            count: *count,
+        },
+    }
+}
+
+/// The shape of a ctype with everything it refers to erased, so that only the local content of the type is compared.
+fn type_shape(ty: &CType) -> CType {
+    match ty {
+        CType::Alias(_) => CType::Alias(NodeIndex::new(0)),
+        _ => map_referenced_types(ty, &|_| TypeId(0)),
+    }
+}
+
+/// Assigns canonical type ids and merges structurally identical types.
+/// Types are partitioned by their shape and the partition is refined by the classes of referenced types until it is stable,
+/// which merges isomorphic types, including recursive ones. Each class is numbered by its rank among the sorted signatures of the final round,
+/// so type ids only depend on the content of the types and not on node indices or the order types were built in.
+pub fn canonicalize_types(
+    node_to_ty: &HashMap<NodeIndex, TypeId>,
+    types: &BTreeMap<TypeId, CType>,
+) -> (HashMap<NodeIndex, TypeId>, BTreeMap<TypeId, CType>) {
+    let rank = |sigs: &BTreeMap<TypeId, (usize, Vec<usize>)>| -> BTreeMap<TypeId, usize> {
+        let ordered = sigs.values().collect::<BTreeSet<_>>();
+        let ranks = ordered
+            .into_iter()
+            .enumerate()
+            .map(|(rank, sig)| (sig, rank))
+            .collect::<BTreeMap<_, _>>();
+        sigs.iter().map(|(id, sig)| (*id, ranks[sig])).collect()
+    };
+
+    let shapes = types
+        .values()
+        .map(type_shape)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .enumerate()
+        .map(|(rank, shape)| (shape, rank))
+        .collect::<BTreeMap<_, _>>();
+    let mut classes = types
+        .iter()
+        .map(|(id, ty)| (*id, shapes[&type_shape(ty)]))
+        .collect::<BTreeMap<_, _>>();
+    let mut num_classes = shapes.len();
+
+    loop {
+        let sigs = types
+            .iter()
+            .map(|(id, ty)| {
+                let mut referenced = referenced_types(ty, node_to_ty)
+                    .into_iter()
+                    .filter_map(|referenced| classes.get(&referenced).copied())
+                    .collect::<Vec<_>>();
+                // union members are a set so their order and multiplicity do not matter
+                if let CType::Union(_) = ty {
+                    referenced.sort_unstable();
+                    referenced.dedup();
+                }
+                (*id, (classes[id], referenced))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        classes = rank(&sigs);
+        let refined_classes = classes.values().collect::<BTreeSet<_>>().len();
+        // every round refines the previous partition, so the partition is stable once no class splits
+        if refined_classes == num_classes {
+            break;
+        }
+        num_classes = refined_classes;
+    }
+
+    let canonical_id = |id: TypeId| TypeId(classes[&id]);
+    let mut canonical_types = BTreeMap::new();
+    for (id, ty) in types.iter() {
+        canonical_types
+            .entry(canonical_id(*id))
+            .or_insert_with(|| map_referenced_types(ty, &canonical_id));
+    }
+
+    let canonical_nodes = node_to_ty
+        .iter()
+        .map(|(nd, id)| (*nd, canonical_id(*id)))
+        .collect();
+
+    (canonical_nodes, canonical_types)
+}
+
+/// Counts the types [LoweringContext] builds for a node, following the same rules as its type builders.
+/// A node with more than one alternative is lowered as a union.
+pub(crate) fn count_lowered_alternatives<U: NamedLatticeElement>(
+    nd: NodeIndex,
+    grph: &SketchGraph<LatticeBounds<U>>,
+) -> usize {
+    let edges = || {
+        grph.get_graph()
+            .get_graph()
+            .edges_directed(nd, EdgeDirection::Outgoing)
+    };
+
+    let structures = if has_non_zero_fields(nd, grph) {
+        schedule_structures(
+            &edges()
+                .filter_map(|e| {
+                    if let FieldLabel::Field(fld) = e.weight() {
+                        translate_field(fld, TypeId(0))
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+        .len()
+    } else {
+        0
+    };
+    let pointers = edges()
+        .filter(|e| matches!(e.weight(), FieldLabel::Load | FieldLabel::Store))
+        .map(|e| e.target())
+        .collect::<BTreeSet<_>>()
+        .len();
+    let functions =
+        usize::from(edges().any(|e| matches!(e.weight(), FieldLabel::In(_) | FieldLabel::Out(_))));
+
+    structures + build_alias_types(nd, grph).len() + pointers + functions
+}
+
 fn field_to_protobuf(internal_field: Field) -> ctypes::Field {
     ctypes::Field {
         bit_size: internal_field.bit_sz.try_into().unwrap(),
         byte_offset: internal_field.byte_offset.try_into().unwrap(),
         type_id: Some(convert_typeid(internal_field.type_index)),
+        bit_offset: internal_field.bit_offset.try_into().unwrap(),
     }
 }
 
@@ -261,12 +605,24 @@ pub fn produce_inner_types(
 
             ctypes::c_type::InnerType::Function(func)
         }
-        CType::Pointer { target } => ctypes::c_type::InnerType::Pointer(ctypes::Pointer {
+        CType::Pointer {
+            target,
+            const_target,
+        } => ctypes::c_type::InnerType::Pointer(ctypes::Pointer {
             to_type_id: Some(convert_typeid(target)),
+            const_target,
+        }),
+        CType::Primitive {
+            type_constant,
+            lower_bound,
+            upper_bound,
+            is_const,
+        } => ctypes::c_type::InnerType::Primitive(ctypes::Primitive {
+            type_constant,
+            lower_bound,
+            upper_bound,
+            is_const,
         }),
-        CType::Primitive(val) => {
-            ctypes::c_type::InnerType::Primitive(ctypes::Primitive { type_constant: val })
-        }
         CType::Structure(fields) => {
             let mut st = ctypes::Structure::default();
             fields
@@ -283,6 +639,22 @@ pub fn produce_inner_types(
 
             ctypes::c_type::InnerType::Union(union)
         }
+        CType::Enum {
+            underlying_type,
+            constants,
+        } => ctypes::c_type::InnerType::Enumeration(ctypes::Enum {
+            underlying_type: Some(convert_typeid(underlying_type)),
+            constants: constants.into_iter().collect(),
+        }),
+        CType::Array {
+            element,
+            stride,
+            count,
+        } => ctypes::c_type::InnerType::Array(ctypes::Array {
+            element_type: Some(convert_typeid(element)),
+            stride: stride.try_into().unwrap(),
+            count: count.try_into().unwrap(),
+        }),
     }
 }
 
@@ -292,7 +664,10 @@ pub fn convert_mapping_to_profobuf(
     mp: BTreeMap<TypeId, CType>,
     node_to_ty: &HashMap<NodeIndex, TypeId>,
 ) -> CTypeMapping {
-    let mut mapping = CTypeMapping::default();
+    let mut mapping = CTypeMapping {
+        format_version: Some(crate::interchange::format_version_message()),
+        ..Default::default()
+    };
 
     mp.into_iter().for_each(|(idx, ctype)| {
         let ctype = produce_inner_types(ctype, node_to_ty);
@@ -301,6 +676,7 @@ pub fn convert_mapping_to_profobuf(
             ctypes::CType {
                 type_id: Some(convert_typeid(idx)),
                 inner_type: Some(ctype),
+                name: String::new(),
             },
         );
     });
@@ -308,16 +684,56 @@ pub fn convert_mapping_to_profobuf(
     mapping
 }
 
+/// Adds the type of each interesting tid to the protobuf mapping.
+pub fn add_tid_types_to_protobuf(
+    mapping: &mut CTypeMapping,
+    tid_to_node: &BTreeMap<Tid, NodeIndex>,
+    node_to_ty: &HashMap<NodeIndex, TypeId>,
+) {
+    for (tid, idx) in tid_to_node.iter() {
+        if let Some(ty) = node_to_ty.get(idx) {
+            mapping.type_variable_repr_nodes.push(ctypes::TidToTypeId {
+                tid: Some(ctypes::Tid {
+                    name: tid.get_str_repr().to_owned(),
+                    address: tid.address.clone(),
+                }),
+                type_id: Some(convert_typeid(*ty)),
+            });
+        }
+    }
+}
+
+/// Adds the type instantiated at each callsite to the protobuf mapping, keyed by the tid of the call.
+pub fn add_callsite_types_to_protobuf(
+    mapping: &mut CTypeMapping,
+    callsite_to_node: &BTreeMap<Tid, NodeIndex>,
+    node_to_ty: &HashMap<NodeIndex, TypeId>,
+) {
+    for (call, idx) in callsite_to_node.iter() {
+        if let Some(ty) = node_to_ty.get(idx) {
+            mapping.callsite_types.push(ctypes::TidToTypeId {
+                tid: Some(ctypes::Tid {
+                    name: call.get_str_repr().to_owned(),
+                    address: call.address.clone(),
+                }),
+                type_id: Some(convert_typeid(*ty)),
+            });
+        }
+    }
+}
+
 /// The context needed to attempt to lower a node to a ctype.
-/// The heuristics need to know the original outparam locations for
+/// The heuristics need to know the original outparam locations and memory returns for
 /// subprocedure nodes, and a default lattice element to use for unknown types.
 pub struct LoweringContext<'a, U: NamedLatticeElement> {
     grph: &'a SketchGraph<LatticeBounds<U>>,
     out_params: BTreeMap<NodeIndex, Vec<Arg>>,
+    memory_returns: BTreeMap<NodeIndex, Vec<MemoryReturn>>,
     default_lattice_elem: LatticeBounds<U>,
     ephemeral_types: BTreeMap<TypeId, CType>,
-    cached_primitivies: BTreeMap<String, TypeId>,
+    interned_types: BTreeMap<CType, TypeId>,
     curr_id: usize,
+    policy: LoweringPolicy,
 }
 
 impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
@@ -338,7 +754,15 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
                 .edges_directed(nd, EdgeDirection::Outgoing)
                 .filter_map(|e| {
                     if let constraints::FieldLabel::Field(fld) = e.weight() {
-                        translate_field(fld, self.add_type(CType::Alias(e.target())))
+                        let mut idx = self.add_type(CType::Alias(e.target()));
+                        if let Some(array) = &fld.array {
+                            idx = self.add_type(CType::Array {
+                                element: idx,
+                                stride: usize::try_from(array.stride).ok()?,
+                                count: usize::try_from(array.count).ok()?,
+                            });
+                        }
+                        translate_field(fld, idx)
                     } else {
                         None
                     }
@@ -348,16 +772,13 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
     }
 
     fn build_terminal_type(&mut self, nd_bounds: &LatticeBounds<U>) -> TypeId {
-        // TODO(Ian): be more clever
-        let nm = nd_bounds.get_upper().get_name();
-        if let Some(id) = self.cached_primitivies.get(nm) {
-            return *id;
-        }
-
-        let ty = CType::Primitive(nm.to_owned());
-        let res = self.add_type(ty);
-        self.cached_primitivies.insert(nm.to_owned(), res);
-        res
+        self.add_type(CType::Primitive {
+            type_constant: self.policy.select(nd_bounds).get_name().to_owned(),
+            lower_bound: nd_bounds.get_lower().get_name().to_owned(),
+            upper_bound: nd_bounds.get_upper().get_name().to_owned(),
+            // NOTE(Ian): the characters of string literals are the only values known to be read-only
+            is_const: nd_bounds.get_upper().get_name() == CONST_STRING_TYPE,
+        })
     }
 
     fn build_pointer_types(
@@ -365,31 +786,49 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
         nd: NodeIndex,
         grph: &SketchGraph<LatticeBounds<U>>,
     ) -> Vec<CType> {
-        let load_or_store_targets = grph
+        let load_or_store_edges = grph
             .get_graph()
             .get_graph()
             .edges_directed(nd, EdgeDirection::Outgoing)
             .filter(|e| {
                 matches!(e.weight(), FieldLabel::Load) || matches!(e.weight(), FieldLabel::Store)
             })
-            .map(|e| e.target())
-            .collect::<BTreeSet<_>>();
+            .collect::<Vec<_>>();
 
-        load_or_store_targets
+        // Stores by callees are part of the parameter's sketch, so a pointer without a store capability is never written through.
+        // NOTE(Ian): other pointers only see the accesses of the code that constrained them, so only parameters are marked const
+        let is_parameter = grph
+            .get_graph()
+            .get_graph()
+            .edges_directed(nd, EdgeDirection::Incoming)
+            .any(|e| matches!(e.weight(), FieldLabel::In(_)));
+        let const_target = is_parameter
+            && !load_or_store_edges
+                .iter()
+                .any(|e| matches!(e.weight(), FieldLabel::Store));
+
+        load_or_store_edges
+            .into_iter()
+            .map(|e| e.target())
+            .collect::<BTreeSet<_>>()
             .into_iter()
             .map(|tgt| CType::Pointer {
                 target: self.add_type(CType::Alias(tgt)),
+                const_target,
             })
             .collect()
     }
 
     /// Creates a new type lowering context from a mapping from term to node,
-    /// a mapping from subprocedure term to out parameters and a defualt lattice element.
+    /// a mapping from subprocedure term to out parameters, a mapping from subprocedure term to the results it returns through memory,
+    /// a defualt lattice element, and the policy for selecting primitive types.
     pub fn new<'b>(
         grph: &'b SketchGraph<LatticeBounds<U>>,
-        tid_to_node_index: &HashMap<Tid, NodeIndex>,
+        tid_to_node_index: &BTreeMap<Tid, NodeIndex>,
         out_param_mapping: &HashMap<Tid, Vec<Arg>>,
+        memory_return_mapping: &HashMap<Tid, Vec<MemoryReturn>>,
         default_lattice_elem: LatticeBounds<U>,
+        policy: LoweringPolicy,
     ) -> LoweringContext<'b, U> {
         LoweringContext {
             grph,
@@ -397,9 +836,13 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
                 .iter()
                 .filter_map(|(k, v)| tid_to_node_index.get(k).map(|nd_idx| (*nd_idx, v.clone())))
                 .collect(),
+            memory_returns: memory_return_mapping
+                .iter()
+                .filter_map(|(k, v)| tid_to_node_index.get(k).map(|nd_idx| (*nd_idx, v.clone())))
+                .collect(),
             default_lattice_elem,
             ephemeral_types: BTreeMap::new(),
-            cached_primitivies: BTreeMap::new(),
+            interned_types: BTreeMap::new(),
             curr_id: grph
                 .get_graph()
                 .get_graph()
@@ -408,13 +851,42 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
                 .max()
                 .unwrap_or(0)
                 + 1,
+            policy,
+        }
+    }
+
+    /// Lowers nodes that instantiate a subprocedure's type, such as callsite types, with the out parameters and memory returns of that subprocedure.
+    pub fn add_instantiated_nodes(
+        &mut self,
+        node_to_instantiated_tid: &HashMap<NodeIndex, Tid>,
+        out_param_mapping: &HashMap<Tid, Vec<Arg>>,
+        memory_return_mapping: &HashMap<Tid, Vec<MemoryReturn>>,
+    ) {
+        for (nd_idx, tid) in node_to_instantiated_tid.iter() {
+            if let Some(params) = out_param_mapping.get(tid) {
+                self.out_params
+                    .entry(*nd_idx)
+                    .or_insert_with(|| params.clone());
+            }
+
+            if let Some(mem_rets) = memory_return_mapping.get(tid) {
+                self.memory_returns
+                    .entry(*nd_idx)
+                    .or_insert_with(|| mem_rets.clone());
+            }
         }
     }
 
+    /// Hash conses types so that identical types, such as aliases to the same node, share a type id.
     fn add_type(&mut self, ty: CType) -> TypeId {
+        if let Some(id) = self.interned_types.get(&ty) {
+            return *id;
+        }
+
         let id = self.curr_id;
         self.curr_id += 1;
         let ty_id = TypeId(id);
+        self.interned_types.insert(ty.clone(), ty_id);
         self.ephemeral_types.insert(ty_id, ty);
         ty_id
     }
@@ -468,7 +940,7 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
     fn build_return_type_structure(
         &mut self,
         _idx: NodeIndex,
-        orig_param_locs: &[Arg],
+        orig_param_sizes: &[ByteSize],
         params: &[Parameter],
         default_lattice_elem: &LatticeBounds<U>,
     ) -> CType {
@@ -479,17 +951,18 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
 
         let mut flds = Vec::new();
         let mut curr_off = 0;
-        for (i, arg) in orig_param_locs.iter().enumerate() {
+        for (i, sz) in orig_param_sizes.iter().enumerate() {
             flds.push(Field {
                 byte_offset: curr_off,
-                bit_sz: arg.bytesize().as_bit_length(),
+                bit_sz: sz.as_bit_length(),
                 type_index: mp
                     .get(&i)
                     .map(|x| x.type_index)
                     .unwrap_or_else(|| self.build_terminal_type(default_lattice_elem)),
+                bit_offset: 0,
             });
             // TODO(Ian) doesnt seem like there is a non bit length accessor on the private field?
-            curr_off += arg.bytesize().as_bit_length() / 8;
+            curr_off += sz.as_bit_length() / 8;
         }
 
         CType::Structure(flds)
@@ -502,7 +975,7 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
         grph: &SketchGraph<LatticeBounds<U>>,
     ) -> Vec<CType> {
         // index to vector of targets
-        let in_params = self.collect_params(nd, grph, &|lbl| {
+        let mut in_params = self.collect_params(nd, grph, &|lbl| {
             if let FieldLabel::In(idx) = lbl {
                 Some(*idx)
             } else {
@@ -521,13 +994,37 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
         out_params.sort_by_key(|p| p.index);
         let def = vec![];
         let curr_orig_params = self.out_params.get(&nd).unwrap_or(&def);
-        // has multiple out params need to pad
-        let oparam = if out_params.len() > 1 || curr_orig_params.len() > 1 {
+        let mem_rets = self.memory_returns.get(&nd).cloned().unwrap_or_default();
+
+        // memory returns are numbered after the register returns
+        let sret = mem_rets
+            .iter()
+            .enumerate()
+            .find(|(_, mret)| mret.kind == MemoryReturnKind::StructReturn)
+            .and_then(|(k, mret)| {
+                out_params
+                    .iter()
+                    .find(|p| p.index == curr_orig_params.len() + k)
+                    .map(|p| (p.type_index, mret.param_index))
+            });
+
+        let mut orig_sizes = curr_orig_params
+            .iter()
+            .map(|arg| arg.bytesize())
+            .collect::<Vec<_>>();
+        orig_sizes.extend(mem_rets.iter().map(|mret| mret.size));
+
+        let oparam = if let Some((struct_ty, hidden_param)) = sret {
+            // the struct is the return value, the hidden pointer to it is not a parameter
+            log::info!("Creating struct return type");
+            in_params.retain(|p| p.index != hidden_param);
+            Some(struct_ty)
+        } else if out_params.len() > 1 || orig_sizes.len() > 1 {
+            // has multiple out params need to pad
             log::info!("Creating multifield return type");
-            let args = self.out_params.get(&nd).unwrap_or(&def).clone();
             let ret_struct = self.build_return_type_structure(
                 nd,
-                &args,
+                &orig_sizes,
                 &out_params,
                 &self.default_lattice_elem.clone(),
             );
@@ -554,7 +1051,16 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
             .count()
             == 0
         {
-            return self.build_terminal_type(&act_graph[nd]);
+            let prim = self.build_terminal_type(&act_graph[nd]);
+            let constants = act_graph[nd].get_constants();
+            return if constants.len() >= MIN_ENUM_CONSTANTS {
+                self.add_type(CType::Enum {
+                    underlying_type: prim,
+                    constants: constants.clone(),
+                })
+            } else {
+                prim
+            };
         }
 
         let struct_types = self.build_structure_types(nd, grph);
@@ -594,6 +1100,517 @@ impl<'a, U: NamedLatticeElement> LoweringContext<'a, U> {
             types.insert(nd, self.build_type(nd, self.grph));
         }
 
-        Ok((types, self.ephemeral_types))
+        // the build order does decide type ids, so renumber them canonically
+        Ok(canonicalize_types(&types, &self.ephemeral_types))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    use cwe_checker_lib::intermediate_representation::{ByteSize, Tid};
+
+    use crate::{
+        analysis::memory_returns::{MemoryReturn, MemoryReturnKind},
+        constraint_generation::comparison_tvar,
+        constraints::{DerivedTypeVar, Field, FieldLabel, SubtypeConstraint, TypeVariable},
+        graph_algos::find_node,
+        solver::{
+            type_lattice::{NamedLattice, NamedLatticeElement, CONST_STRING_TYPE, STRING_TYPE},
+            type_sketch::{identity_element, LatticeBounds},
+        },
+        test_utils::{register_arg, sketch_for, test_lattice},
+    };
+
+    use petgraph::graph::NodeIndex;
+
+    use super::{
+        canonicalize_types, schedule_structures, CType, Field as CField, LoweringContext,
+        LoweringPolicy, TypeId,
+    };
+
+    fn dtv(base: &str, lbls: Vec<FieldLabel>) -> DerivedTypeVar {
+        let mut dtv = DerivedTypeVar::new(TypeVariable::new(base.to_owned()));
+        lbls.into_iter().for_each(|lbl| dtv.add_field_label(lbl));
+        dtv
+    }
+
+    #[test]
+    fn struct_return_replaces_hidden_parameter() {
+        let int = || dtv("int", vec![]);
+        let cons = vec![
+            SubtypeConstraint::new(dtv("f", vec![FieldLabel::In(1)]), int()),
+            SubtypeConstraint::new(dtv("f", vec![FieldLabel::In(0)]), dtv("p", vec![])),
+            SubtypeConstraint::new(dtv("f", vec![FieldLabel::Out(0)]), dtv("p", vec![])),
+            SubtypeConstraint::new(
+                dtv(
+                    "f",
+                    vec![FieldLabel::Out(1), FieldLabel::Field(Field::new(0, 64))],
+                ),
+                int(),
+            ),
+            SubtypeConstraint::new(
+                dtv(
+                    "f",
+                    vec![FieldLabel::Out(1), FieldLabel::Field(Field::new(8, 32))],
+                ),
+                int(),
+            ),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+
+        let (lattice, sg) = sketch_for(
+            &cons,
+            test_lattice(
+                &[("int", "weakint"), ("weakint", "top"), ("bottom", "int")],
+                "weakint",
+            ),
+        );
+
+        let f_tid = Tid::create("f".to_owned(), "0".to_owned());
+        let f_idx = sg
+            .get_node_index_for_variable(&dtv("f", vec![]))
+            .expect("f should have a node");
+        let ret_reg = register_arg("RAX", 8);
+
+        let (node_to_ty, types) = LoweringContext::new(
+            &sg,
+            &vec![(f_tid.clone(), f_idx)].into_iter().collect(),
+            &vec![(f_tid.clone(), vec![ret_reg])].into_iter().collect(),
+            &vec![(
+                f_tid,
+                vec![MemoryReturn {
+                    kind: MemoryReturnKind::StructReturn,
+                    param_index: 0,
+                    size: ByteSize::new(12),
+                }],
+            )]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes()
+        .expect("should lower types");
+
+        let (params, return_ty) = match &types[&node_to_ty[&f_idx]] {
+            CType::Function { params, return_ty } => (params, return_ty),
+            other => panic!("expected a function type, got {:?}", other),
+        };
+
+        assert!(params.iter().all(|p| p.index != 0));
+        assert!(params.iter().any(|p| p.index == 1));
+
+        let ret_node = match &types[&return_ty.expect("should have a return type")] {
+            CType::Alias(tgt) => *tgt,
+            other => panic!("expected an alias to the returned struct, got {:?}", other),
+        };
+        assert!(matches!(
+            &types[&node_to_ty[&ret_node]],
+            CType::Structure(flds) if flds.len() == 2
+        ));
+    }
+
+    #[test]
+    fn values_compared_against_constants_are_enums() {
+        let cmp_tid =
+            |idx: usize| Tid::create(format!("instr_00001000_{}", idx), "00001000".to_owned());
+        let cmp = |idx| DerivedTypeVar::new(comparison_tvar(&cmp_tid(idx)));
+        let cons = vec![
+            SubtypeConstraint::new(dtv("x", vec![]), cmp(0)),
+            SubtypeConstraint::new(dtv("x", vec![]), cmp(1)),
+            SubtypeConstraint::new(dtv("y", vec![]), cmp(2)),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+        let constants = vec![
+            (comparison_tvar(&cmp_tid(0)), vec![3, 12]),
+            (comparison_tvar(&cmp_tid(1)), vec![-7]),
+            (comparison_tvar(&cmp_tid(2)), vec![1, 2]),
+        ]
+        .into_iter()
+        .map(|(tv, cs)| (tv, cs.into_iter().collect::<BTreeSet<_>>()))
+        .collect::<BTreeMap<_, _>>();
+
+        let (lattice, mut sg) = sketch_for(
+            &cons,
+            test_lattice(&[("int", "top"), ("bottom", "int")], "int"),
+        );
+        sg.label_comparisons(&constants);
+
+        let (node_to_ty, types) = LoweringContext::new(
+            &sg,
+            &BTreeMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes()
+        .expect("should lower types");
+
+        let x_idx = sg
+            .get_node_index_for_variable(&dtv("x", vec![]))
+            .expect("x should have a node");
+        assert!(matches!(
+            &types[&node_to_ty[&x_idx]],
+            CType::Enum { constants, underlying_type }
+                if constants == &vec![-7, 3, 12].into_iter().collect::<BTreeSet<_>>()
+                    && matches!(&types[underlying_type], CType::Primitive { .. })
+        ));
+
+        // two constants are not enough evidence
+        let y_idx = sg
+            .get_node_index_for_variable(&dtv("y", vec![]))
+            .expect("y should have a node");
+        assert!(matches!(
+            &types[&node_to_ty[&y_idx]],
+            CType::Primitive { .. }
+        ));
+    }
+
+    #[test]
+    fn pointers_only_loaded_through_have_const_targets() {
+        let int = || dtv("int", vec![]);
+        let fld = || FieldLabel::Field(Field::new(0, 32));
+        let cons = vec![
+            SubtypeConstraint::new(
+                dtv("f", vec![FieldLabel::In(0), FieldLabel::Load, fld()]),
+                int(),
+            ),
+            SubtypeConstraint::new(
+                int(),
+                dtv("f", vec![FieldLabel::In(1), FieldLabel::Store, fld()]),
+            ),
+            // f passes its third parameter to g which passes it to h, only h stores through it
+            SubtypeConstraint::new(
+                dtv("f", vec![FieldLabel::In(2)]),
+                dtv("g", vec![FieldLabel::In(0)]),
+            ),
+            SubtypeConstraint::new(
+                dtv("g", vec![FieldLabel::In(0)]),
+                dtv("h", vec![FieldLabel::In(0)]),
+            ),
+            SubtypeConstraint::new(
+                int(),
+                dtv("h", vec![FieldLabel::In(0), FieldLabel::Store, fld()]),
+            ),
+            SubtypeConstraint::new(
+                dtv("f", vec![FieldLabel::Out(0), FieldLabel::Load, fld()]),
+                int(),
+            ),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+
+        let (lattice, sg) = sketch_for(
+            &cons,
+            test_lattice(&[("int", "top"), ("bottom", "int")], "int"),
+        );
+
+        let (node_to_ty, types) = LoweringContext::new(
+            &sg,
+            &BTreeMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes()
+        .expect("should lower types");
+
+        let f_idx = sg
+            .get_node_index_for_variable(&dtv("f", vec![]))
+            .expect("f should have a node");
+        let is_const = |lbl| {
+            let nd = find_node(sg.get_graph().get_graph(), f_idx, vec![lbl].iter())
+                .expect("pointer should have a node");
+            match &types[&node_to_ty[&nd]] {
+                CType::Pointer { const_target, .. } => *const_target,
+                other => panic!("expected a pointer, got {:?}", other),
+            }
+        };
+
+        assert!(is_const(FieldLabel::In(0)));
+        assert!(!is_const(FieldLabel::In(1)));
+        assert!(!is_const(FieldLabel::In(2)));
+        // only parameters have const targets
+        assert!(!is_const(FieldLabel::Out(0)));
+    }
+
+    #[test]
+    fn read_only_string_characters_are_const() {
+        let chr = |base| {
+            dtv(
+                base,
+                vec![FieldLabel::Load, FieldLabel::Field(Field::new(0, 8))],
+            )
+        };
+        let cons = vec![
+            SubtypeConstraint::new(chr("literal"), dtv(CONST_STRING_TYPE, vec![])),
+            SubtypeConstraint::new(chr("buffer"), dtv(STRING_TYPE, vec![])),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+
+        let def = test_lattice(&[("int", "top"), ("bottom", "int")], "int");
+        let string_types = def.string_types();
+        let (lattice, sg) = sketch_for(
+            &cons,
+            def.merge_with_other(string_types)
+                .expect("should merge string types"),
+        );
+
+        let (node_to_ty, types) = LoweringContext::new(
+            &sg,
+            &BTreeMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes()
+        .expect("should lower types");
+
+        let character_is_const = |base| {
+            let ptr = sg
+                .get_node_index_for_variable(&dtv(base, vec![]))
+                .expect("pointer should have a node");
+            let nd = find_node(
+                sg.get_graph().get_graph(),
+                ptr,
+                vec![FieldLabel::Load, FieldLabel::Field(Field::new(0, 8))].iter(),
+            )
+            .expect("character should have a node");
+            match &types[&node_to_ty[&nd]] {
+                CType::Primitive { is_const, .. } => *is_const,
+                other => panic!("expected a primitive, got {:?}", other),
+            }
+        };
+
+        assert!(character_is_const("literal"));
+        assert!(!character_is_const("buffer"));
+    }
+
+    #[test]
+    fn strided_fields_are_arrays() {
+        let elements = FieldLabel::Field(Field::new_array(8, 32, 16, 3).unwrap());
+        let cons = vec![SubtypeConstraint::new(
+            dtv("buf", vec![FieldLabel::Load, elements.clone()]),
+            dtv("int", vec![]),
+        )]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+
+        let (lattice, sg) = sketch_for(
+            &cons,
+            test_lattice(&[("int", "top"), ("bottom", "int")], "int"),
+        );
+
+        let (node_to_ty, types) = LoweringContext::new(
+            &sg,
+            &BTreeMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            identity_element(&lattice),
+            LoweringPolicy::default(),
+        )
+        .collect_ctypes()
+        .expect("should lower types");
+
+        let buf = sg
+            .get_node_index_for_variable(&dtv("buf", vec![]))
+            .expect("buf should have a node");
+        let target = find_node(
+            sg.get_graph().get_graph(),
+            buf,
+            vec![FieldLabel::Load].iter(),
+        )
+        .expect("buf should be loaded from");
+        let flds = match &types[&node_to_ty[&target]] {
+            CType::Structure(flds) => flds,
+            other => panic!("expected a structure, got {:?}", other),
+        };
+        assert_eq!(flds.len(), 1);
+        assert_eq!(flds[0].byte_offset, 8);
+        assert_eq!(flds[0].bit_sz, 16 * 3 * 8);
+        match &types[&flds[0].type_index] {
+            CType::Array {
+                element,
+                stride,
+                count,
+            } => {
+                assert_eq!((*stride, *count), (16, 3));
+                assert!(matches!(
+                    &types[element],
+                    CType::Alias(_) | CType::Primitive { .. }
+                ));
+            }
+            other => panic!("expected an array, got {:?}", other),
+        }
+    }
+
+    fn cfield(byte_offset: usize, bit_offset: usize, bit_sz: usize) -> CField {
+        CField {
+            byte_offset,
+            bit_sz,
+            type_index: TypeId(0),
+            bit_offset,
+        }
+    }
+
+    #[test]
+    fn bitfields_in_one_byte_share_a_structure() {
+        let structs = schedule_structures(&[
+            cfield(0, 3, 3),
+            cfield(0, 0, 3),
+            cfield(0, 6, 2),
+            cfield(1, 0, 8),
+        ]);
+        assert_eq!(
+            structs,
+            vec![CType::Structure(vec![
+                cfield(0, 0, 3),
+                cfield(0, 3, 3),
+                cfield(0, 6, 2),
+                cfield(1, 0, 8)
+            ])]
+        );
+
+        // Overlapping bitfields still need a union of structures
+        assert_eq!(
+            schedule_structures(&[cfield(0, 0, 4), cfield(0, 2, 4)]).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn lowering_policies_select_bounds() {
+        let def = test_lattice(
+            &[("int", "weakint"), ("weakint", "top"), ("bottom", "int")],
+            "weakint",
+        );
+        let lattice = def.generate_lattice();
+        let elem = |nm: &str| lattice.get_elem(nm).expect("should be part of lattice");
+        let select = |policy: LoweringPolicy, lower: &str, upper: &str| {
+            policy
+                .select(&LatticeBounds::new(elem(lower), elem(upper)))
+                .get_name()
+                .to_owned()
+        };
+
+        assert_eq!(select(LoweringPolicy::Upper, "int", "weakint"), "weakint");
+        assert_eq!(select(LoweringPolicy::Upper, "int", "top"), "top");
+        assert_eq!(select(LoweringPolicy::Lower, "int", "weakint"), "int");
+        assert_eq!(select(LoweringPolicy::Lower, "bottom", "weakint"), "bottom");
+
+        // the lower bound is more specific when the bounds are consistent
+        assert_eq!(select(LoweringPolicy::Tightest, "int", "weakint"), "int");
+        assert_eq!(select(LoweringPolicy::Tightest, "int", "top"), "int");
+        assert_eq!(
+            select(LoweringPolicy::Tightest, "bottom", "weakint"),
+            "weakint"
+        );
+        // unsatisfiable bounds use the type the node is used as
+        assert_eq!(select(LoweringPolicy::Tightest, "weakint", "int"), "int");
+        // without an informative bound top is preferred over bottom
+        assert_eq!(select(LoweringPolicy::Tightest, "bottom", "top"), "top");
+        assert_eq!(select(LoweringPolicy::Tightest, "top", "bottom"), "top");
+        assert_eq!(
+            select(LoweringPolicy::Tightest, "bottom", "bottom"),
+            "bottom"
+        );
+    }
+
+    #[test]
+    fn canonical_types_do_not_depend_on_build_order() {
+        let nd = NodeIndex::new;
+        let int = || CType::Primitive {
+            type_constant: "int".to_owned(),
+            lower_bound: "bottom".to_owned(),
+            upper_bound: "int".to_owned(),
+            is_const: false,
+        };
+        let ptr = |target| CType::Pointer {
+            target,
+            const_target: false,
+        };
+        let list = |next| {
+            CType::Structure(vec![CField {
+                type_index: next,
+                ..cfield(0, 0, 64)
+            }])
+        };
+
+        // node 0 and node 1 are pointers to an int, nodes 2 and 3 are two copies of a linked list
+        let first = (
+            vec![
+                (nd(0), TypeId(10)),
+                (nd(1), TypeId(12)),
+                (nd(2), TypeId(16)),
+                (nd(3), TypeId(19)),
+                (nd(4), TypeId(13)),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+            vec![
+                (TypeId(10), ptr(TypeId(11))),
+                (TypeId(11), CType::Alias(nd(4))),
+                (TypeId(12), ptr(TypeId(14))),
+                (TypeId(13), int()),
+                (TypeId(14), CType::Alias(nd(4))),
+                (TypeId(15), CType::Alias(nd(3))),
+                (TypeId(16), list(TypeId(17))),
+                (TypeId(17), ptr(TypeId(15))),
+                (TypeId(18), CType::Alias(nd(2))),
+                (TypeId(19), list(TypeId(20))),
+                (TypeId(20), ptr(TypeId(18))),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+        );
+        let second = (
+            vec![
+                (nd(0), TypeId(7)),
+                (nd(1), TypeId(7)),
+                (nd(2), TypeId(5)),
+                (nd(3), TypeId(5)),
+                (nd(4), TypeId(1)),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+            vec![
+                (TypeId(1), int()),
+                (TypeId(3), CType::Alias(nd(2))),
+                (TypeId(4), ptr(TypeId(3))),
+                (TypeId(5), list(TypeId(4))),
+                (TypeId(6), CType::Alias(nd(4))),
+                (TypeId(7), ptr(TypeId(6))),
+            ]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+        );
+
+        let (first_nodes, first_types) = canonicalize_types(&first.0, &first.1);
+        let (second_nodes, second_types) = canonicalize_types(&second.0, &second.1);
+
+        assert_eq!(first_nodes, second_nodes);
+        assert_eq!(first_nodes[&nd(0)], first_nodes[&nd(1)]);
+        assert_eq!(first_nodes[&nd(2)], first_nodes[&nd(3)]);
+        // int, the pointer to it and its alias, and the list with its next pointer and alias
+        assert_eq!(first_types.len(), 6);
+        assert_eq!(second_types.len(), 6);
+        assert_eq!(
+            first_types
+                .iter()
+                .filter(|(_, ty)| !matches!(ty, CType::Alias(_)))
+                .collect::<Vec<_>>(),
+            second_types
+                .iter()
+                .filter(|(_, ty)| !matches!(ty, CType::Alias(_)))
+                .collect::<Vec<_>>()
+        );
     }
 }
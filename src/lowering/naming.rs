@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use cwe_checker_lib::intermediate_representation::Tid;
+use petgraph::graph::NodeIndex;
+
+use crate::ctypes::CTypeMapping;
+
+use super::{convert_typeid, referenced_types, CType, TypeId};
+
+/// How many levels of referenced types contribute to the hash of a type.
+const HASH_DEPTH: usize = 4;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a, used instead of the std hasher because the hash ends up in names that need to be stable across runs and builds.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>, init: u64) -> u64 {
+    bytes.into_iter().fold(init, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn encode_usize(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend((value as u64).to_le_bytes());
+}
+
+fn encode_str(bytes: &mut Vec<u8>, value: &str) {
+    encode_usize(bytes, value.len());
+    bytes.extend(value.as_bytes());
+}
+
+/// Encodes the local content of a type, leaving out the types it refers to.
+/// Each kind of type is tagged by a fixed byte so the encoding does not depend on the names or layout of the rust types.
+fn shape_encoding(ty: &CType) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match ty {
+        CType::Primitive {
+            type_constant,
+            lower_bound,
+            upper_bound,
+            is_const,
+        } => {
+            bytes.push(0);
+            encode_str(&mut bytes, type_constant);
+            encode_str(&mut bytes, lower_bound);
+            encode_str(&mut bytes, upper_bound);
+            bytes.push(u8::from(*is_const));
+        }
+        CType::Pointer { const_target, .. } => {
+            bytes.push(1);
+            bytes.push(u8::from(*const_target));
+        }
+        CType::Alias(_) => bytes.push(2),
+        CType::Structure(flds) => {
+            bytes.push(3);
+            encode_usize(&mut bytes, flds.len());
+            for fld in flds.iter() {
+                encode_usize(&mut bytes, fld.byte_offset);
+                encode_usize(&mut bytes, fld.bit_offset);
+                encode_usize(&mut bytes, fld.bit_sz);
+            }
+        }
+        CType::Function { params, return_ty } => {
+            bytes.push(4);
+            encode_usize(&mut bytes, params.len());
+            for param in params.iter() {
+                encode_usize(&mut bytes, param.index);
+            }
+            bytes.push(u8::from(return_ty.is_some()));
+        }
+        // The children of a union are hashed as a set
+        CType::Union(_) => bytes.push(5),
+        CType::Enum { constants, .. } => {
+            bytes.push(6);
+            encode_usize(&mut bytes, constants.len());
+            for constant in constants.iter() {
+                bytes.extend(constant.to_le_bytes());
+            }
+        }
+        CType::Array { stride, count, .. } => {
+            bytes.push(7);
+            encode_usize(&mut bytes, *stride);
+            encode_usize(&mut bytes, *count);
+        }
+    }
+    bytes
+}
+
+/// The prefix of the generated name for a kind of type, types without a prefix are not named.
+fn kind_prefix(ty: &CType) -> Option<&'static str> {
+    match ty {
+        CType::Structure(_) => Some("struct"),
+        CType::Union(_) => Some("union"),
+        CType::Enum { .. } => Some("enum"),
+        CType::Function { .. } => Some("func"),
+        CType::Primitive { .. } | CType::Pointer { .. } | CType::Alias(_) | CType::Array { .. } => {
+            None
+        }
+    }
+}
+
+/// Replaces characters that cannot appear in a C identifier.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+struct TypeNamer<'a> {
+    types: &'a BTreeMap<TypeId, CType>,
+    node_to_ty: &'a HashMap<NodeIndex, TypeId>,
+    hashes: BTreeMap<(TypeId, usize), u64>,
+    names: BTreeMap<TypeId, String>,
+    used_names: BTreeSet<String>,
+}
+
+impl<'a> TypeNamer<'a> {
+    /// Hashes the shape of a type and the types it refers to up to a depth, so isomorphic types hash the same regardless of their type ids.
+    fn structural_hash(&mut self, id: TypeId, depth: usize) -> u64 {
+        if let Some(hash) = self.hashes.get(&(id, depth)) {
+            return *hash;
+        }
+
+        let types = self.types;
+        let ty = &types[&id];
+        let mut hash = fnv1a(shape_encoding(ty), FNV_OFFSET_BASIS);
+        if depth > 0 {
+            let mut referenced = referenced_types(ty, self.node_to_ty)
+                .into_iter()
+                .filter(|referenced| types.contains_key(referenced))
+                .map(|referenced| self.structural_hash(referenced, depth - 1))
+                .collect::<Vec<_>>();
+            if let CType::Union(_) = ty {
+                referenced.sort_unstable();
+                referenced.dedup();
+            }
+            hash = referenced
+                .into_iter()
+                .fold(hash, |hash, child| fnv1a(child.to_le_bytes(), hash));
+        }
+
+        self.hashes.insert((id, depth), hash);
+        hash
+    }
+
+    fn assign_name(&mut self, id: TypeId, base: String) {
+        if self.names.contains_key(&id) {
+            return;
+        }
+
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while self.used_names.contains(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+
+        self.used_names.insert(name.clone());
+        self.names.insert(id, name);
+    }
+
+    /// Names the types reachable from each root after the path from the root, visiting roots in order.
+    fn name_from_roots(&mut self, roots: Vec<(TypeId, String)>) {
+        let mut visited = BTreeSet::new();
+        let mut worklist = roots.into_iter().collect::<VecDeque<_>>();
+        while let Some((id, origin)) = worklist.pop_front() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            let types = self.types;
+            let ty = match types.get(&id) {
+                Some(ty) => ty,
+                None => continue,
+            };
+
+            if let Some(prefix) = kind_prefix(ty) {
+                self.assign_name(id, format!("{}_{}", prefix, origin));
+            }
+
+            match ty {
+                CType::Function { params, return_ty } => {
+                    worklist.extend(
+                        params
+                            .iter()
+                            .map(|p| (p.type_index, format!("{}_arg{}", origin, p.index))),
+                    );
+                    worklist.extend(return_ty.map(|ret| (ret, format!("{}_ret", origin))));
+                }
+                CType::Structure(flds) => worklist.extend(flds.iter().map(|fld| {
+                    (
+                        fld.type_index,
+                        format!("{}_field{}", origin, fld.byte_offset),
+                    )
+                })),
+                _ => worklist.extend(
+                    referenced_types(ty, self.node_to_ty)
+                        .into_iter()
+                        .map(|referenced| (referenced, origin.clone())),
+                ),
+            }
+        }
+    }
+}
+
+/// Generates a stable name for each structure, union, enum and function type.
+/// A type reachable from an interesting tid is named after the tid and the parameters and fields leading to it, visiting tids in order.
+/// Other types are named by a hash of their structure. Names are made unique by numbering repeated names.
+pub fn name_types(
+    types: &BTreeMap<TypeId, CType>,
+    node_to_ty: &HashMap<NodeIndex, TypeId>,
+    tid_to_node: &BTreeMap<Tid, NodeIndex>,
+) -> BTreeMap<TypeId, String> {
+    let mut namer = TypeNamer {
+        types,
+        node_to_ty,
+        hashes: BTreeMap::new(),
+        names: BTreeMap::new(),
+        used_names: BTreeSet::new(),
+    };
+
+    namer.name_from_roots(
+        tid_to_node
+            .iter()
+            .filter_map(|(tid, nd)| {
+                node_to_ty
+                    .get(nd)
+                    .map(|id| (*id, sanitize(tid.get_str_repr())))
+            })
+            .collect(),
+    );
+
+    for (id, ty) in types.iter() {
+        if let Some(prefix) = kind_prefix(ty) {
+            let hash = namer.structural_hash(*id, HASH_DEPTH);
+            namer.assign_name(*id, format!("{}_{:016x}", prefix, hash));
+        }
+    }
+
+    namer.names
+}
+
+/// Adds the generated name of each type to the protobuf mapping.
+pub fn add_names_to_protobuf(mapping: &mut CTypeMapping, names: &BTreeMap<TypeId, String>) {
+    for (id, name) in names.iter() {
+        if let Some(ctype) = mapping
+            .type_id_to_ctype
+            .get_mut(&convert_typeid(*id).type_id)
+        {
+            ctype.name = name.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use cwe_checker_lib::intermediate_representation::Tid;
+    use petgraph::graph::NodeIndex;
+
+    use crate::lowering::{CType, Field, Parameter, TypeId};
+
+    use super::name_types;
+
+    fn int() -> CType {
+        CType::Primitive {
+            type_constant: "int".to_owned(),
+            lower_bound: "bottom".to_owned(),
+            upper_bound: "int".to_owned(),
+            is_const: false,
+        }
+    }
+
+    fn pair(fst: TypeId, snd: TypeId) -> CType {
+        CType::Structure(vec![
+            Field {
+                byte_offset: 0,
+                bit_sz: 32,
+                type_index: fst,
+                bit_offset: 0,
+            },
+            Field {
+                byte_offset: 4,
+                bit_sz: 32,
+                type_index: snd,
+                bit_offset: 0,
+            },
+        ])
+    }
+
+    #[test]
+    fn types_are_named_after_where_they_are_reached() {
+        let nd = NodeIndex::new;
+        let node_to_ty = vec![(nd(0), TypeId(0)), (nd(1), TypeId(1))]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let types = vec![
+            (
+                TypeId(0),
+                CType::Function {
+                    params: vec![Parameter {
+                        index: 0,
+                        type_index: TypeId(2),
+                    }],
+                    return_ty: None,
+                },
+            ),
+            (TypeId(1), int()),
+            (
+                TypeId(2),
+                CType::Pointer {
+                    target: TypeId(3),
+                    const_target: false,
+                },
+            ),
+            (TypeId(3), pair(TypeId(1), TypeId(1))),
+            // unreachable from any tid, so named by its structure
+            (TypeId(4), pair(TypeId(1), TypeId(3))),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+        let tids = vec![(Tid::create("sub_1000".to_owned(), "1000".to_owned()), nd(0))]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        let names = name_types(&types, &node_to_ty, &tids);
+
+        assert_eq!(names[&TypeId(0)], "func_sub_1000");
+        assert_eq!(names[&TypeId(3)], "struct_sub_1000_arg0");
+        // the structural hash is part of the output, so it must not change between builds
+        assert_eq!(names[&TypeId(4)], "struct_62cef17b7a04c3a6");
+        assert!(!names.contains_key(&TypeId(1)));
+        assert!(!names.contains_key(&TypeId(2)));
+        assert_eq!(names, name_types(&types, &node_to_ty, &tids));
+    }
+}
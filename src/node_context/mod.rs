@@ -1,18 +1,19 @@
 use std::{
     borrow::Borrow,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     rc::Rc,
 };
 
 use cwe_checker_lib::{
     analysis::pointer_inference::Config,
-    intermediate_representation::{Program, Tid},
+    intermediate_representation::{Bitvector, Program, RuntimeMemoryImage, Tid},
     AnalysisResults,
 };
 
 use petgraph::graph::NodeIndex;
 
 use crate::{
+    analysis::{memory_returns::MemoryReturn, reaching_definitions::ClobberPolicy},
     constraint_generation::{
         ConstantResolver, NodeContext, NodeContextMapping, PointsToMapping, RegisterMapping,
         SubprocedureLocators,
@@ -31,6 +32,7 @@ pub mod register_map;
 pub mod subproc_loc;
 
 use anyhow::Result;
+use points_to::ObjectNamingPolicy;
 use std::iter::Iterator;
 
 use self::{
@@ -73,6 +75,31 @@ pub fn make_node_contexts<
 /// to map some known addresses to global variable terms.
 pub struct GhidraConstantResolver {
     global_map: Rc<BTreeMap<u64, Tid>>,
+    string_literals: Rc<BTreeSet<String>>,
+}
+
+impl GhidraConstantResolver {
+    /// Finds the globals that are string literals: NUL-terminated text in read-only memory.
+    pub fn with_string_literals(mut self, rt_mem: &RuntimeMemoryImage) -> Self {
+        self.string_literals = Rc::new(
+            self.global_map
+                .iter()
+                .filter(|(addr, _)| {
+                    let addr = Bitvector::from_u64(**addr);
+                    matches!(rt_mem.is_address_writeable(&addr), Ok(false))
+                        && rt_mem
+                            .read_string_until_null_terminator(&addr)
+                            .map(|s| {
+                                !s.is_empty()
+                                    && s.chars().all(|c| !c.is_control() || c.is_whitespace())
+                            })
+                            .unwrap_or(false)
+                })
+                .map(|(_, tid)| tid.get_str_repr().to_owned())
+                .collect(),
+        );
+        self
+    }
 }
 
 impl<T> From<T> for GhidraConstantResolver
@@ -90,6 +117,7 @@ where
 
         GhidraConstantResolver {
             global_map: Rc::new(mp),
+            string_literals: Rc::new(BTreeSet::new()),
         }
     }
 }
@@ -132,12 +160,23 @@ impl ConstantResolver for GhidraConstantResolver {
             })
             .map(DerivedTypeVar::new)
     }
+
+    fn is_string_literal(&self, var: &DerivedTypeVar) -> bool {
+        var.get_field_labels().is_empty()
+            && self
+                .string_literals
+                .contains(&var.get_base_variable().get_name())
+    }
 }
 
-/// Creates a default context with the default analyses [register_map], [points_to], and [subproc_loc]
+/// Creates a default context with the default analyses [register_map], [points_to], and [subproc_loc].
+/// Abstract objects are named as type variables by the object naming policy.
 pub fn create_default_context<'a>(
     proj: &'a AnalysisResults<'a>,
     config: Config,
+    clobber_policy: ClobberPolicy,
+    object_naming: ObjectNamingPolicy,
+    memory_returns: HashMap<Tid, Vec<MemoryReturn>>,
     weakest_integral_type: TypeVariable,
     debug_dir: FileDebugLogger,
 ) -> Result<
@@ -146,7 +185,8 @@ pub fn create_default_context<'a>(
         NodeContext<RegisterContext, PointsToContext, ProcedureContext, GhidraConstantResolver>,
     >,
 > {
-    let reg_context = register_map::run_analysis(proj.project, proj.control_flow_graph);
+    let reg_context =
+        register_map::run_analysis(proj.project, proj.control_flow_graph, clobber_policy);
 
     for nd_idx in proj.control_flow_graph.node_indices() {
         let nd = &proj.control_flow_graph[nd_idx];
@@ -163,10 +203,11 @@ pub fn create_default_context<'a>(
         }
     }
 
-    let points_to_context = points_to::run_analysis(proj, config)?;
+    let points_to_context = points_to::run_analysis(proj, config, object_naming)?;
 
     let proc_handler = ProcedureContext {
         stack_pointer: proj.project.stack_pointer_register.clone(),
+        memory_returns: Rc::new(memory_returns),
     };
     let proc_context: HashMap<NodeIndex, ProcedureContext> = proj
         .control_flow_graph
@@ -174,7 +215,8 @@ pub fn create_default_context<'a>(
         .map(|idx| (idx, proc_handler.clone()))
         .collect();
 
-    let const_context = GhidraConstantResolver::from(&proj.project.program.term);
+    let const_context = GhidraConstantResolver::from(&proj.project.program.term)
+        .with_string_literals(&proj.project.runtime_memory_image);
 
     Ok(make_node_contexts(
         reg_context,
@@ -188,3 +230,45 @@ pub fn create_default_context<'a>(
         weakest_integral_type,
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use cwe_checker_lib::intermediate_representation::Bitvector;
+
+    use crate::constraint_generation::ConstantResolver;
+    use crate::constraints::{DerivedTypeVar, TypeVariable};
+    use crate::inference_job::InferenceJob;
+    use crate::test_utils;
+
+    use super::GhidraConstantResolver;
+
+    #[test]
+    fn read_only_text_globals_are_string_literals() {
+        let bin = test_utils::test_file_to_bytes("mooosl");
+        let proj = crate::util::get_intermediate_representation_for_reader(
+            test_utils::open_test_file("mooosl.json"),
+            &bin,
+        )
+        .expect("should be able to parse mooosl");
+        let rt_mem =
+            InferenceJob::get_runtime_image(&proj, &bin).expect("should be able to load mooosl");
+        let resolver =
+            GhidraConstantResolver::from(&proj.program.term).with_string_literals(&rt_mem);
+
+        let is_literal = |name: &str| {
+            resolver.is_string_literal(&DerivedTypeVar::new(TypeVariable::new_global(
+                name.to_owned(),
+            )))
+        };
+        assert!(is_literal("glb_00102000_s_key_size:_00102000"));
+        assert!(is_literal("glb_0010204b_s_2:_query_0010204b"));
+        // writable memory and code are not literals
+        assert!(!is_literal("glb_00104040_DAT_00104040"));
+        assert!(!is_literal("glb_00101000__init"));
+
+        let resolved = resolver
+            .maybe_resolve_constant_to_variable(&Bitvector::from_u64(0x0010_2000))
+            .expect("the literal's address should resolve to its global");
+        assert!(resolver.is_string_literal(&resolved));
+    }
+}
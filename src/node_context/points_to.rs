@@ -1,24 +1,32 @@
 use crate::constraint_generation::{
-    ConstantResolver, NodeContextMapping, PointsToMapping, TypeVariableAccess,
+    ConstantResolver, NodeContextMapping, OffsetRange, PointsToMapping, TypeVariableAccess,
 };
 use crate::constraints::{DerivedTypeVar, TypeVariable};
 
 use anyhow::Result;
 use cwe_checker_lib::abstract_domain::{
-    AbstractIdentifier, DataDomain, IntervalDomain, TryToBitvec,
+    AbstractDomain, AbstractIdentifier, DataDomain, IntervalDomain, RegisterDomain, SizedDomain,
+    TryToBitvec, TryToInterval,
 };
 
+use cwe_checker_lib::analysis::graph::Node;
 use cwe_checker_lib::analysis::interprocedural_fixpoint_generic::NodeValue;
 use cwe_checker_lib::analysis::pointer_inference::{self, Config};
-use cwe_checker_lib::intermediate_representation::{ByteSize, Def, Expression, Variable};
+use cwe_checker_lib::intermediate_representation::{
+    Arg, BinOpType, ByteSize, Def, Expression, Jmp, Project, Sub, Term, Tid, Variable,
+};
 use cwe_checker_lib::AnalysisResults;
 
 use cwe_checker_lib::intermediate_representation::RuntimeMemoryImage;
+use itertools::Itertools;
 use log::warn;
 use petgraph::graph::NodeIndex;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::analysis::reaching_definitions::get_clobbered_registers;
+
 lazy_static! {
     /// The default set of allocation symbols an deallocation symbols
     pub static ref DEFAULT_PTR_CONFIG: Config = {
@@ -39,6 +47,140 @@ pub struct PointerState {
     /// The inner pointer inference state
     pub state: pointer_inference::State,
     rt_mem: Arc<RuntimeMemoryImage>,
+    returned_from: Option<Arc<CalleeReturn>>,
+}
+
+/// The callee's side of a call return, used to compute the caller's state after the return.
+struct CalleeReturn {
+    callee: Tid,
+    /// The callee's pointer state at its return
+    state: pointer_inference::State,
+    parameter_registers: Vec<Variable>,
+    return_registers: Vec<Variable>,
+    clobbered_registers: Vec<Variable>,
+}
+
+fn register_args(args: &[Arg]) -> impl Iterator<Item = &Variable> {
+    args.iter().filter_map(|arg| match arg {
+        Arg::Register {
+            expr: Expression::Var(var),
+            ..
+        } => Some(var),
+        _ => None,
+    })
+}
+
+impl CalleeReturn {
+    fn new(project: &Project, callee: &Term<Sub>, state: pointer_inference::State) -> CalleeReturn {
+        let return_registers = register_args(&callee.term.formal_rets)
+            .cloned()
+            .collect::<Vec<_>>();
+        let cc = project.get_specific_calling_convention(&callee.term.calling_convention);
+        CalleeReturn {
+            callee: callee.tid.clone(),
+            state,
+            parameter_registers: register_args(&callee.term.formal_args).cloned().collect(),
+            clobbered_registers: get_clobbered_registers(
+                project,
+                cc,
+                return_registers.iter().collect(),
+            )
+            .cloned()
+            .collect(),
+            return_registers,
+        }
+    }
+
+    /// Maps each abstract identifier the callee uses for a parameter to the value of the argument in the caller.
+    /// Depending on the cwe_checker version parameter identifiers are either timed at the callee's entry or at the callsite
+    /// so both are mapped.
+    fn parameter_replacements(
+        &self,
+        caller_state: &pointer_inference::State,
+        call_term: &Term<Jmp>,
+    ) -> BTreeMap<AbstractIdentifier, DataDomain<IntervalDomain>> {
+        self.parameter_registers
+            .iter()
+            .flat_map(|reg| {
+                let actual = caller_state.eval(&Expression::Var(reg.clone()));
+                vec![
+                    (
+                        AbstractIdentifier::from_var(self.callee.clone(), reg),
+                        actual.clone(),
+                    ),
+                    (
+                        AbstractIdentifier::from_var(call_term.tid.clone(), reg),
+                        actual,
+                    ),
+                ]
+            })
+            .collect()
+    }
+
+    /// Applies the return to the caller's state at the callsite.
+    fn apply_to_caller(
+        &self,
+        caller_state: &pointer_inference::State,
+        call_term: &Term<Jmp>,
+    ) -> pointer_inference::State {
+        let replacements = self.parameter_replacements(caller_state, call_term);
+        let mut new_state = caller_state.clone();
+
+        for reg in self.clobbered_registers.iter() {
+            new_state.set_register(reg, DataDomain::new_top(reg.size));
+        }
+
+        for reg in self.return_registers.iter() {
+            let returned = self.state.eval(&Expression::Var(reg.clone()));
+            new_state.set_register(
+                reg,
+                map_callee_value(&self.callee, &call_term.tid, &returned, &replacements),
+            );
+        }
+
+        new_state
+    }
+}
+
+/// Translates a value in the callee's state into the caller's state. Pointers to the callee's parameters become pointers to the
+/// actual arguments, pointers to objects local to the callee (its stack frame) are lost, and pointers to any other object, such as
+/// heap objects allocated by the callee, are kept since their identifiers are unique to the program.
+fn map_callee_value(
+    callee: &Tid,
+    call: &Tid,
+    value: &DataDomain<IntervalDomain>,
+    replacements: &BTreeMap<AbstractIdentifier, DataDomain<IntervalDomain>>,
+) -> DataDomain<IntervalDomain> {
+    let mut contains_top = value.contains_top();
+    let mut mapped: Option<DataDomain<IntervalDomain>> = value
+        .get_absolute_value()
+        .map(|abs| DataDomain::from(abs.clone()));
+
+    for (aid, offset) in value.get_relative_values().iter() {
+        let target = if let Some(actual) = replacements.get(aid) {
+            actual.bin_op(BinOpType::IntAdd, &DataDomain::from(offset.clone()))
+        } else if aid.get_tid() == callee || aid.get_tid() == call {
+            contains_top = true;
+            continue;
+        } else {
+            DataDomain::from_target(aid.clone(), offset.clone())
+        };
+
+        mapped = Some(match mapped {
+            Some(prev) => prev.merge(&target),
+            None => target,
+        });
+    }
+
+    match mapped {
+        Some(mut mapped) => {
+            if contains_top {
+                mapped.set_contains_top_flag();
+            }
+            mapped
+        }
+        None => DataDomain::new_top(value.bytesize()),
+    }
 }
 
 impl NodeContextMapping for PointerState {
@@ -62,22 +204,213 @@ impl NodeContextMapping for PointerState {
         PointerState {
             state: new_ptr_state,
             rt_mem: self.rt_mem.clone(),
+            returned_from: self.returned_from.clone(),
         }
     }
 
     fn apply_return_node(
         &self,
-        _call_term: &cwe_checker_lib::intermediate_representation::Term<
+        call_term: &cwe_checker_lib::intermediate_representation::Term<
             cwe_checker_lib::intermediate_representation::Jmp,
         >,
         _return_term: &cwe_checker_lib::intermediate_representation::Term<
             cwe_checker_lib::intermediate_representation::Jmp,
         >,
     ) -> Self {
-        // TODO(Ian): This is only ok because "generally" pointer returns arent a thing. Revisit this. What we need to do to fast forward the pointer state
-        // is call the update_return method on the pointer inference context but that is a private structure in cwe checker. We could expose it and store the context as
-        // an Rc in every node but that isnt ideal.
-        self.clone()
+        // NOTE(Ian): cwe checker's update_return is private to its pointer inference context so we reimplement the parts of the
+        // return transfer we need, relating the values the callee returns to the caller's abstract objects.
+        match &self.returned_from {
+            Some(returned_from) => PointerState {
+                state: returned_from.apply_to_caller(&self.state, call_term),
+                rt_mem: self.rt_mem.clone(),
+                returned_from: None,
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+/// Selects how the abstract objects of the pointer analysis are named as type variables, trading precision for merging.
+/// Objects with the same name share a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectNamingPolicy {
+    /// One type variable per abstract identifier, the site that allocated the object and the location its pointer was first held in
+    AllocationSite,
+    /// The allocation site qualified by the call string of at most k functions the object was allocated under: the allocating function
+    /// followed by the calls leading up to the function accessing the object. Objects a helper allocates are split by the caller they are returned to.
+    CallString(usize),
+    /// One type variable per allocating function and location, summarizing every object the function allocates
+    Summary,
+}
+
+impl Default for ObjectNamingPolicy {
+    fn default() -> Self {
+        ObjectNamingPolicy::AllocationSite
+    }
+}
+
+impl FromStr for ObjectNamingPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "site" => Ok(ObjectNamingPolicy::AllocationSite),
+            None if s == "summary" => Ok(ObjectNamingPolicy::Summary),
+            Some(("call_string", k)) => k
+                .parse()
+                .map(ObjectNamingPolicy::CallString)
+                .map_err(|_| anyhow::anyhow!("Call string length is not a number: {}", k)),
+            _ => Err(anyhow::anyhow!("Unknown object naming policy: {}", s)),
+        }
+    }
+}
+
+/// Names the abstract objects accessed within a function according to an [ObjectNamingPolicy].
+#[derive(Clone)]
+struct ObjectNamer {
+    policy: ObjectNamingPolicy,
+    /// The function accessing the objects
+    accessing_function: Tid,
+    /// Maps the tid of each term to the function containing it
+    containing_function: Arc<HashMap<Tid, Tid>>,
+    /// Maps each function to its callers
+    callers: Arc<HashMap<Tid, HashSet<Tid>>>,
+}
+
+impl ObjectNamer {
+    fn name(&self, aid: &AbstractIdentifier) -> TypeVariable {
+        let site_name = PointsToContext::type_variable_from_abstract_id(aid);
+        match self.policy {
+            ObjectNamingPolicy::CallString(k) => {
+                match self.containing_function.get(aid.get_tid()) {
+                    Some(allocating_function) if k > 0 => TypeVariable::new(format!(
+                        "{}/{}",
+                        site_name.get_name(),
+                        allocation_context(
+                            allocating_function,
+                            &self.accessing_function,
+                            k,
+                            &self.callers
+                        )
+                        .iter()
+                        .map(|tid| tid.get_str_repr())
+                        .join("/")
+                    )),
+                    _ => site_name,
+                }
+            }
+            ObjectNamingPolicy::Summary => {
+                match (
+                    self.containing_function.get(aid.get_tid()),
+                    site_name.get_name().split_once('@'),
+                ) {
+                    (Some(func), Some((_site, location))) => {
+                        TypeVariable::new(format!("{}@{}", func.get_str_repr(), location))
+                    }
+                    _ => site_name,
+                }
+            }
+            _ => site_name,
+        }
+    }
+}
+
+/// Maps each function to its callers.
+fn collect_callers(project: &Project) -> HashMap<Tid, HashSet<Tid>> {
+    let mut callers: HashMap<Tid, HashSet<Tid>> = HashMap::new();
+    for sub in project.program.term.subs.values() {
+        for jmp in sub.term.blocks.iter().flat_map(|blk| blk.term.jmps.iter()) {
+            if let Jmp::Call { target, .. } = &jmp.term {
+                callers
+                    .entry(target.clone())
+                    .or_default()
+                    .insert(sub.tid.clone());
+            }
+        }
+    }
+    callers
+}
+
+/// Builds the call string of at most k functions ending in the function, stopping at the first function without a unique caller.
+fn call_string(func: &Tid, k: usize, callers: &HashMap<Tid, HashSet<Tid>>) -> Vec<Tid> {
+    let mut frames = vec![func.clone()];
+    while frames.len() < k {
+        let unique_caller = callers
+            .get(frames.last().expect("call string has a frame"))
+            .filter(|callers| callers.len() == 1)
+            .and_then(|callers| callers.iter().next());
+        match unique_caller {
+            Some(caller) if !frames.contains(caller) => frames.push(caller.clone()),
+            _ => break,
+        }
+    }
+    frames.truncate(k);
+    frames
+}
+
+/// Builds the call string of at most k functions an object allocated in the allocating function is accessed under.
+/// If the accessing function reaches the allocating function through calls within the limit, the call string is the shortest path of calls
+/// from the allocating function up to the accessing function. Otherwise it is the allocating function extended by its unique callers, see [call_string].
+fn allocation_context(
+    allocating_function: &Tid,
+    accessing_function: &Tid,
+    k: usize,
+    callers: &HashMap<Tid, HashSet<Tid>>,
+) -> Vec<Tid> {
+    if allocating_function != accessing_function {
+        let mut paths = VecDeque::new();
+        paths.push_back(vec![allocating_function.clone()]);
+        while let Some(path) = paths.pop_front() {
+            let last = path.last().expect("call string has a frame");
+            if last == accessing_function {
+                return path;
+            }
+
+            if path.len() >= k {
+                continue;
+            }
+
+            for caller in callers.get(last).into_iter().flatten().sorted() {
+                if !path.contains(caller) {
+                    let mut next = path.clone();
+                    next.push(caller.clone());
+                    paths.push_back(next);
+                }
+            }
+        }
+    }
+
+    call_string(allocating_function, k, callers)
+}
+
+/// Maps the tid of every sub, block, def, and jump to the function that contains it.
+fn collect_containing_functions(project: &Project) -> HashMap<Tid, Tid> {
+    let mut containing = HashMap::new();
+    for sub in project.program.term.subs.values() {
+        containing.insert(sub.tid.clone(), sub.tid.clone());
+        for blk in sub.term.blocks.iter() {
+            containing.insert(blk.tid.clone(), sub.tid.clone());
+            for def in blk.term.defs.iter() {
+                containing.insert(def.tid.clone(), sub.tid.clone());
+            }
+            for jmp in blk.term.jmps.iter() {
+                containing.insert(jmp.tid.clone(), sub.tid.clone());
+            }
+        }
+    }
+    containing
+}
+
+fn node_function<'a>(nd: Node<'a>) -> &'a Tid {
+    match nd {
+        Node::BlkStart(_, sub) | Node::BlkEnd(_, sub) => &sub.tid,
+        Node::CallReturn {
+            call: (_, caller), ..
+        } => &caller.tid,
+        Node::CallSource {
+            source: (_, caller),
+            ..
+        } => &caller.tid,
     }
 }
 
@@ -87,17 +420,33 @@ pub struct PointsToContext {
     pointer_state: PointerState,
     /// Stack pointer for the program, used to determine the stack offset
     pub stack_pointer: Variable,
+    object_namer: ObjectNamer,
 }
 
 impl PointsToContext {
-    fn new(st: PointerState, stack_pointer: Variable) -> PointsToContext {
+    fn new(
+        st: PointerState,
+        stack_pointer: Variable,
+        object_namer: ObjectNamer,
+    ) -> PointsToContext {
         PointsToContext {
             pointer_state: st,
             stack_pointer,
+            object_namer,
         }
     }
 }
 
+/// Gets the strided range of a non constant offset. Offsets without a sensible bound, such as top or widened intervals, have no range.
+fn offset_range(offset: &IntervalDomain) -> Option<OffsetRange> {
+    let interval = offset.try_to_interval().ok()?;
+    OffsetRange::new(
+        interval.start.try_to_i64().ok()?,
+        interval.end.try_to_i64().ok()?,
+        interval.stride,
+    )
+}
+
 impl PointsToContext {
     /// Converts a cwe_checker abstract identifier into a unique type variable representing that
     /// abstract identifier for this function.
@@ -127,9 +476,11 @@ impl PointsToContext {
         sz: ByteSize,
     ) -> TypeVariableAccess {
         // TODO(ian): we may want to normalize this offset to the abstract object offset
+        let exact_offset = offset.try_to_offset().ok();
         TypeVariableAccess {
-            offset: offset.try_to_offset().ok(),
-            ty_var: Self::type_variable_from_abstract_id(object_id),
+            offset: exact_offset,
+            offset_range: exact_offset.map_or_else(|| offset_range(offset), |_| None),
+            ty_var: self.object_namer.name(object_id),
 
             sz,
         }
@@ -157,7 +508,11 @@ impl NodeContextMapping for PointsToContext {
     ) -> Self {
         let new_ptr_state = self.pointer_state.apply_def(term);
 
-        PointsToContext::new(new_ptr_state, self.stack_pointer.clone())
+        PointsToContext::new(
+            new_ptr_state,
+            self.stack_pointer.clone(),
+            self.object_namer.clone(),
+        )
     }
 
     fn apply_return_node(
@@ -169,16 +524,16 @@ impl NodeContextMapping for PointsToContext {
             cwe_checker_lib::intermediate_representation::Jmp,
         >,
     ) -> Self {
-        PointsToContext {
-            pointer_state: self.pointer_state.apply_return_node(call_term, return_term),
-            stack_pointer: self.stack_pointer.clone(),
-        }
+        PointsToContext::new(
+            self.pointer_state.apply_return_node(call_term, return_term),
+            self.stack_pointer.clone(),
+            self.object_namer.clone(),
+        )
     }
 }
 
 impl PointsToMapping for PointsToContext {
     /// This method is conservative and only returns abstract objects for which we have an
-    // TODO(ian): we should probably handle conflicting sizes
     fn points_to(
         &self,
         address: &cwe_checker_lib::intermediate_representation::Expression,
@@ -208,10 +563,11 @@ impl PointsToMapping for PointsToContext {
     }
 }
 
-/// Runs analysis on the project to generate a [PointsToMapping]
+/// Runs analysis on the project to generate a [PointsToMapping], naming abstract objects by the policy.
 pub fn run_analysis<'a>(
     analysis_results: &'a AnalysisResults<'a>,
     config: pointer_inference::Config,
+    object_naming: ObjectNamingPolicy,
 ) -> Result<HashMap<NodeIndex, PointsToContext>> {
     let pointer_res = pointer_inference::run(analysis_results, config, false, false);
 
@@ -222,6 +578,33 @@ pub fn run_analysis<'a>(
         .node_indices()
         .filter_map(|idx| {
             pointer_res.get_node_value(idx).and_then(|nv| match nv {
+                // At a call return the caller's state at the callsite is kept along with the callee's state at the return
+                // so that the return can be applied to the caller.
+                NodeValue::CallFlowCombinator {
+                    call_stub: Some(caller_state),
+                    interprocedural_flow: Some(callee_state),
+                } => {
+                    let returned_from = match analysis_results.control_flow_graph[idx] {
+                        Node::CallReturn {
+                            return_: (_, callee),
+                            ..
+                        } => Some(Arc::new(CalleeReturn::new(
+                            analysis_results.project,
+                            callee,
+                            callee_state.clone(),
+                        ))),
+                        _ => None,
+                    };
+
+                    Some((
+                        idx,
+                        PointerState {
+                            rt_mem: rt_mem.clone(),
+                            state: caller_state.clone(),
+                            returned_from,
+                        },
+                    ))
+                }
                 NodeValue::CallFlowCombinator {
                     call_stub,
                     interprocedural_flow,
@@ -237,6 +620,7 @@ pub fn run_analysis<'a>(
                         PointerState {
                             rt_mem: rt_mem.clone(),
                             state: v.clone(),
+                            returned_from: None,
                         },
                     )
                 }),
@@ -246,18 +630,40 @@ pub fn run_analysis<'a>(
                     PointerState {
                         rt_mem: rt_mem.clone(),
                         state: v.clone(),
+                        returned_from: None,
                     },
                 )),
             })
         })
         .collect();
 
+    let containing_function = Arc::new(match object_naming {
+        ObjectNamingPolicy::Summary | ObjectNamingPolicy::CallString(_) => {
+            collect_containing_functions(analysis_results.project)
+        }
+        ObjectNamingPolicy::AllocationSite => HashMap::new(),
+    });
+    let callers = Arc::new(match object_naming {
+        ObjectNamingPolicy::CallString(_) => collect_callers(analysis_results.project),
+        _ => HashMap::new(),
+    });
+
     Ok(state_mapping
         .into_iter()
         .map(|(idx, ps)| {
             (
                 idx,
-                PointsToContext::new(ps, analysis_results.project.stack_pointer_register.clone()),
+                PointsToContext::new(
+                    ps,
+                    analysis_results.project.stack_pointer_register.clone(),
+                    ObjectNamer {
+                        policy: object_naming,
+                        accessing_function: node_function(analysis_results.control_flow_graph[idx])
+                            .clone(),
+                        containing_function: containing_function.clone(),
+                        callers: callers.clone(),
+                    },
+                ),
             )
         })
         .collect())
@@ -266,24 +672,33 @@ pub fn run_analysis<'a>(
 #[cfg(test)]
 mod test {
 
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use std::path::{Path, PathBuf};
+    use std::sync::Arc;
 
     use cwe_checker_lib::{
-        abstract_domain::TryToBitvec,
+        abstract_domain::{
+            AbstractIdentifier, DataDomain, IntervalDomain, SizedDomain, TryToBitvec,
+        },
         analysis::graph::{Graph, Node},
         intermediate_representation::{
-            BinOpType, Bitvector, Blk, ByteSize, Expression, RuntimeMemoryImage, Term, Variable,
+            BinOpType, Bitvector, Blk, ByteSize, Expression, RuntimeMemoryImage, Term, Tid,
+            Variable,
         },
         AnalysisResults,
     };
     use petgraph::stable_graph::NodeIndex;
 
     use crate::{
-        constraint_generation::NodeContextMapping, inference_job::InferenceJob,
+        constraint_generation::{NodeContextMapping, PointsToMapping},
+        inference_job::InferenceJob,
         node_context::points_to::PointsToContext,
     };
 
-    use super::{run_analysis, DEFAULT_PTR_CONFIG};
+    use super::{
+        allocation_context, call_string, map_callee_value, offset_range, run_analysis, ObjectNamer,
+        ObjectNamingPolicy, DEFAULT_PTR_CONFIG,
+    };
 
     fn test_data_dir<P: AsRef<Path>>(pth: P) -> String {
         let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -372,8 +787,12 @@ mod test {
 
         let analysis_results = analysis_results.with_function_signatures(Some(&res));
 
-        let pts_to_ctx = run_analysis(&analysis_results, DEFAULT_PTR_CONFIG.clone())
-            .expect("analysis should succeed");
+        let pts_to_ctx = run_analysis(
+            &analysis_results,
+            DEFAULT_PTR_CONFIG.clone(),
+            ObjectNamingPolicy::default(),
+        )
+        .expect("analysis should succeed");
         let (ndidx, nd_body) =
             find_ndidx_for_block("001015bf", &cfg).expect("should have allocating block");
         let ctx = pts_to_ctx
@@ -470,4 +889,218 @@ mod test {
             0
         );
     }
+
+    #[test]
+    fn call_strings_follow_the_allocation_context() {
+        let target_bin_path = test_data_dir("mooosl");
+        let bin =
+            InferenceJob::parse_binary(&target_bin_path).expect("should be able to parse mooosl");
+        let project = InferenceJob::parse_project(&test_data_dir("mooosl.json"), &bin)
+            .expect("Should get cwe checker project");
+        let cfg = InferenceJob::graph_from_project(&project);
+
+        let analysis_results = AnalysisResults::new(&bin, &cfg, &project);
+        let (res, logs) = analysis_results.compute_function_signatures();
+        logs.iter().for_each(crate::util::log_cwe_message);
+        let analysis_results = analysis_results.with_function_signatures(Some(&res));
+
+        let (ndidx, _) =
+            find_ndidx_for_block("001015bf", &cfg).expect("should have allocating block");
+        let rax = Expression::Var(register("RAX"));
+        let names_at_allocation = |policy| {
+            let pts_to_ctx = run_analysis(&analysis_results, DEFAULT_PTR_CONFIG.clone(), policy)
+                .expect("analysis should succeed");
+            pts_to_ctx[&ndidx]
+                .points_to(&rax, ByteSize::new(8))
+                .into_iter()
+                .map(|acc| acc.ty_var.get_name())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names_at_allocation(ObjectNamingPolicy::AllocationSite),
+            vec!["instr_001015ba_2@RAX"]
+        );
+        // the allocating function has a unique caller, so the object is named under it
+        assert_eq!(
+            names_at_allocation(ObjectNamingPolicy::CallString(3)),
+            vec!["instr_001015ba_2@RAX/sub_001015a4/sub_00101841"]
+        );
+        assert_eq!(
+            names_at_allocation(ObjectNamingPolicy::Summary),
+            vec!["sub_001015a4@RAX"]
+        );
+    }
+
+    fn register(name: &str) -> Variable {
+        Variable {
+            name: name.to_owned(),
+            size: ByteSize::new(8),
+            is_temp: false,
+        }
+    }
+
+    fn offset(off: i64) -> IntervalDomain {
+        IntervalDomain::from(Bitvector::from_i64(off))
+    }
+
+    #[test]
+    fn maps_callee_returned_pointers_into_caller() {
+        let callee = Tid::new("sub_00001000");
+        let call = Tid::new("instr_00002000_0");
+        let caller_object =
+            AbstractIdentifier::from_var(Tid::new("instr_00001ff0_2"), &register("RAX"));
+        let heap_object =
+            AbstractIdentifier::from_var(Tid::new("instr_00001010_2"), &register("RAX"));
+        let parameter = AbstractIdentifier::from_var(callee.clone(), &register("RDI"));
+        let callee_stack = AbstractIdentifier::from_var(callee.clone(), &register("RSP"));
+
+        let mut replacements = BTreeMap::new();
+        replacements.insert(
+            parameter.clone(),
+            DataDomain::from_target(caller_object.clone(), offset(16)),
+        );
+
+        // returning a field of a pointer parameter points into the caller's argument
+        let mapped = map_callee_value(
+            &callee,
+            &call,
+            &DataDomain::from_target(parameter, offset(8)),
+            &replacements,
+        );
+        assert_eq!(
+            mapped.get_if_unique_target(),
+            Some((&caller_object, &offset(24)))
+        );
+
+        // objects allocated by the callee keep their identity in the caller
+        let mapped = map_callee_value(
+            &callee,
+            &call,
+            &DataDomain::from_target(heap_object.clone(), offset(0)),
+            &replacements,
+        );
+        assert_eq!(
+            mapped.get_if_unique_target(),
+            Some((&heap_object, &offset(0)))
+        );
+
+        // the callee's stack frame does not outlive the return
+        let mapped = map_callee_value(
+            &callee,
+            &call,
+            &DataDomain::from_target(callee_stack, offset(-8)),
+            &replacements,
+        );
+        assert!(mapped.get_relative_values().is_empty());
+        assert!(mapped.contains_top());
+    }
+
+    #[test]
+    fn names_objects_by_policy() {
+        let entry = Tid::new("sub_00002000");
+        let caller = Tid::new("sub_00003000");
+        let other_caller = Tid::new("sub_00004000");
+        let helper = Tid::new("sub_00001000");
+        let site = Tid::new("instr_00001010_2");
+
+        let mut callers = HashMap::new();
+        callers.insert(
+            helper.clone(),
+            vec![caller.clone(), other_caller.clone()]
+                .into_iter()
+                .collect::<HashSet<_>>(),
+        );
+        callers.insert(caller.clone(), vec![entry.clone()].into_iter().collect());
+        assert_eq!(
+            call_string(&caller, 3, &callers),
+            vec![caller.clone(), entry.clone()]
+        );
+        assert_eq!(call_string(&caller, 1, &callers), vec![caller.clone()]);
+        assert_eq!(call_string(&helper, 3, &callers), vec![helper.clone()]);
+
+        // objects are split by the caller the helper returns them to
+        assert_eq!(
+            allocation_context(&helper, &caller, 2, &callers),
+            vec![helper.clone(), caller.clone()]
+        );
+        assert_eq!(
+            allocation_context(&helper, &other_caller, 2, &callers),
+            vec![helper.clone(), other_caller.clone()]
+        );
+        assert_eq!(
+            allocation_context(&helper, &entry, 3, &callers),
+            vec![helper.clone(), caller.clone(), entry.clone()]
+        );
+        // past the limit, or within the allocating function, the context is the allocating function's unique callers
+        assert_eq!(
+            allocation_context(&helper, &entry, 2, &callers),
+            vec![helper.clone()]
+        );
+        assert_eq!(
+            allocation_context(&caller, &caller, 2, &callers),
+            vec![caller.clone(), entry]
+        );
+
+        let mut containing_function = HashMap::new();
+        containing_function.insert(site.clone(), helper.clone());
+        let namer = |policy, accessing_function: &Tid| ObjectNamer {
+            policy,
+            accessing_function: accessing_function.clone(),
+            containing_function: Arc::new(containing_function.clone()),
+            callers: Arc::new(callers.clone()),
+        };
+
+        let heap_object = AbstractIdentifier::from_var(site, &register("RAX"));
+        assert_eq!(
+            namer(ObjectNamingPolicy::AllocationSite, &caller)
+                .name(&heap_object)
+                .get_name(),
+            "instr_00001010_2@RAX"
+        );
+        assert_eq!(
+            namer(ObjectNamingPolicy::CallString(2), &caller)
+                .name(&heap_object)
+                .get_name(),
+            "instr_00001010_2@RAX/sub_00001000/sub_00003000"
+        );
+        assert_eq!(
+            namer(ObjectNamingPolicy::CallString(2), &other_caller)
+                .name(&heap_object)
+                .get_name(),
+            "instr_00001010_2@RAX/sub_00001000/sub_00004000"
+        );
+        assert_eq!(
+            namer(ObjectNamingPolicy::CallString(2), &helper)
+                .name(&heap_object)
+                .get_name(),
+            "instr_00001010_2@RAX/sub_00001000"
+        );
+        assert_eq!(
+            namer(ObjectNamingPolicy::Summary, &caller)
+                .name(&heap_object)
+                .get_name(),
+            "sub_00001000@RAX"
+        );
+
+        assert_eq!(
+            "call_string:2".parse::<ObjectNamingPolicy>().unwrap(),
+            ObjectNamingPolicy::CallString(2)
+        );
+        assert!("call_string:x".parse::<ObjectNamingPolicy>().is_err());
+    }
+
+    #[test]
+    fn offset_ranges_need_a_sensible_bound() {
+        let bounded = offset_range(&IntervalDomain::new(
+            Bitvector::from_i64(0),
+            Bitvector::from_i64(12),
+        ))
+        .unwrap();
+        assert_eq!((bounded.lower, bounded.upper), (0, 12));
+
+        let widened = IntervalDomain::new(Bitvector::from_i64(0), Bitvector::from_i64(i64::MAX));
+        assert!(offset_range(&widened).is_none());
+        assert!(offset_range(&IntervalDomain::new_top(ByteSize::new(8))).is_none());
+    }
 }
@@ -7,12 +7,12 @@ use std::rc::Rc;
 use cwe_checker_lib::abstract_domain::DomainMap;
 use cwe_checker_lib::analysis::graph::Graph;
 use cwe_checker_lib::analysis::interprocedural_fixpoint_generic::NodeValue;
-use cwe_checker_lib::intermediate_representation::{Project, Variable};
+use cwe_checker_lib::intermediate_representation::{Project, Tid, Variable};
 use petgraph::graph::NodeIndex;
 
 use crate::analysis;
 use crate::analysis::reaching_definitions::{
-    self, Context, Definition, ImplicitBottomMappingDomain, TermSet,
+    self, ClobberPolicy, Context, Definition, ImplicitBottomMappingDomain, TermSet,
 };
 use crate::constraint_generation::{self, NodeContextMapping, RegisterMapping};
 use crate::constraints::TypeVariable;
@@ -25,6 +25,8 @@ use cwe_checker_lib::intermediate_representation::Def;
 pub struct RegisterContext {
     mapping: BTreeMap<Variable, TermSet>,
     project: Rc<Project>,
+    loaded_definitions: Rc<HashSet<Tid>>,
+    clobber_policy: ClobberPolicy,
 }
 
 impl Display for RegisterContext {
@@ -38,10 +40,18 @@ impl Display for RegisterContext {
 
 impl RegisterContext {
     /// Creates a new register context that can answer register access queries from a reaching definitions [NodeValue].
-    pub fn new(mapping: BTreeMap<Variable, TermSet>, project: &Rc<Project>) -> RegisterContext {
+    /// The loaded definitions are the [Tid]s of the project's load definitions.
+    pub fn new(
+        mapping: BTreeMap<Variable, TermSet>,
+        project: &Rc<Project>,
+        loaded_definitions: &Rc<HashSet<Tid>>,
+        clobber_policy: ClobberPolicy,
+    ) -> RegisterContext {
         RegisterContext {
             mapping,
             project: project.clone(),
+            loaded_definitions: loaded_definitions.clone(),
+            clobber_policy,
         }
     }
 
@@ -83,6 +93,8 @@ impl NodeContextMapping for RegisterContext {
         RegisterContext {
             mapping: new_mapping.deref().deref().clone(),
             project: self.project.clone(),
+            loaded_definitions: self.loaded_definitions.clone(),
+            clobber_policy: self.clobber_policy,
         }
     }
 
@@ -101,11 +113,14 @@ impl NodeContextMapping for RegisterContext {
             ))),
             call_term,
             &self.project,
+            self.clobber_policy,
         );
 
         RegisterContext {
             mapping: new_mapping.deref().deref().clone(),
             project: self.project.clone(),
+            loaded_definitions: self.loaded_definitions.clone(),
+            clobber_policy: self.clobber_policy,
         }
     }
 }
@@ -117,6 +132,28 @@ impl RegisterMapping for RegisterContext {
         ts.map(|x| Self::generate_multi_def_constraint(var, x))
             .unwrap_or_default()
     }
+
+    fn is_loaded(&self, var: &Variable) -> bool {
+        self.mapping.get(var).map_or(false, |defs| {
+            !defs.is_empty()
+                && defs.iter().all(|def| {
+                    matches!(def, Definition::Normal(tid) if self.loaded_definitions.contains(tid))
+                })
+        })
+    }
+}
+
+/// Collects the [Tid]s of every load definition in the project.
+fn collect_loaded_definitions(proj: &Project) -> HashSet<Tid> {
+    proj.program
+        .term
+        .subs
+        .values()
+        .flat_map(|sub| sub.term.blocks.iter())
+        .flat_map(|blk| blk.term.defs.iter())
+        .filter(|df| matches!(df.term, Def::Load { .. }))
+        .map(|df| df.tid.clone())
+        .collect()
 }
 
 fn generate_fresh_definition(proj: &Project, curr_id: &mut usize) -> BTreeMap<Variable, TermSet> {
@@ -132,9 +169,19 @@ fn generate_fresh_definition(proj: &Project, curr_id: &mut usize) -> BTreeMap<Va
 }
 
 /// Runs reaching definitions on the project and produces a mapping from node index to the Register Context.
-/// The register context can be queried to determine the representing type variable for an accessed register
-pub fn run_analysis(proj: &Project, graph: &Graph) -> HashMap<NodeIndex, RegisterContext> {
-    let cont = Context::new(proj, graph, &proj.program.term.extern_symbols);
+/// The register context can be queried to determine the representing type variable for an accessed register.
+/// The clobber policy determines which registers lose their definitions across calls.
+pub fn run_analysis(
+    proj: &Project,
+    graph: &Graph,
+    clobber_policy: ClobberPolicy,
+) -> HashMap<NodeIndex, RegisterContext> {
+    let cont = Context::new(
+        proj,
+        graph,
+        &proj.program.term.extern_symbols,
+        clobber_policy,
+    );
     let mut computation = forward_interprocedural_fixpoint::create_computation(cont, None);
 
     let entry_sub_to_entry_node_map =
@@ -160,6 +207,7 @@ pub fn run_analysis(proj: &Project, graph: &Graph) -> HashMap<NodeIndex, Registe
 
     computation.compute();
     let shared_project = Rc::new(proj.clone());
+    let loaded_definitions = Rc::new(collect_loaded_definitions(proj));
     computation
         .node_values()
         .iter()
@@ -176,12 +224,22 @@ pub fn run_analysis(proj: &Project, graph: &Graph) -> HashMap<NodeIndex, Registe
             .map(|v| {
                 (
                     *ind,
-                    RegisterContext::new(v.deref().deref().clone(), &shared_project),
+                    RegisterContext::new(
+                        v.deref().deref().clone(),
+                        &shared_project,
+                        &loaded_definitions,
+                        clobber_policy,
+                    ),
                 )
             }),
             NodeValue::Value(v) => Some((
                 *ind,
-                RegisterContext::new(v.deref().deref().clone(), &shared_project),
+                RegisterContext::new(
+                    v.deref().deref().clone(),
+                    &shared_project,
+                    &loaded_definitions,
+                    clobber_policy,
+                ),
             )),
         })
         .collect()
@@ -1,8 +1,10 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
 
 use cwe_checker_lib::intermediate_representation::Arg;
-use cwe_checker_lib::intermediate_representation::{Expression, Variable};
+use cwe_checker_lib::intermediate_representation::{Expression, Tid, Variable};
 
+use crate::analysis::memory_returns::MemoryReturn;
 use crate::constraint_generation::{ArgTvar, NodeContextMapping, SubprocedureLocators};
 
 #[derive(Clone)]
@@ -10,6 +12,8 @@ use crate::constraint_generation::{ArgTvar, NodeContextMapping, SubprocedureLoca
 pub struct ProcedureContext {
     /// The procedure context doesnt change. It only needs to know about the stack variable for this project.
     pub stack_pointer: Variable,
+    /// The results each subprocedure returns through memory.
+    pub memory_returns: Rc<HashMap<Tid, Vec<MemoryReturn>>>,
 }
 
 impl NodeContextMapping for ProcedureContext {
@@ -57,8 +61,8 @@ impl SubprocedureLocators for ProcedureContext {
                 // Therefore the offset is safe for our current frame.
 
                 // If it's a formal this is still valid because we add the base for that frame but the frame still points to the same 0 point.
-                // Returns are less clear
-                // TODO(ian): examine returns (returns cant be on the stack so do we care)?
+                // Returns are less clear, results returned through memory are instead found by [crate::analysis::memory_returns]
+                // and linked through the pointer parameter.
                 let accessed_pointers = points_to.points_to(address, *size);
 
                 accessed_pointers
@@ -68,4 +72,8 @@ impl SubprocedureLocators for ProcedureContext {
             }
         }
     }
+
+    fn get_memory_returns(&self, sub: &Tid) -> Vec<MemoryReturn> {
+        self.memory_returns.get(sub).cloned().unwrap_or_default()
+    }
 }
@@ -62,7 +62,7 @@ pub struct InterestingVar {
 
 impl Display for InterestingVar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}_{}", self.tv, self.dir)
+        write!(f, "{}/{}", self.tv, self.dir)
     }
 }
 
@@ -351,10 +351,11 @@ pub fn parse_typvarnode(input: &str) -> IResult<&str, FiniteState> {
 }
 
 /// Parses a finite state which may be a start, end, or type variable state.
+/// Start and end may be written in either case.
 pub fn parse_finite_state(input: &str) -> IResult<&str, FiniteState> {
     alt((
-        map(tag("start"), |_| FiniteState::Start),
-        map(tag("end"), |_| FiniteState::End),
+        map(alt((tag("start"), tag("START"))), |_| FiniteState::Start),
+        map(alt((tag("end"), tag("END"))), |_| FiniteState::End),
         parse_typvarnode,
     ))(input)
 }
@@ -381,7 +382,7 @@ pub fn parse_edge_type(input: &str) -> IResult<&str, FSAEdge> {
 
     alt((
         map(tag("1"), |_| FSAEdge::Success),
-        map(tag("1"), |_| FSAEdge::Failed),
+        map(tag("0"), |_| FSAEdge::Failed),
         parse_push_pop,
     ))(input)
 }
@@ -1345,15 +1346,20 @@ impl FSA {
 #[cfg(test)]
 mod tests {
     use super::StackSymbol;
-    use super::{parse_finite_state, ControlState, Rule, TypeVarControlState, VHat};
+    use super::{
+        parse_edge_type, parse_finite_state, parse_stack_symbol, ControlState, Rule,
+        TypeVarControlState, VHat,
+    };
 
     use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
 
     use std::{collections::BTreeSet, iter::FromIterator, vec};
 
     use crate::util::FileDebugLogger;
     use crate::{
         constraints::{
+            test::{arb_field_label, arb_field_labels, arb_type_variable},
             ConstraintSet, DerivedTypeVar, FieldLabel, SubtypeConstraint, TyConstraint,
             TypeVariable, Variance,
         },
@@ -1407,6 +1413,72 @@ mod tests {
         (constraints, context)
     }
 
+    fn arb_variance() -> impl Strategy<Value = Variance> {
+        prop_oneof![Just(Variance::Covariant), Just(Variance::Contravariant)]
+    }
+
+    fn arb_interesting_var() -> impl Strategy<Value = InterestingVar> {
+        (
+            arb_type_variable(),
+            prop_oneof![Just(Direction::Lhs), Just(Direction::Rhs)],
+        )
+            .prop_map(|(tv, dir)| InterestingVar { tv, dir })
+    }
+
+    fn arb_finite_state() -> impl Strategy<Value = FiniteState> {
+        let vhat = prop_oneof![
+            arb_interesting_var().prop_map(VHat::Interesting),
+            arb_type_variable().prop_map(VHat::Uninteresting),
+        ];
+        prop_oneof![
+            Just(FiniteState::Start),
+            Just(FiniteState::End),
+            (vhat, arb_variance(), arb_field_labels()).prop_map(
+                |(dt_var, variance, access_path)| FiniteState::Tv(TypeVarNode {
+                    base_var: TypeVarControlState { dt_var, variance },
+                    access_path,
+                })
+            ),
+        ]
+    }
+
+    fn arb_stack_symbol() -> impl Strategy<Value = StackSymbol> {
+        prop_oneof![
+            arb_field_label().prop_map(StackSymbol::Label),
+            (arb_interesting_var(), arb_variance())
+                .prop_map(|(iv, var)| StackSymbol::InterestingVar(iv, var)),
+        ]
+    }
+
+    fn arb_fsa_edge() -> impl Strategy<Value = FSAEdge> {
+        prop_oneof![
+            arb_stack_symbol().prop_map(FSAEdge::Push),
+            arb_stack_symbol().prop_map(FSAEdge::Pop),
+            Just(FSAEdge::Success),
+            Just(FSAEdge::Failed),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn finite_states_round_trip(st in arb_finite_state()) {
+            let printed = st.to_string();
+            prop_assert_eq!(parse_finite_state(&printed), Ok(("", st)));
+        }
+
+        #[test]
+        fn stack_symbols_round_trip(symb in arb_stack_symbol()) {
+            let printed = symb.to_string();
+            prop_assert_eq!(parse_stack_symbol(&printed), Ok(("", symb)));
+        }
+
+        #[test]
+        fn fsa_edges_round_trip(edge in arb_fsa_edge()) {
+            let printed = edge.to_string();
+            prop_assert_eq!(parse_edge_type(&printed), Ok(("", edge)));
+        }
+    }
+
     fn get_subtys(cons: &ConstraintSet) -> Vec<&SubtypeConstraint> {
         cons.iter()
             .filter_map(|x| {
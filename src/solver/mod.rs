@@ -12,3 +12,6 @@ pub mod scc_constraint_generation;
 
 /// Provides oeprations on dfas that support type sketch solving
 pub mod dfa_operations;
+
+/// Replays the simplification of an scc from a logged constraint repro file and shrinks failing constraint sets.
+pub mod replay;
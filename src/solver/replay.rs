@@ -0,0 +1,246 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use cwe_checker_lib::intermediate_representation::Tid;
+
+use super::{
+    scc_constraint_generation::LatticeInfo,
+    type_lattice::{CustomLatticeElement, EnumeratedNamedLattice, NamedLattice},
+    type_sketch::{insert_dtv, LatticeBounds, SketchBuilder, SketchGraph},
+};
+use crate::{
+    constraint_dsl::default_address,
+    constraints::{ConstraintSet, TyConstraint, TypeVariable, VariableManager},
+    util::{constraint_set_to_subtys, FileDebugLogger},
+};
+
+const REPRO_FILE_SUFFIX: &str = "_basic_cons_repro_file";
+
+/// The intermediate and final results of replaying the simplification of an scc.
+pub struct ReplayOutput {
+    /// The constraints after pointers are inferred from add constraints
+    pub resolved_constraints: ConstraintSet,
+    /// The constraints after FSA simplification
+    pub simplified_constraints: ConstraintSet,
+    /// The sketch built from the simplified constraints
+    pub sketch: SketchGraph<LatticeBounds<CustomLatticeElement>>,
+}
+
+/// Reads a constraint set from a repro file logged by scc constraint generation.
+pub fn read_repro_file(repro_file: &str) -> anyhow::Result<ConstraintSet> {
+    let fl = std::fs::File::open(repro_file).context("opening repro file")?;
+    serde_json::from_reader(fl).map_err(|e| anyhow::Error::from(e).context("repro file json"))
+}
+
+/// Gets the tid of the scc's representative from the name of a repro file, <tid>_basic_cons_repro_file.
+pub fn repro_file_tid(repro_file: &str) -> Option<Tid> {
+    let name = Path::new(repro_file)
+        .file_name()?
+        .to_str()?
+        .strip_suffix(REPRO_FILE_SUFFIX)?;
+    Some(Tid::create(name.to_owned(), default_address(name)))
+}
+
+/// Reruns pointer inference, FSA simplification, and sketch building on the basic constraints of an scc in isolation.
+/// The scc is simplified relative to the same interesting variables as when generating a signature.
+pub fn replay_scc(
+    cs_set: &ConstraintSet,
+    scc: &[Tid],
+    lattice: &EnumeratedNamedLattice,
+    weakest_integral_type: &TypeVariable,
+    mut debug_dir: FileDebugLogger,
+) -> anyhow::Result<ReplayOutput> {
+    let type_lattice_elements = lattice
+        .get_nds()
+        .iter()
+        .map(|(name, _elem)| TypeVariable::new(name.clone()))
+        .collect::<HashSet<_>>();
+
+    let lattice_info = LatticeInfo::new(
+        lattice,
+        type_lattice_elements.clone(),
+        lattice
+            .get_elem(&weakest_integral_type.get_name())
+            .ok_or_else(|| anyhow!("the weak integer type is not in the lattice"))?,
+    );
+    let mut vman = VariableManager::new();
+    let (resolved_constraints, simplified_constraints) = lattice_info.simplify_scc_constraints(
+        cs_set,
+        scc,
+        BTreeSet::new(),
+        &mut debug_dir,
+        &mut vman,
+    )?;
+
+    let sketch = SketchBuilder::new(
+        lattice,
+        &type_lattice_elements,
+        &|dtv, mpgrph| {
+            insert_dtv(lattice, mpgrph, dtv.clone());
+            Ok(())
+        },
+        debug_dir,
+    )
+    .build_and_label_constraints_representing(
+        scc,
+        &constraint_set_to_subtys(&simplified_constraints),
+    )?;
+
+    Ok(ReplayOutput {
+        resolved_constraints,
+        simplified_constraints,
+        sketch,
+    })
+}
+
+fn without_chunk(chunks: &[Vec<TyConstraint>], skipped: usize) -> Vec<TyConstraint> {
+    chunks
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != skipped)
+        .flat_map(|(_, chunk)| chunk.iter().cloned())
+        .collect()
+}
+
+/// Shrinks a constraint set to a 1-minimal subset on which the predicate still holds, using delta debugging (ddmin).
+/// Removing any single constraint from the result makes the predicate fail.
+pub fn shrink_constraints(
+    cs_set: &ConstraintSet,
+    mut still_fails: impl FnMut(&ConstraintSet) -> anyhow::Result<bool>,
+) -> anyhow::Result<ConstraintSet> {
+    let mut test = |cons: &[TyConstraint]| {
+        still_fails(&ConstraintSet::from(
+            cons.iter().cloned().collect::<BTreeSet<_>>(),
+        ))
+    };
+
+    let mut current = cs_set.iter().cloned().collect::<Vec<_>>();
+    if !test(&current)? {
+        return Err(anyhow!(
+            "the predicate does not hold on the original constraints"
+        ));
+    }
+
+    let mut granularity = 2;
+    while current.len() >= 2 {
+        let chunk_size = (current.len() + granularity - 1) / granularity;
+        let chunks = current
+            .chunks(chunk_size)
+            .map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+
+        let mut reduced = None;
+        for chunk in chunks.iter() {
+            if test(chunk)? {
+                reduced = Some((chunk.clone(), 2));
+                break;
+            }
+        }
+
+        // a complement of a single chunk is the chunk we already tried
+        if reduced.is_none() && chunks.len() > 2 {
+            for skipped in 0..chunks.len() {
+                let complement = without_chunk(&chunks, skipped);
+                if test(&complement)? {
+                    reduced = Some((complement, std::cmp::max(granularity - 1, 2)));
+                    break;
+                }
+            }
+        }
+
+        match reduced {
+            Some((next, next_granularity)) => {
+                current = next;
+                granularity = next_granularity;
+            }
+            None if granularity >= current.len() => break,
+            None => granularity = std::cmp::min(granularity * 2, current.len()),
+        }
+    }
+
+    Ok(ConstraintSet::from(
+        current.into_iter().collect::<BTreeSet<_>>(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use cwe_checker_lib::intermediate_representation::Tid;
+
+    use super::{read_repro_file, replay_scc, repro_file_tid, shrink_constraints};
+    use crate::{
+        constraints::parse_constraint_set, inference_job::InferenceJob, util::FileDebugLogger,
+    };
+
+    fn test_data_dir(pth: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("sketch_test_files");
+        d.push(pth);
+        d.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn shrinks_to_the_failing_constraints() {
+        let (_, cs_set) = parse_constraint_set(
+            "
+            a <= b
+            b <= c
+            c <= d
+            d.load <= e
+            e <= f.store
+            f <= g
+            g <= h
+            h.σ32@0 <= i
+            ",
+        )
+        .unwrap();
+        let (_, expected) = parse_constraint_set(
+            "
+            b <= c
+            g <= h
+            ",
+        )
+        .unwrap();
+
+        let mut num_tests = 0;
+        let shrunk = shrink_constraints(&cs_set, |cons| {
+            num_tests += 1;
+            Ok(expected.iter().all(|needed| cons.contains(needed)))
+        })
+        .unwrap();
+
+        assert_eq!(shrunk, expected);
+        assert!(num_tests < 2usize.pow(cs_set.len() as u32));
+        assert!(shrink_constraints(&cs_set, |_| Ok(false)).is_err());
+        assert_eq!(shrink_constraints(&cs_set, |_| Ok(true)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn replays_logged_repro_file() {
+        let repro_file = test_data_dir("sub_0015c310_basic_cons_repro_file");
+        let tid = repro_file_tid(&repro_file).expect("repro file names its tid");
+        assert_eq!(
+            tid,
+            Tid::create("sub_0015c310".to_owned(), "0015c310".to_owned())
+        );
+
+        let cs_set = read_repro_file(&repro_file).expect("read repro file");
+        let (lattice, weakest_integral_type) =
+            InferenceJob::parse_lattice_json(&test_data_dir("lattice.json"), vec![])
+                .expect("parse lattice");
+
+        let output = replay_scc(
+            &cs_set,
+            &[tid],
+            &lattice,
+            &weakest_integral_type,
+            FileDebugLogger::default(),
+        )
+        .expect("replay should succeed");
+        assert!(!output.simplified_constraints.is_empty());
+        assert!(output.resolved_constraints.is_superset(&cs_set));
+    }
+}
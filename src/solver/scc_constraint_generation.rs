@@ -11,7 +11,10 @@ use cwe_checker_lib::{
     intermediate_representation::{ExternSymbol, Tid},
 };
 use itertools::Itertools;
-use petgraph::{graph::NodeIndex, EdgeDirection::Outgoing};
+use petgraph::{
+    graph::NodeIndex,
+    EdgeDirection::{Incoming, Outgoing},
+};
 
 use super::{
     constraint_graph::{RuleContext, FSA},
@@ -21,8 +24,8 @@ use super::{
 use crate::{
     analysis::callgraph::{self, CallGraph},
     constraint_generation::{
-        self, tid_to_tvar, ConstantResolver, NodeContext, PointsToMapping, RegisterMapping,
-        SubprocedureLocators,
+        self, is_comparison_tvar, tid_to_tvar, ConstantResolver, NodeContext, PointsToMapping,
+        RegisterMapping, SubprocedureLocators,
     },
     constraints::{
         AddConstraint, ConstraintSet, DerivedTypeVar, FieldLabel, SubtypeConstraint, TyConstraint,
@@ -62,6 +65,36 @@ pub struct SCCConstraints {
     pub constraints: BTreeSet<SubtypeConstraint>,
 }
 
+/// Simplification results retained from a previous run so that a later run only needs to
+/// resimplify the sccs affected by newly added constraints.
+#[derive(Default)]
+pub struct SimplificationCache {
+    signatures: HashMap<Vec<Tid>, Rc<Signature>>,
+    scc_constraints: HashMap<Vec<Tid>, BTreeSet<SubtypeConstraint>>,
+    stats: CacheStats,
+}
+
+/// The number of sccs whose constraints were reused from the cache or resimplified during the last run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Sccs whose simplified constraints were taken from the cache
+    pub reused: usize,
+    /// Sccs that were simplified during the run
+    pub resimplified: usize,
+}
+
+impl SimplificationCache {
+    /// Checks if no simplification results have been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty() && self.scc_constraints.is_empty()
+    }
+
+    /// Gets the cache hits and misses of the last run.
+    pub fn get_stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
 // There are only two types in the world :)
 #[derive(Clone, Copy, Debug)]
 enum TypeLabels {
@@ -361,6 +394,99 @@ where
 
         Ok(next_cs_set)
     }
+
+    /// Infers pointers in the basic constraints of an scc, then simplifies the resolved constraints down to the scc's interesting variables:
+    /// the base interesting variables, the scc's tids, the globals and comparisons the scc refers to, and the lattice elements.
+    /// Returns the resolved constraints along with the simplified constraints.
+    pub(crate) fn simplify_scc_constraints(
+        &self,
+        basic_cons: &ConstraintSet,
+        scc: &[Tid],
+        base_interesting_variables: BTreeSet<TypeVariable>,
+        debug_dir: &mut FileDebugLogger,
+        vman: &mut VariableManager,
+    ) -> anyhow::Result<(ConstraintSet, ConstraintSet)> {
+        let repr_tid = scc
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("every scc must have a node"))?;
+
+        let resolved_cs_set = self.infer_pointers(basic_cons, debug_dir)?;
+
+        let diff = ConstraintSet::from(
+            resolved_cs_set
+                .difference(basic_cons)
+                .cloned()
+                .collect::<BTreeSet<_>>(),
+        );
+
+        debug_dir.log_to_fname(&format!("{}_ptr_diff", repr_tid.get_str_repr()), &|| &diff)?;
+
+        debug_dir.log_to_fname(
+            &format!("{}_ptr_resolved_cons", repr_tid.get_str_repr()),
+            &|| &resolved_cs_set,
+        )?;
+
+        // TODO(Ian): I dislike this collaboration but constraint generation is when we discover which globals we are going to need. Ideally when we lift constraint
+        // generation out we can seperate this out.
+        let new_interesting_vars = base_interesting_variables
+            .into_iter()
+            .chain(scc.iter().map(tid_to_tvar))
+            .chain(
+                resolved_cs_set
+                    .variables()
+                    .filter(|x| x.get_base_variable().is_global())
+                    .map(|global| global.get_base_variable().clone()),
+            )
+            // comparisons are kept so the sketch node of a compared value can be annotated with its constants
+            .chain(
+                resolved_cs_set
+                    .variables()
+                    .filter(|x| is_comparison_tvar(x.get_base_variable()))
+                    .map(|cmp| cmp.get_base_variable().clone()),
+            )
+            .chain(self.type_lattice_elements.iter().cloned());
+
+        let new_rcontext = RuleContext::new(new_interesting_vars.collect());
+
+        debug_dir.log_to_fname(
+            &format!("{}_modified_interesting_vars", repr_tid.get_str_repr()),
+            &|| {
+                new_rcontext
+                    .get_interesting()
+                    .iter()
+                    .map(|var| var.get_name())
+                    .join("\n")
+            },
+        )?;
+
+        let mut fsa = FSA::new(&resolved_cs_set, &new_rcontext)?;
+
+        debug_dir.log_to_fname(
+            &format!("{}_fsa_unsimplified.dot", repr_tid.get_str_repr()),
+            &|| &fsa,
+        )?;
+
+        fsa.simplify_graph(repr_tid.get_str_repr(), debug_dir, vman)?;
+
+        debug_dir.log_to_fname(
+            &format!("{}_fsa_simplified.dot", repr_tid.get_str_repr()),
+            &|| &fsa,
+        )?;
+
+        let cons = fsa.walk_constraints();
+        // forget add constraints at scc barriers
+        let mut cons = cons.forget_add_constraints();
+
+        // Adds var constraint simulations so if we know about parameters but werent able to relate them to interesting variables we still remember they exist
+        insert_missed_formals(&mut cons, &resolved_cs_set);
+
+        debug_dir.log_to_fname(
+            &format!("{}_simplified_constraints", repr_tid.get_str_repr()),
+            &|| &cons,
+        )?;
+
+        Ok((resolved_cs_set, cons))
+    }
 }
 
 fn get_formals_in(cs_set: &ConstraintSet) -> impl Iterator<Item = DerivedTypeVar> + '_ {
@@ -534,10 +660,8 @@ where
                 .collect::<BTreeSet<_>>(),
         );
 
-        let repr_tid = tid_filter
-            .iter()
-            .next()
-            .expect("every scc must have a node");
+        // the first tid names the scc's debug files so a repro file can be replayed under the same name
+        let repr_tid = scc.first().expect("every scc must have a node");
         self.debug_dir.log_to_fname(
             &format!("{}_basic_cons_no_sigs", repr_tid.get_str_repr()),
             &|| &basic_cons,
@@ -561,75 +685,12 @@ where
             &|| serde_json::to_string(&basic_cons).expect("should be able to serialize cons"),
         )?;
 
-        let resolved_cs_set = self
-            .lattice_def
-            .infer_pointers(&basic_cons, &self.debug_dir)?;
-
-        let diff = ConstraintSet::from(
-            resolved_cs_set
-                .difference(&basic_cons)
-                .cloned()
-                .collect::<BTreeSet<_>>(),
-        );
-
-        self.debug_dir
-            .log_to_fname(&format!("{}_ptr_diff", repr_tid.get_str_repr()), &|| &diff)?;
-
-        self.debug_dir.log_to_fname(
-            &format!("{}_ptr_resolved_cons", repr_tid.get_str_repr()),
-            &|| &resolved_cs_set,
-        )?;
-
-        // TODO(Ian): I dislike this collaboration but constraint generation is when we discover which globals we are going to need. Ideally when we lift constraint
-        // generation out we can seperate this out.
-        let new_interesting_vars = base_interesting_variables
-            .into_iter()
-            .chain(tid_filter.iter().map(tid_to_tvar))
-            .chain(
-                resolved_cs_set
-                    .variables()
-                    .filter(|x| x.get_base_variable().is_global())
-                    .map(|global| global.get_base_variable().clone()),
-            )
-            .chain(self.lattice_def.type_lattice_elements.iter().cloned());
-
-        let new_rcontext = RuleContext::new(new_interesting_vars.collect());
-
-        self.debug_dir.log_to_fname(
-            &format!("{}_modified_interesting_vars", repr_tid.get_str_repr()),
-            &|| {
-                new_rcontext
-                    .get_interesting()
-                    .iter()
-                    .map(|var| var.get_name())
-                    .join("\n")
-            },
-        )?;
-
-        let mut fsa = FSA::new(&resolved_cs_set, &new_rcontext)?;
-
-        self.debug_dir.log_to_fname(
-            &format!("{}_fsa_unsimplified.dot", repr_tid.get_str_repr()),
-            &|| &fsa,
-        )?;
-
-        fsa.simplify_graph(repr_tid.get_str_repr(), &mut self.debug_dir, self.vman)?;
-
-        self.debug_dir.log_to_fname(
-            &format!("{}_fsa_simplified.dot", repr_tid.get_str_repr()),
-            &|| &fsa,
-        )?;
-
-        let cons = fsa.walk_constraints();
-        // forget add constraints at scc barriers
-        let mut cons = cons.forget_add_constraints();
-
-        // Adds var constraint simulations so if we know about parameters but werent able to relate them to interesting variables we still remember they exist
-        insert_missed_formals(&mut cons, &resolved_cs_set);
-
-        self.debug_dir.log_to_fname(
-            &format!("{}_simplified_constraints", repr_tid.get_str_repr()),
-            &|| &cons,
+        let (_, cons) = self.lattice_def.simplify_scc_constraints(
+            &basic_cons,
+            scc,
+            base_interesting_variables,
+            &mut self.debug_dir,
+            self.vman,
         )?;
 
         let sub_cons = cons
@@ -650,7 +711,13 @@ where
         scc: &[Tid],
         state: &HashMap<TypeVariable, Rc<Signature>>,
     ) -> anyhow::Result<Signature> {
-        self.simplify_scc(scc, state, BTreeSet::new())
+        let mut sig = self.simplify_scc(scc, state, BTreeSet::new())?;
+        // comparisons reach callers as annotations on the callee's sketch rather than through its signature
+        sig.cs_set.retain(|cons| {
+            !is_comparison_tvar(cons.lhs.get_base_variable())
+                && !is_comparison_tvar(cons.rhs.get_base_variable())
+        });
+        Ok(sig)
     }
 
     fn simplify_scc_cons(
@@ -668,15 +735,52 @@ where
     /// Runs the computation, generating FSA simplified scc constraints for each.
     /// Temporary sketches are created to propogate pointer information.
     pub fn get_simplified_constraints(&mut self) -> anyhow::Result<Vec<SCCConstraints>> {
+        self.get_simplified_constraints_incremental(
+            &mut SimplificationCache::default(),
+            &BTreeSet::new(),
+        )
+    }
+
+    /// Generates simplified scc constraints, reusing results from the cache for sccs that are unaffected by changes to the given tids.
+    /// An scc is resimplified if it contains a changed tid, has no cached result, or transitively calls such an scc.
+    /// The cache is updated with the new results.
+    pub fn get_simplified_constraints_incremental(
+        &mut self,
+        cache: &mut SimplificationCache,
+        changed_tids: &BTreeSet<Tid>,
+    ) -> anyhow::Result<Vec<SCCConstraints>> {
         let condensed_cg = callgraph::CGOrdering::new(&self.cg)?;
-        let sigs = self.get_signatures(&condensed_cg)?;
+        let invalidated = get_invalidated_sccs(&condensed_cg, cache, changed_tids);
+        let sigs = self.get_signatures(&condensed_cg, cache, &invalidated)?;
+        cache.stats = CacheStats::default();
         condensed_cg
             .topo_order
             .iter()
-            .map(|ndidx| {
+            .map(|ndidx| -> anyhow::Result<SCCConstraints> {
                 let scc = &condensed_cg.condensed_cg[*ndidx];
-                self.simplify_scc_cons(scc, &sigs).map(|s| SCCConstraints {
-                    constraints: s.cs_set,
+                let cached = cache
+                    .scc_constraints
+                    .get(scc)
+                    .filter(|_| !invalidated.contains(ndidx))
+                    .cloned();
+
+                let constraints = match cached {
+                    Some(constraints) => {
+                        cache.stats.reused += 1;
+                        constraints
+                    }
+                    None => {
+                        cache.stats.resimplified += 1;
+                        let constraints = self.simplify_scc_cons(scc, &sigs)?.cs_set;
+                        cache
+                            .scc_constraints
+                            .insert(scc.clone(), constraints.clone());
+                        constraints
+                    }
+                };
+
+                Ok(SCCConstraints {
+                    constraints,
                     scc: scc.clone(),
                 })
             })
@@ -686,12 +790,28 @@ where
     fn get_signatures(
         &mut self,
         condensed_cg: &callgraph::CGOrdering,
+        cache: &mut SimplificationCache,
+        invalidated: &HashSet<NodeIndex>,
     ) -> anyhow::Result<HashMap<TypeVariable, Rc<Signature>>> {
         // holds a shared reference to the sig for an scc from each callee so the callee can be looked up
         let mut state: HashMap<TypeVariable, Rc<Signature>> = HashMap::new();
         for nd in condensed_cg.get_reverse_topo() {
             let scc = &condensed_cg.condensed_cg[nd];
-            let sig = Rc::from(self.simplify_signature(scc, &state)?);
+            let cached = cache
+                .signatures
+                .get(scc)
+                .filter(|_| !invalidated.contains(&nd))
+                .cloned();
+
+            let sig = match cached {
+                Some(sig) => sig,
+                None => {
+                    let sig = Rc::from(self.simplify_signature(scc, &state)?);
+                    cache.signatures.insert(scc.clone(), sig.clone());
+                    sig
+                }
+            };
+
             for tid in scc {
                 state.insert(tid_to_tvar(tid), sig.clone());
             }
@@ -701,6 +821,34 @@ where
     }
 }
 
+/// Collects the sccs that need to be resimplified: sccs with changed tids or without cached results, as well as all of their transitive callers.
+fn get_invalidated_sccs(
+    condensed_cg: &callgraph::CGOrdering,
+    cache: &SimplificationCache,
+    changed_tids: &BTreeSet<Tid>,
+) -> HashSet<NodeIndex> {
+    let cg = &condensed_cg.condensed_cg;
+    let mut worklist = cg
+        .node_indices()
+        .filter(|idx| {
+            let scc = &cg[*idx];
+            scc.iter().any(|tid| changed_tids.contains(tid))
+                || !cache.signatures.contains_key(scc)
+                || !cache.scc_constraints.contains_key(scc)
+        })
+        .collect::<Vec<_>>();
+
+    let mut invalidated = HashSet::new();
+    while let Some(nd) = worklist.pop() {
+        if invalidated.insert(nd) {
+            // edges go from caller to callee
+            worklist.extend(cg.neighbors_directed(nd, Incoming));
+        }
+    }
+
+    invalidated
+}
+
 /// Signatures present an external view of a function as type constants, formals, and globals as base variables
 struct Signature {
     cs_set: BTreeSet<SubtypeConstraint>,
@@ -5,6 +5,7 @@ use petgraph::{
     visit::{Dfs, GraphRef, IntoNeighbors, VisitMap, Visitable, Walker},
     Directed, Graph,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
@@ -66,8 +67,30 @@ impl NamedLattice<CustomLatticeElement> for EnumeratedNamedLattice {
     }
 }
 
+/// The single precision float type added to every lattice.
+pub const FLOAT_TYPE: &str = "float";
+/// The double precision float type added to every lattice.
+pub const DOUBLE_TYPE: &str = "double";
+/// The extended precision float type added to every lattice.
+pub const LONG_DOUBLE_TYPE: &str = "long_double";
+
+/// The type of the characters of a string, merged into every lattice.
+pub const STRING_TYPE: &str = "string";
+/// The type of the characters of a string in read-only memory, a subtype of [STRING_TYPE].
+pub const CONST_STRING_TYPE: &str = "const_string";
+
+/// Gets the float type for a value with the given size in bytes.
+pub fn float_type_for_size(bytes: u64) -> Option<&'static str> {
+    match bytes {
+        4 => Some(FLOAT_TYPE),
+        8 => Some(DOUBLE_TYPE),
+        10 | 12 | 16 => Some(LONG_DOUBLE_TYPE),
+        _ => None,
+    }
+}
+
 /// User input that defines a complete lattice.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 pub struct LatticeDefinition {
     less_than_relations_between_handles: Vec<(String, String)>,
     top_handle: String,
@@ -83,6 +106,39 @@ impl LatticeDefinition {
         Ok(self)
     }
 
+    /// Adds the float types between the bottom and top handles, unless the lattice already has them.
+    pub fn with_float_types(mut self) -> LatticeDefinition {
+        let existing: HashSet<String> = self
+            .less_than_relations_between_handles
+            .iter()
+            .flat_map(|(x, y)| vec![x.clone(), y.clone()])
+            .collect();
+
+        for ty in [FLOAT_TYPE, DOUBLE_TYPE, LONG_DOUBLE_TYPE].iter() {
+            if !existing.contains(*ty) {
+                self.less_than_relations_between_handles
+                    .push((self.bottom_handle.clone(), ty.to_string()));
+                self.less_than_relations_between_handles
+                    .push((ty.to_string(), self.top_handle.clone()));
+            }
+        }
+        self
+    }
+
+    /// Gets the string types to merge into this lattice. The characters of a string are integers.
+    pub fn string_types(&self) -> LatticeDefinition {
+        LatticeDefinition::new(
+            vec![
+                (self.bottom_handle.clone(), CONST_STRING_TYPE.to_owned()),
+                (CONST_STRING_TYPE.to_owned(), STRING_TYPE.to_owned()),
+                (STRING_TYPE.to_owned(), self.weakest_integral_type.clone()),
+            ],
+            self.top_handle.clone(),
+            self.bottom_handle.clone(),
+            self.weakest_integral_type.clone(),
+        )
+    }
+
     /// Creates a new user defined lattice from element names.
     pub fn new(
         less_than_relations_between_handles: Vec<(String, String)>,
@@ -230,6 +286,42 @@ impl LatticeDefinition {
         Self::create_reachable_sets(&lt_graph)
     }
 
+    /// Checks that the definition can be generated into a lattice: the less than relation is acyclic,
+    /// every element is between the bottom and top handles, and the weakest integral type is an element.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let lt_graph = self.get_lt_graph();
+        if petgraph::algo::is_cyclic_directed(&lt_graph) {
+            return Err(anyhow::anyhow!("The less than relation has a cycle"));
+        }
+
+        let less_than = Self::create_reachable_sets(&lt_graph);
+        for handle in [
+            &self.top_handle,
+            &self.bottom_handle,
+            &self.weakest_integral_type,
+        ]
+        .iter()
+        {
+            if !less_than.contains_key(*handle) {
+                return Err(anyhow::anyhow!("{} is not in the lattice", handle));
+            }
+        }
+
+        let greater_than_bottom = &less_than[&self.bottom_handle];
+        for (elem, greater) in less_than.iter() {
+            if !greater.contains(&self.top_handle) || !greater_than_bottom.contains(elem) {
+                return Err(anyhow::anyhow!(
+                    "{} is not between {} and {}",
+                    elem,
+                    self.bottom_handle,
+                    self.top_handle
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     /// From a user definition generates a named lattice that has joins, meets, and a lookup table for the less than relation.
     pub fn generate_lattice(&self) -> EnumeratedNamedLattice {
         let join = Rc::new(self.create_join_table());
@@ -383,3 +475,107 @@ impl MeetSemilattice for CustomLatticeElement {
             .expect("All relations should be defined in table")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        LatticeDefinition, NamedLattice, CONST_STRING_TYPE, DOUBLE_TYPE, FLOAT_TYPE,
+        LONG_DOUBLE_TYPE, STRING_TYPE,
+    };
+
+    fn relations(rels: &[(&str, &str)]) -> Vec<(String, String)> {
+        rels.iter()
+            .map(|(x, y)| (x.to_string(), y.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn validates_lattice_definitions() {
+        let valid = LatticeDefinition::new(
+            relations(&[
+                ("bottom", "int"),
+                ("bottom", "char"),
+                ("int", "top"),
+                ("char", "top"),
+            ]),
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        );
+        assert!(valid.validate().is_ok());
+
+        let cyclic = LatticeDefinition::new(
+            relations(&[("bottom", "int"), ("int", "top"), ("top", "int")]),
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        );
+        assert!(cyclic.validate().is_err());
+
+        let unbounded = LatticeDefinition::new(
+            relations(&[("bottom", "int"), ("int", "top"), ("bottom", "char")]),
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        );
+        assert!(unbounded.validate().is_err());
+
+        let missing_integral = LatticeDefinition::new(
+            relations(&[("bottom", "top")]),
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        );
+        assert!(missing_integral.validate().is_err());
+    }
+
+    #[test]
+    fn float_types_are_added_once() {
+        let def = LatticeDefinition::new(
+            relations(&[
+                ("bottom", "int"),
+                ("bottom", "double"),
+                ("int", "top"),
+                ("double", "top"),
+            ]),
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        )
+        .with_float_types();
+        assert!(def.validate().is_ok());
+        assert_eq!(
+            def.less_than_relations_between_handles
+                .iter()
+                .filter(|(x, y)| x == DOUBLE_TYPE || y == DOUBLE_TYPE)
+                .count(),
+            2
+        );
+
+        let lattice = def.generate_lattice();
+        for ty in [FLOAT_TYPE, DOUBLE_TYPE, LONG_DOUBLE_TYPE].iter() {
+            let elem = lattice
+                .get_elem(ty)
+                .expect("float types should be in the lattice");
+            assert!(lattice.bot() < elem && elem < lattice.top());
+        }
+    }
+
+    #[test]
+    fn string_types_are_integers() {
+        let def = LatticeDefinition::new(
+            relations(&[("bottom", "int"), ("int", "top")]),
+            "top".to_owned(),
+            "bottom".to_owned(),
+            "int".to_owned(),
+        );
+        let def = def.string_types().merge_with_other(def).unwrap();
+        assert!(def.validate().is_ok());
+
+        let lattice = def.generate_lattice();
+        let const_string = lattice.get_elem(CONST_STRING_TYPE).unwrap();
+        let string = lattice.get_elem(STRING_TYPE).unwrap();
+        assert!(const_string < string);
+        assert!(string < lattice.get_elem("int").unwrap());
+    }
+}
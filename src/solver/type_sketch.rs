@@ -5,6 +5,7 @@ use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::str::FromStr;
 use std::{collections::HashMap, hash::Hash};
 
 use alga::general::{AbstractMagma, Additive, JoinSemilattice, Lattice, MeetSemilattice};
@@ -58,10 +59,13 @@ pub trait SketchLabelElement:
 }
 
 /// Labels for the sketch graph that mantain both an upper bound and lower bound on merged type
+/// along with the constants values of the type are compared against.
 #[derive(Clone, PartialEq, Debug, Eq, Serialize, Deserialize)]
 pub struct LatticeBounds<T: Clone + Lattice> {
     upper_bound: T,
     lower_bound: T,
+    #[serde(default)]
+    constants: BTreeSet<i64>,
 }
 
 impl<T> SketchLabelElement for LatticeBounds<T> where T: Clone + Lattice + Display {}
@@ -82,6 +86,15 @@ where
     T: Lattice,
     T: Clone,
 {
+    /// Creates bounds from a lower and an upper bound.
+    pub fn new(lower_bound: T, upper_bound: T) -> Self {
+        Self {
+            upper_bound,
+            lower_bound,
+            constants: BTreeSet::new(),
+        }
+    }
+
     /// Get the upper bound on this lattice element
     pub fn get_upper(&self) -> &T {
         &self.upper_bound
@@ -92,10 +105,25 @@ where
         &self.lower_bound
     }
 
+    /// Get the constants values of this type are compared against
+    pub fn get_constants(&self) -> &BTreeSet<i64> {
+        &self.constants
+    }
+
+    /// Adds constants that values of this type are compared against.
+    pub fn with_constants(&self, constants: &BTreeSet<i64>) -> Self {
+        Self {
+            upper_bound: self.upper_bound.clone(),
+            lower_bound: self.lower_bound.clone(),
+            constants: self.constants.union(constants).cloned().collect(),
+        }
+    }
+
     fn refine_lower(&self, other: &T) -> Self {
         Self {
             upper_bound: self.upper_bound.clone(),
             lower_bound: self.lower_bound.join(other),
+            constants: self.constants.clone(),
         }
     }
 
@@ -103,15 +131,18 @@ where
         Self {
             upper_bound: self.upper_bound.meet(other),
             lower_bound: self.lower_bound.clone(),
+            constants: self.constants.clone(),
         }
     }
 }
 
+// NOTE(Ian): constants are evidence rather than part of the type so merging bounds in either direction keeps all of them
 impl<T: Lattice + Clone> JoinSemilattice for LatticeBounds<T> {
     fn join(&self, other: &Self) -> Self {
         Self {
             upper_bound: self.upper_bound.join(&other.upper_bound),
             lower_bound: self.lower_bound.join(&other.lower_bound),
+            constants: self.constants.union(&other.constants).cloned().collect(),
         }
     }
 }
@@ -121,6 +152,7 @@ impl<T: Lattice + Clone> MeetSemilattice for LatticeBounds<T> {
         Self {
             upper_bound: self.upper_bound.meet(&other.upper_bound),
             lower_bound: self.lower_bound.meet(&other.lower_bound),
+            constants: self.constants.union(&other.constants).cloned().collect(),
         }
     }
 }
@@ -155,6 +187,7 @@ where
         LatticeBounds {
             upper_bound: right.upper_bound.meet(&self.upper_bound),
             lower_bound: right.lower_bound.join(&self.lower_bound),
+            constants: right.constants.union(&self.constants).cloned().collect(),
         }
     }
 }
@@ -385,6 +418,7 @@ pub fn identity_element<T: NamedLattice<U>, U: NamedLatticeElement>(
     LatticeBounds {
         upper_bound: top,
         lower_bound: bot,
+        constants: BTreeSet::new(),
     }
 }
 
@@ -594,6 +628,36 @@ impl TypeLocation {
     }
 }
 
+/// Selects how callsite types are bound to the formal types of callees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolymorphismPolicy {
+    /// Callees are refined by the types of their callsites, callsites only share structurally equal subgraphs with the callee
+    RefineFormals,
+    /// Callee formals are refined and every callsite is unified with the callee's formal
+    Monomorphic,
+    /// Callee formals are left unrefined, callsites only share structurally equal subgraphs with the callee
+    Polymorphic,
+}
+
+impl Default for PolymorphismPolicy {
+    fn default() -> Self {
+        PolymorphismPolicy::RefineFormals
+    }
+}
+
+impl FromStr for PolymorphismPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refine" => Ok(PolymorphismPolicy::RefineFormals),
+            "monomorphic" => Ok(PolymorphismPolicy::Monomorphic),
+            "polymorphic" => Ok(PolymorphismPolicy::Polymorphic),
+            _ => Err(anyhow::anyhow!("Unknown polymorphism policy: {}", s)),
+        }
+    }
+}
+
 /// Creates a structured and labeled sketch graph
 /// This algorithm creates polymorphic function types.
 /// Type information flows up to callers but not down to callees (callees wont be unified).
@@ -607,8 +671,11 @@ pub struct SCCSketchsBuilder<'a, U: NamedLatticeElement, T: NamedLattice<U>> {
     cg: CallGraph,
     lattice: &'a T,
     type_lattice_elements: HashSet<TypeVariable>,
+    /// The constants compared at each comparison type variable
+    comparison_constants: BTreeMap<TypeVariable, BTreeSet<i64>>,
     /// Aliases some type nodes accross sccs to bind polymorphic parameters loc->loc
     parameter_aliases: BTreeMap<TypeLocation, TypeLocation>,
+    polymorphism_policy: PolymorphismPolicy,
 
     debug_dir: FileDebugLogger,
 }
@@ -632,7 +699,9 @@ where
         scc_constraints: Vec<SCCConstraints>,
         lattice: &'a T,
         type_lattice_elements: HashSet<TypeVariable>,
+        comparison_constants: BTreeMap<TypeVariable, BTreeSet<i64>>,
         debug_dir: FileDebugLogger,
+        polymorphism_policy: PolymorphismPolicy,
     ) -> SCCSketchsBuilder<'a, U, T> {
         let scc_signatures = scc_constraints
             .into_iter()
@@ -648,7 +717,9 @@ where
             cg,
             lattice,
             type_lattice_elements,
+            comparison_constants,
             parameter_aliases: BTreeMap::new(),
+            polymorphism_policy,
             debug_dir,
             global_repr: HashMap::new(),
         }
@@ -702,7 +773,8 @@ where
             self.debug_dir.clone(),
         );
 
-        let orig_graph = bldr.build_and_label_constraints_representing(to_reprs, sig)?;
+        let mut orig_graph = bldr.build_and_label_constraints_representing(to_reprs, sig)?;
+        orig_graph.label_comparisons(&self.comparison_constants);
 
         let sk_graph = Rc::new(orig_graph);
 
@@ -769,6 +841,7 @@ where
         resulting_graph: &mut StableDiGraph<LatticeBounds<U>, FieldLabel>,
         resulting_labeling: &mut HashMap<DerivedTypeVar, NodeIndex>,
         sg: SketchGraph<LatticeBounds<U>>,
+        label_callsites: bool,
     ) {
         for nd_idx in sg.quotient_graph.get_graph().node_indices() {
             let weight = &sg.quotient_graph.get_graph()[nd_idx];
@@ -813,7 +886,7 @@ where
         // We should only label base variables imo. This means we look through the scc and find the sccs base variables within the graph
         for (dtv, tgt_idx) in sg.quotient_graph.get_node_mapping().iter() {
             if dtv.get_field_labels().is_empty()
-                && dtv.get_base_variable().get_cs_tag().is_none()
+                && (label_callsites || dtv.get_base_variable().get_cs_tag().is_none())
                 // Dont want labels for concrete types, no need to solve for them if not used.
                 && !self.type_lattice_elements.contains(dtv.get_base_variable())
                 && !dtv.is_global()
@@ -857,6 +930,21 @@ where
 
     /// After binding polymorphic types we can build a singular sketch graph representing all types. The only labels that are preserved are scc internal dtvs
     pub fn build_global_type_graph(&self) -> anyhow::Result<SketchGraph<LatticeBounds<U>>> {
+        self.build_global_type_graph_labeling_callsites(false)
+    }
+
+    /// Builds the global sketch graph as in [SCCSketchsBuilder::build_global_type_graph] but additionally preserves callsite tagged labels,
+    /// so the callee's type instantiated at each callsite is kept in the graph.
+    pub fn build_global_type_graph_with_callsites(
+        &self,
+    ) -> anyhow::Result<SketchGraph<LatticeBounds<U>>> {
+        self.build_global_type_graph_labeling_callsites(true)
+    }
+
+    fn build_global_type_graph_labeling_callsites(
+        &self,
+        label_callsites: bool,
+    ) -> anyhow::Result<SketchGraph<LatticeBounds<U>>> {
         let mut location_to_index: BTreeMap<TypeLocation, NodeIndex> = BTreeMap::new();
         let mut resulting_graph: StableDiGraph<LatticeBounds<U>, FieldLabel> = StableDiGraph::new();
         let mut resulting_labeling: HashMap<DerivedTypeVar, NodeIndex> = HashMap::new();
@@ -880,16 +968,20 @@ where
                 &mut resulting_graph,
                 &mut resulting_labeling,
                 built_sg,
+                label_callsites,
             )
         }
 
         let mp = MappingGraph::from_dfa_and_labeling(resulting_graph);
         let mut final_graph = mp.relable_representative_nodes(resulting_labeling);
 
-        assert!(final_graph
-            .get_node_mapping()
-            .iter()
-            .all(|(x, _)| x.get_base_variable().get_cs_tag().is_none()));
+        assert!(
+            label_callsites
+                || final_graph
+                    .get_node_mapping()
+                    .iter()
+                    .all(|(x, _)| x.get_base_variable().get_cs_tag().is_none())
+        );
 
         final_graph.remove_nodes_unreachable_from_label();
         Ok(SketchGraph {
@@ -1149,6 +1241,23 @@ where
             .quotient_graph
             .get_node(target_dtv)
             .expect("If we replaced in a target dtv then it should exist in the new sketch");
+
+        if self.polymorphism_policy == PolymorphismPolicy::Monomorphic {
+            // Every callsite shares the callee's type, aliasing the root unifies the reachable subgraphs
+            return callsites
+                .into_iter()
+                .map(|(_, callsite_loc)| {
+                    (
+                        TypeLocation::SCCLoc(callsite_loc),
+                        TypeLocation::SCCLoc(SCCLocation {
+                            scc: associated_scc_tids.to_vec(),
+                            target_path: *orig_loc,
+                        }),
+                    )
+                })
+                .collect();
+        }
+
         callsites
             .into_iter()
             .flat_map(|(old_callsite_type, callsite_loc)| {
@@ -1541,6 +1650,10 @@ where
     }
 
     fn bind_polymorphic_types(&mut self) -> anyhow::Result<()> {
+        if self.polymorphism_policy == PolymorphismPolicy::Polymorphic {
+            return Ok(());
+        }
+
         let ordering = callgraph::CGOrdering::new(&self.cg)?;
         ordering.iter().for_each(|(ccg, target_scc_idx, scc_tids)| {
             self.refine_formals(ccg, scc_tids, target_scc_idx)
@@ -1693,6 +1806,32 @@ impl<U: std::cmp::PartialEq> SketchGraph<U> {
     }
 }
 
+impl<T: Lattice + Clone> SketchGraph<LatticeBounds<T>> {
+    /// Labels the nodes of comparison type variables with the constants compared at that comparison.
+    pub fn label_comparisons(
+        &mut self,
+        comparison_constants: &BTreeMap<TypeVariable, BTreeSet<i64>>,
+    ) {
+        let compared = self
+            .quotient_graph
+            .get_node_mapping()
+            .iter()
+            .filter(|(dtv, _)| dtv.get_field_labels().is_empty())
+            .filter_map(|(dtv, idx)| {
+                comparison_constants
+                    .get(dtv.get_base_variable())
+                    .map(|constants| (*idx, constants))
+            })
+            .collect::<Vec<_>>();
+
+        for (idx, constants) in compared {
+            if let Some(bounds) = self.quotient_graph.get_graph_mut().node_weight_mut(idx) {
+                *bounds = bounds.with_constants(constants);
+            }
+        }
+    }
+}
+
 impl<U: Display + Clone + std::cmp::PartialEq + AbstractMagma<Additive>> SketchGraph<U> {
     fn replace_dtv(&mut self, dtv: &DerivedTypeVar, sketch: Sketch<U>) {
         self.quotient_graph
@@ -2332,7 +2471,7 @@ impl<T: AbstractMagma<Additive> + std::cmp::PartialEq> SketchGraph<T> {
                             *dst,
                             Field {
                                 offset: field.offset + disp,
-                                size: field.size,
+                                ..field.clone()
                             },
                         )
                     })
@@ -2528,7 +2667,7 @@ impl<T: AbstractMagma<Additive> + std::cmp::PartialEq> SketchGraph<T> {
 mod test {
 
     use std::{
-        collections::{BTreeSet, HashSet},
+        collections::{BTreeMap, BTreeSet, HashMap, HashSet},
         path::{Path, PathBuf},
     };
 
@@ -2553,7 +2692,9 @@ mod test {
         util::{constraint_set_to_subtys, FileDebugLogger},
     };
 
-    use super::{insert_dtv, LatticeBounds, SCCSketchsBuilder, Sketch, SketchBuilder};
+    use super::{
+        insert_dtv, LatticeBounds, PolymorphismPolicy, SCCSketchsBuilder, Sketch, SketchBuilder,
+    };
 
     fn test_data_dir<P: AsRef<Path>>(pth: P) -> String {
         let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -2755,7 +2896,9 @@ mod test {
             ],
             &lat,
             nd_set,
+            BTreeMap::new(),
             FileDebugLogger::default(),
+            PolymorphismPolicy::default(),
         );
 
         skb.build().expect("Should succeed in building sketch");
@@ -2887,7 +3030,9 @@ mod test {
             ],
             &lat,
             nd_set,
+            BTreeMap::new(),
             FileDebugLogger::default(),
+            PolymorphismPolicy::default(),
         );
 
         skb.build().expect("Should succeed in building sketch");
@@ -2937,6 +3082,159 @@ mod test {
         assert_eq!(wt.lower_bound.get_name(), "bottom");
     }
 
+    #[derive(serde::Deserialize)]
+    struct ExpectedSCC {
+        scc: Vec<Tid>,
+        constraints: Vec<SubtypeConstraint>,
+    }
+
+    fn polymorphism_test_file(dir: &str, fname: &str) -> String {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push(dir);
+        d.push("polymorphism_tests");
+        d.push(fname);
+        d.to_string_lossy().into_owned()
+    }
+
+    fn build_prevent_unification_sketches<'a>(
+        lattice: &'a EnumeratedNamedLattice,
+        policy: PolymorphismPolicy,
+    ) -> SCCSketchsBuilder<'a, CustomLatticeElement, EnumeratedNamedLattice> {
+        let fl = std::fs::File::open(polymorphism_test_file(
+            "expected_output",
+            "unification_expected_sccs.json",
+        ))
+        .expect("expected sccs should open");
+        let sccs: Vec<ExpectedSCC> = serde_json::from_reader(fl).expect("should parse sccs");
+
+        let fl = std::fs::File::open(polymorphism_test_file(
+            "test_data",
+            "prevent_unification_additional_constraints.json",
+        ))
+        .expect("additional constraints should open");
+        let additional: Vec<SubtypeConstraint> =
+            serde_json::from_reader(fl).expect("should parse additional constraints");
+
+        let mut cg: CallGraph = DiGraph::new();
+        let nodes = sccs
+            .iter()
+            .map(|scc| {
+                (
+                    scc.scc[0].get_str_repr().to_owned(),
+                    cg.add_node(scc.scc[0].clone()),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        // caller1 and caller2 call alias_id which calls id
+        cg.add_edge(nodes["sub_00000010"], nodes["sub_00000004"], ());
+        cg.add_edge(nodes["sub_0000001c"], nodes["sub_00000004"], ());
+        cg.add_edge(nodes["sub_00000004"], nodes["sub_00000000"], ());
+
+        let scc_constraints = sccs
+            .into_iter()
+            .map(|scc| {
+                let tvar = TypeVariable::new(scc.scc[0].get_str_repr().to_owned());
+                let mut constraints = scc.constraints.into_iter().collect::<BTreeSet<_>>();
+                constraints.extend(
+                    additional
+                        .iter()
+                        .filter(|cons| cons.lhs.get_base_variable() == &tvar)
+                        .cloned(),
+                );
+                SCCConstraints {
+                    scc: scc.scc,
+                    constraints,
+                }
+            })
+            .collect();
+
+        let nd_set = lattice
+            .get_nds()
+            .iter()
+            .map(|x| TypeVariable::new(x.0.clone()))
+            .collect::<HashSet<TypeVariable>>();
+
+        let mut skb = SCCSketchsBuilder::new(
+            cg,
+            scc_constraints,
+            lattice,
+            nd_set,
+            BTreeMap::new(),
+            FileDebugLogger::default(),
+            policy,
+        );
+        skb.build().expect("Should succeed in building sketch");
+        skb
+    }
+
+    #[test]
+    fn test_polymorphism_policies_on_prevent_unification() {
+        init();
+        let (lattice, _) = InferenceJob::parse_lattice_json(
+            &polymorphism_test_file("test_data", "simple_lattice.json"),
+            vec![],
+        )
+        .expect("lattice should parse");
+
+        let in0 = DerivedTypeVar::create_with_path(
+            TypeVariable::new("sub_00000004".to_owned()),
+            vec![FieldLabel::In(0)],
+        );
+
+        for policy in [
+            PolymorphismPolicy::RefineFormals,
+            PolymorphismPolicy::Monomorphic,
+            PolymorphismPolicy::Polymorphic,
+        ] {
+            let skb = build_prevent_unification_sketches(&lattice, policy);
+
+            let alias_sketch = skb.get_built_sketch_from_scc(&[Tid::create(
+                "sub_00000004".to_owned(),
+                "00000004".to_owned(),
+            )]);
+            let alias_in0 = alias_sketch
+                .quotient_graph
+                .get_node(&in0)
+                .expect("alias should have an in parameter");
+            let alias_in0_is_refined = alias_sketch
+                .quotient_graph
+                .get_graph()
+                .edges_directed(*alias_in0, Outgoing)
+                .next()
+                .is_some();
+            assert_eq!(
+                alias_in0_is_refined,
+                policy != PolymorphismPolicy::Polymorphic,
+                "refinement of alias_id.in_0 under {:?}",
+                policy
+            );
+
+            let global_graph = skb
+                .build_global_type_graph()
+                .expect("Global graph should build");
+            let caller_in0 = |name: &str| {
+                let idx = global_graph
+                    .get_node_index_for_variable(&DerivedTypeVar::new(TypeVariable::new(
+                        name.to_owned(),
+                    )))
+                    .expect("caller should be labeled");
+                find_node(
+                    global_graph.get_graph().get_graph(),
+                    idx,
+                    [FieldLabel::In(0)].iter(),
+                )
+                .expect("caller should have an in parameter")
+            };
+
+            assert_eq!(
+                caller_in0("sub_00000010") == caller_in0("sub_0000001c"),
+                policy == PolymorphismPolicy::Monomorphic,
+                "unification of caller parameters under {:?}",
+                policy
+            );
+        }
+    }
+
     #[test]
     fn test_simple_subgraph_equiv() {
         init();
@@ -3012,7 +3310,9 @@ mod test {
             ],
             &lat,
             nd_set,
+            BTreeMap::new(),
             FileDebugLogger::default(),
+            PolymorphismPolicy::default(),
         );
 
         skb.build().expect("able to build sketches");
@@ -3160,7 +3460,9 @@ mod test {
             ],
             &lat,
             nd_set,
+            BTreeMap::new(),
             FileDebugLogger::default(),
+            PolymorphismPolicy::default(),
         );
 
         skb.build().expect("Should succeed in building sketch");
@@ -3351,6 +3653,7 @@ mod test {
         let def = LatticeBounds {
             upper_bound: top,
             lower_bound: bot.clone(),
+            constants: BTreeSet::new(),
         };
 
         let representing = DerivedTypeVar::new(TypeVariable::new_global("something".to_owned()));
@@ -3363,6 +3666,7 @@ mod test {
             LatticeBounds {
                 upper_bound: nds.get("int").unwrap().clone(),
                 lower_bound: bot,
+                constants: BTreeSet::new(),
             },
         );
 
@@ -3421,6 +3725,7 @@ mod test {
             LatticeBounds {
                 upper_bound: bound.clone(),
                 lower_bound: bound,
+                constants: BTreeSet::new(),
             },
         )
     }
@@ -1,6 +1,17 @@
+use std::collections::{BTreeSet, HashSet};
 use std::path::PathBuf;
 use std::vec::Vec;
 
+use cwe_checker_lib::intermediate_representation::{Arg, ByteSize, Expression, Project, Variable};
+
+use crate::constraints::{SubtypeConstraint, TypeVariable};
+use crate::inference_job::InferenceJob;
+use crate::solver::type_lattice::{
+    CustomLatticeElement, EnumeratedNamedLattice, LatticeDefinition,
+};
+use crate::solver::type_sketch::{insert_dtv, LatticeBounds, SketchBuilder, SketchGraph};
+use crate::util::FileDebugLogger;
+
 fn get_test_data_path(fname: &str) -> PathBuf {
     let mut pbuf = PathBuf::new();
     pbuf.push(env!("CARGO_MANIFEST_DIR"));
@@ -16,3 +27,71 @@ pub fn open_test_file(fname: &str) -> std::fs::File {
 pub fn test_file_to_bytes(fname: &str) -> Vec<u8> {
     std::fs::read(get_test_data_path(fname)).unwrap()
 }
+
+/// Loads the composite_return project, which tests use as a template for hand built procedures.
+pub fn load_composite_return_project() -> Project {
+    let d = get_test_data_path("composite_return");
+    let bin = InferenceJob::parse_binary(&d.join("composite_return.so").to_string_lossy())
+        .expect("should be able to parse binary");
+    InferenceJob::parse_project(&d.join("composite_return.json").to_string_lossy(), &bin)
+        .expect("should be able to parse project")
+}
+
+/// A non-temporary register variable of the given size in bytes.
+pub fn register(name: &str, size: u64) -> Variable {
+    Variable {
+        name: name.to_owned(),
+        size: ByteSize::new(size),
+        is_temp: false,
+    }
+}
+
+/// A register parameter or return of the given size in bytes.
+pub fn register_arg(name: &str, size: u64) -> Arg {
+    Arg::Register {
+        expr: Expression::Var(register(name, size)),
+        data_type: None,
+    }
+}
+
+/// A lattice between "top" and "bottom" with the given (subtype, supertype) relations.
+pub fn test_lattice(relations: &[(&str, &str)], weakest_integral: &str) -> LatticeDefinition {
+    LatticeDefinition::new(
+        relations
+            .iter()
+            .map(|(sub, sup)| ((*sub).to_owned(), (*sup).to_owned()))
+            .collect(),
+        "top".to_owned(),
+        "bottom".to_owned(),
+        weakest_integral.to_owned(),
+    )
+}
+
+/// Builds the labeled sketch graph for the constraints over the given lattice.
+pub fn sketch_for(
+    cons: &BTreeSet<SubtypeConstraint>,
+    def: LatticeDefinition,
+) -> (
+    EnumeratedNamedLattice,
+    SketchGraph<LatticeBounds<CustomLatticeElement>>,
+) {
+    let lattice = def.generate_lattice();
+    let elems: HashSet<_> = lattice
+        .get_nds()
+        .iter()
+        .map(|(nm, _)| TypeVariable::new(nm.clone()))
+        .collect();
+
+    let sg = SketchBuilder::new(
+        &lattice,
+        &elems,
+        &|dtv, mpgrph| {
+            insert_dtv(&lattice, mpgrph, dtv.clone());
+            Ok(())
+        },
+        FileDebugLogger::default(),
+    )
+    .build_and_label_constraints(cons)
+    .expect("should build sketch graph");
+    (lattice, sg)
+}